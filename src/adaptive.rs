@@ -0,0 +1,199 @@
+use rand::Rng;
+use rand::prelude::IndexedRandom;
+use std::collections::HashMap;
+
+use crate::key_stats::{KeyStat, KeyStats};
+
+/// Words in one adaptive test
+pub const WORD_NUMBER: usize = 50;
+
+/// Keys and bigrams pressed fewer times than this are not judged yet
+const MIN_PRESSES: u32 = 5;
+
+/// How many of the weakest keys and bigrams a test targets
+const TARGETS: usize = 5;
+
+/// An error costs as much as typing a key ten times slower than average
+const ERROR_WEIGHT: f64 = 10.0;
+
+/// A key is only targeted when its weakness is this much above the mean,
+/// so an even typist gets plain words instead of drills
+const STANDOUT: f64 = 1.2;
+
+/// Higher for keys that are missed more often or take longer than `avg_ms`
+fn weakness(stat: &KeyStat, avg_ms: f64) -> f64 {
+    let slowness = match stat.avg_ms() {
+        Some(ms) if avg_ms > 0.0 => ms / avg_ms,
+        _ => 1.0,
+    };
+    stat.error_rate() * ERROR_WEIGHT + slowness
+}
+
+fn average_ms(stats: &KeyStats) -> f64 {
+    let (total_ms, timed) = stats
+        .keys
+        .values()
+        .fold((0, 0), |(ms, n), stat| (ms + stat.total_ms, n + stat.timed));
+    if timed == 0 {
+        return 0.0;
+    }
+    total_ms as f64 / timed as f64
+}
+
+fn weakest<K: Clone + Ord>(stats: &HashMap<K, KeyStat>, avg_ms: f64, keep: impl Fn(&K) -> bool) -> Vec<(K, f64)> {
+    let mut scored: Vec<(K, f64)> = stats
+        .iter()
+        .filter(|(key, stat)| stat.presses >= MIN_PRESSES && keep(key))
+        .map(|(key, stat)| (key.clone(), weakness(stat, avg_ms)))
+        .collect();
+    if scored.is_empty() {
+        return scored;
+    }
+    let mean = scored.iter().map(|(_, w)| w).sum::<f64>() / scored.len() as f64;
+    scored.retain(|(_, w)| *w > mean * STANDOUT);
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+    scored.truncate(TARGETS);
+    scored
+}
+
+/// Letters that stand out as weak, weakest first, with their weakness
+pub fn weak_keys(stats: &KeyStats) -> Vec<(char, f64)> {
+    weakest(&stats.keys, average_ms(stats), |key| key.is_alphabetic())
+}
+
+/// Letter pairs that stand out as weak, weakest first, with their weakness
+pub fn weak_bigrams(stats: &KeyStats) -> Vec<(String, f64)> {
+    weakest(&stats.bigrams, average_ms(stats), |_| true)
+}
+
+/// How well a word trains the weak keys and bigrams, 0 when it has none of them
+fn word_score(word: &str, keys: &[(char, f64)], bigrams: &[(String, f64)]) -> f64 {
+    let lower = word.to_lowercase();
+    let key_score: f64 = lower
+        .chars()
+        .filter_map(|c| keys.iter().find(|(k, _)| *k == c).map(|(_, w)| *w))
+        .sum();
+    let bigram_score: f64 = bigrams
+        .iter()
+        .filter(|(bigram, _)| lower.contains(bigram.as_str()))
+        .map(|(_, w)| 2.0 * w)
+        .sum();
+    key_score + bigram_score
+}
+
+/// Words from `word_list` that contain weak keys or bigrams, with their weight
+pub fn candidate_words<'a>(stats: &KeyStats, word_list: &'a [String]) -> Vec<(&'a String, f64)> {
+    let keys = weak_keys(stats);
+    let bigrams = weak_bigrams(stats);
+    word_list
+        .iter()
+        .map(|word| (word, word_score(word, &keys, &bigrams)))
+        .filter(|(_, score)| *score > 0.0)
+        .collect()
+}
+
+/// Made up words built from the weak bigrams and keys
+fn synthetic_words(stats: &KeyStats, word_number: usize) -> Vec<String> {
+    let mut clusters: Vec<String> = weak_bigrams(stats).into_iter().map(|(b, _)| b).collect();
+    clusters.extend(weak_keys(stats).into_iter().map(|(k, _)| k.to_string()));
+    if clusters.is_empty() {
+        clusters = ('a'..='z').map(|c| c.to_string()).collect();
+    }
+    let mut rng = rand::rng();
+    (0..word_number)
+        .map(|_| {
+            let parts = rng.random_range(2..5);
+            (0..parts)
+                .filter_map(|_| clusters.choose(&mut rng))
+                .map(String::as_str)
+                .collect()
+        })
+        .collect()
+}
+
+/// Practice text aimed at the weakest keys and bigrams in `stats`. Prefers real words
+/// from `word_list` and falls back to letter clusters when none of them fit.
+pub fn create_words(stats: &KeyStats, word_list: &[String], word_number: usize) -> String {
+    let candidates = candidate_words(stats, word_list);
+    let mut rng = rand::rng();
+    let words: Vec<String> = if !candidates.is_empty() {
+        (0..word_number)
+            .filter_map(|_| candidates.choose_weighted(&mut rng, |(_, score)| *score).ok())
+            .map(|(word, _)| word.to_string())
+            .collect()
+    } else if weak_keys(stats).is_empty() && !word_list.is_empty() {
+        // Nothing to target yet, so any common words will do
+        (0..word_number)
+            .filter_map(|_| word_list.choose(&mut rng).cloned())
+            .collect()
+    } else {
+        synthetic_words(stats, word_number)
+    };
+    words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(presses: u32, errors: u32, avg_ms: u64) -> KeyStat {
+        KeyStat { presses, errors, timed: presses, total_ms: avg_ms * presses as u64 }
+    }
+
+    fn sample_stats() -> KeyStats {
+        let mut stats = KeyStats::default();
+        stats.keys.insert('a', stat(50, 0, 100));
+        stats.keys.insert('e', stat(50, 1, 100));
+        stats.keys.insert('q', stat(20, 8, 300));
+        stats.keys.insert('z', stat(20, 4, 250));
+        // Not enough presses to be judged
+        stats.keys.insert('x', stat(2, 2, 900));
+        stats.bigrams.insert("qu".to_string(), stat(10, 5, 400));
+        stats.bigrams.insert("th".to_string(), stat(30, 0, 100));
+        stats
+    }
+
+    #[test]
+    fn test_weak_keys_order() {
+        let keys: Vec<char> = weak_keys(&sample_stats()).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec!['q', 'z']);
+        let bigrams: Vec<String> = weak_bigrams(&sample_stats()).into_iter().map(|(b, _)| b).collect();
+        assert_eq!(bigrams, vec!["qu".to_string()]);
+    }
+
+    #[test]
+    fn test_even_typist_has_no_weak_keys() {
+        let mut stats = KeyStats::default();
+        for (i, key) in ('a'..='h').enumerate() {
+            stats.keys.insert(key, stat(40, (i % 2) as u32, 95 + i as u64 * 2));
+        }
+        assert!(weak_keys(&stats).is_empty());
+
+        let word_list: Vec<String> = vec!["hill".to_string()];
+        assert_eq!(create_words(&stats, &word_list, 3), "hill hill hill");
+    }
+
+    #[test]
+    fn test_prefers_words_with_weak_keys() {
+        let word_list: Vec<String> = ["quiz", "queen", "hill"].iter().map(|w| w.to_string()).collect();
+        let candidates = candidate_words(&sample_stats(), &word_list);
+        let words: Vec<&str> = candidates.iter().map(|(w, _)| w.as_str()).collect();
+        assert_eq!(words, vec!["quiz", "queen"]);
+        assert!(candidates[0].1 > candidates[1].1);
+
+        let text = create_words(&sample_stats(), &word_list, 20);
+        assert_eq!(text.split(' ').count(), 20);
+        assert!(text.split(' ').all(|w| w == "quiz" || w == "queen"));
+    }
+
+    #[test]
+    fn test_falls_back_to_clusters() {
+        let word_list: Vec<String> = vec!["hill".to_string()];
+        let mut stats = KeyStats::default();
+        stats.keys.insert('a', stat(50, 0, 100));
+        stats.keys.insert('q', stat(20, 8, 300));
+        let text = create_words(&stats, &word_list, 10);
+        assert_eq!(text.split(' ').count(), 10);
+        assert!(text.chars().all(|c| c == 'q' || c == ' '));
+    }
+}
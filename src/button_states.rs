@@ -31,6 +31,9 @@ pub struct ButtonStates {
     pub words: ButtonState,
     pub quote: ButtonState,
     pub practice: ButtonState,
+    pub adaptive: ButtonState,
+    pub code: ButtonState,
+    pub texts: ButtonState,
     pub wiki_mode: ButtonState,
 }
 
@@ -53,6 +56,9 @@ impl ButtonStates {
             divider1: btn("|", "|", "|"),
             quote: btn("quote", "quote", "quote"),
             practice: btn("practice", "practice", "practice"),
+            adaptive: btn("adaptive", "adaptive", "adapt"),
+            code: btn("code", "code", "code"),
+            texts: btn("texts", "texts", "texts"),
             wiki_mode: btn("wiki", "wikipedia", "wiki"),
             time: btn("time", "time", "time"),
             words: btn("words", "words", "words"),
@@ -76,6 +82,9 @@ impl ButtonStates {
             &self.words,
             &self.quote,
             &self.practice,
+            &self.adaptive,
+            &self.code,
+            &self.texts,
             &self.wiki_mode,
         ]
     }
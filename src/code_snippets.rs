@@ -0,0 +1,425 @@
+use rand::prelude::IndexedRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::leaderboard::{get_config_dir, LeaderboardError};
+
+const BUNDLED_SNIPPETS: &str = include_str!("../assets/code_snippets.json");
+
+/// Lines taken from a user source file for one test
+pub const SNIPPET_LINES: usize = 12;
+
+/// Spaces a tab is expanded to, so every frontend shows the same indentation
+const TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CodeLanguage {
+    Rust,
+    Python,
+    JavaScript,
+    Go,
+    C,
+}
+
+impl CodeLanguage {
+    pub fn all() -> &'static [CodeLanguage] {
+        &[
+            CodeLanguage::Rust,
+            CodeLanguage::Python,
+            CodeLanguage::JavaScript,
+            CodeLanguage::Go,
+            CodeLanguage::C,
+        ]
+    }
+
+    pub fn count() -> usize {
+        Self::all().len()
+    }
+
+    pub fn from_str(s: &str) -> Option<CodeLanguage> {
+        match s.to_lowercase().as_str() {
+            "rust" | "rs" => Some(CodeLanguage::Rust),
+            "python" | "py" => Some(CodeLanguage::Python),
+            "javascript" | "js" => Some(CodeLanguage::JavaScript),
+            "go" | "golang" => Some(CodeLanguage::Go),
+            "c" => Some(CodeLanguage::C),
+            _ => None,
+        }
+    }
+
+    pub fn from_extension(ext: &str) -> Option<CodeLanguage> {
+        match ext.to_lowercase().as_str() {
+            "rs" => Some(CodeLanguage::Rust),
+            "py" => Some(CodeLanguage::Python),
+            "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" => Some(CodeLanguage::JavaScript),
+            "go" => Some(CodeLanguage::Go),
+            "c" | "h" => Some(CodeLanguage::C),
+            _ => None,
+        }
+    }
+
+    /// Next language in `all`, None (any language) after the last one
+    pub fn next(language: Option<CodeLanguage>) -> Option<CodeLanguage> {
+        let all = Self::all();
+        match language {
+            None => all.first().copied(),
+            Some(language) => {
+                let i = all.iter().position(|l| *l == language).unwrap_or(0);
+                all.get(i + 1).copied()
+            }
+        }
+    }
+
+    fn keywords(&self) -> &'static [&'static str] {
+        match self {
+            CodeLanguage::Rust => &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+                "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+                "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+                "trait", "true", "type", "unsafe", "use", "where", "while",
+            ],
+            CodeLanguage::Python => &[
+                "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+                "elif", "else", "except", "False", "finally", "for", "from", "global", "if",
+                "import", "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise",
+                "return", "True", "try", "while", "with", "yield",
+            ],
+            CodeLanguage::JavaScript => &[
+                "async", "await", "break", "case", "catch", "class", "const", "continue", "default",
+                "delete", "do", "else", "export", "extends", "false", "finally", "for", "function",
+                "if", "import", "in", "instanceof", "let", "new", "null", "of", "return", "static",
+                "super", "switch", "this", "throw", "true", "try", "typeof", "undefined", "var",
+                "void", "while", "yield",
+            ],
+            CodeLanguage::Go => &[
+                "break", "case", "chan", "const", "continue", "default", "defer", "else",
+                "fallthrough", "false", "for", "func", "go", "goto", "if", "import", "interface",
+                "map", "nil", "package", "range", "return", "select", "struct", "switch", "true",
+                "type", "var",
+            ],
+            CodeLanguage::C => &[
+                "auto", "break", "case", "char", "const", "continue", "default", "define", "do",
+                "double", "else", "enum", "extern", "float", "for", "goto", "if", "include",
+                "int", "long", "NULL", "register", "return", "short", "signed", "sizeof",
+                "static", "struct", "switch", "typedef", "union", "unsigned", "void", "volatile",
+                "while",
+            ],
+        }
+    }
+
+    fn line_comment(&self) -> &'static str {
+        match self {
+            CodeLanguage::Python => "#",
+            _ => "//",
+        }
+    }
+}
+
+impl Display for CodeLanguage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeLanguage::Rust => write!(f, "Rust"),
+            CodeLanguage::Python => write!(f, "Python"),
+            CodeLanguage::JavaScript => write!(f, "JavaScript"),
+            CodeLanguage::Go => write!(f, "Go"),
+            CodeLanguage::C => write!(f, "C"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSnippet {
+    pub language: CodeLanguage,
+    pub text: String,
+}
+
+/// Syntax class of a reference char, used to color code before it is typed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+    Bracket,
+}
+
+/// Expands tabs, drops trailing whitespace, blank lines around the snippet and
+/// the indentation every line shares
+pub fn clean(text: &str) -> String {
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| line.replace('\t', &" ".repeat(TAB_WIDTH)).trim_end().to_string())
+        .collect();
+    let indent = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().take_while(|c| c.is_whitespace()).count())
+        .min()
+        .unwrap_or(0);
+
+    let mut cleaned: Vec<&str> = Vec::new();
+    for line in &lines {
+        // One blank line is enough to split blocks
+        if line.is_empty() && cleaned.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        // Indentation can hold multi-byte whitespace, so skip chars rather than bytes
+        let start = line.char_indices().nth(indent).map_or(line.len(), |(i, _)| i);
+        cleaned.push(&line[start..]);
+    }
+    while cleaned.last().is_some_and(|line| line.is_empty()) {
+        cleaned.pop();
+    }
+    cleaned.join("\n")
+}
+
+pub fn bundled_snippets() -> Vec<CodeSnippet> {
+    let snippets: HashMap<CodeLanguage, Vec<String>> =
+        serde_json::from_str(BUNDLED_SNIPPETS).unwrap_or_default();
+    CodeLanguage::all()
+        .iter()
+        .flat_map(|language| {
+            snippets
+                .get(language)
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|text| CodeSnippet { language: *language, text: clean(&text) })
+        })
+        .collect()
+}
+
+pub fn get_code_dir() -> Result<PathBuf, LeaderboardError> {
+    let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
+        std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+    ))?;
+    let code_dir = config_dir.join("code");
+    fs::create_dir_all(&code_dir)?;
+    Ok(code_dir)
+}
+
+/// Source files in the code directory and its subdirectories, with their language
+pub fn list_user_files() -> Result<Vec<(PathBuf, CodeLanguage)>, LeaderboardError> {
+    fn walk(dir: &Path, files: &mut Vec<(PathBuf, CodeLanguage)>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+            if path.is_dir() {
+                walk(&path, files);
+            } else if let Some(language) = path
+                .extension()
+                .and_then(|ext| CodeLanguage::from_extension(&ext.to_string_lossy()))
+            {
+                files.push((path, language));
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(&get_code_dir()?, &mut files);
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Up to `SNIPPET_LINES` lines of a source file, starting at a random top level line
+pub fn snippet_from_source<R: Rng + ?Sized>(source: &str, rng: &mut R) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let starts: Vec<usize> = (0..lines.len())
+        .filter(|&i| !lines[i].trim().is_empty() && !lines[i].starts_with([' ', '\t', '}', ')', ']']))
+        .collect();
+    let start = starts.choose(rng).copied().unwrap_or(0);
+    let end = usize::min(start + SNIPPET_LINES, lines.len());
+    // Stop at the last blank line so the snippet ends with a whole block
+    let end = (start + 1..end)
+        .rev()
+        .find(|&i| lines[i].trim().is_empty())
+        .filter(|&i| i > start + SNIPPET_LINES / 2)
+        .unwrap_or(end);
+    clean(&lines[start..end].join("\n"))
+}
+
+/// Random snippet in `language` (any language when None), from the bundled snippets
+/// and the files in the code directory
+pub fn random_snippet(language: Option<CodeLanguage>) -> CodeSnippet {
+    let mut rng = rand::rng();
+    let matches = |l: &CodeLanguage| language.is_none_or(|language| language == *l);
+
+    let bundled: Vec<CodeSnippet> = bundled_snippets().into_iter().filter(|s| matches(&s.language)).collect();
+    let files: Vec<(PathBuf, CodeLanguage)> = list_user_files()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, l)| matches(l))
+        .collect();
+
+    // Every user file is as likely as a bundled snippet
+    let pick = rng.random_range(0..(bundled.len() + files.len()).max(1));
+    if let Some((path, language)) = pick.checked_sub(bundled.len()).and_then(|i| files.get(i)) {
+        if let Ok(source) = fs::read_to_string(path) {
+            let text = snippet_from_source(&source, &mut rng);
+            if !text.trim().is_empty() {
+                return CodeSnippet { language: *language, text };
+            }
+        }
+    }
+    bundled
+        .get(pick)
+        .or_else(|| bundled.first())
+        .cloned()
+        .unwrap_or_else(|| CodeSnippet {
+            language: CodeLanguage::Rust,
+            text: "fn main() {\n    println!(\"Hello, world!\");\n}".to_string(),
+        })
+}
+
+/// Syntax class of every char of `text`
+pub fn highlight(text: &str, language: CodeLanguage) -> Vec<TokenKind> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut kinds = vec![TokenKind::Plain; chars.len()];
+    let line_comment: Vec<char> = language.line_comment().chars().collect();
+    let block_comments = language != CodeLanguage::Python;
+    let starts_with = |i: usize, pattern: &[char]| chars[i..].starts_with(pattern);
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let kind = if starts_with(i, &line_comment) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            TokenKind::Comment
+        } else if block_comments && starts_with(i, &['/', '*']) {
+            i += 2;
+            while i < chars.len() && !starts_with(i, &['*', '/']) {
+                i += 1;
+            }
+            i = usize::min(i + 2, chars.len());
+            TokenKind::Comment
+        } else if is_quote(&chars, i, language) {
+            i += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i = usize::min(i + 1, chars.len());
+            TokenKind::String
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_') {
+                i += 1;
+            }
+            TokenKind::Number
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if language.keywords().contains(&word.as_str()) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Plain
+            }
+        } else {
+            i += 1;
+            if "()[]{}".contains(c) {
+                TokenKind::Bracket
+            } else {
+                TokenKind::Plain
+            }
+        };
+        kinds[start..i].fill(kind);
+    }
+    kinds
+}
+
+/// Quote opening a string or char literal. A Rust `'` is a lifetime unless it closes a char.
+fn is_quote(chars: &[char], i: usize, language: CodeLanguage) -> bool {
+    match chars[i] {
+        '"' => true,
+        '`' => matches!(language, CodeLanguage::JavaScript | CodeLanguage::Go),
+        '\'' if language == CodeLanguage::Rust => {
+            chars.get(i + 2) == Some(&'\'') || (chars.get(i + 1) == Some(&'\\') && chars[i + 1..].iter().take(8).skip(2).any(|c| *c == '\''))
+        }
+        '\'' => true,
+        _ => false,
+    }
+}
+
+/// Position of the bracket matching the one at `pos`, skipping brackets in strings and comments
+pub fn matching_bracket(chars: &[char], kinds: &[TokenKind], pos: usize) -> Option<usize> {
+    const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+    if kinds.get(pos) != Some(&TokenKind::Bracket) {
+        return None;
+    }
+    let c = chars[pos];
+    let is_bracket = |i: usize| kinds.get(i) == Some(&TokenKind::Bracket);
+    if let Some(&(open, close)) = PAIRS.iter().find(|(open, _)| *open == c) {
+        let mut depth = 0;
+        for i in pos..chars.len() {
+            if !is_bracket(i) {
+                continue;
+            }
+            if chars[i] == open {
+                depth += 1;
+            } else if chars[i] == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+        }
+    } else if let Some(&(open, close)) = PAIRS.iter().find(|(_, close)| *close == c) {
+        let mut depth = 0;
+        for i in (0..=pos).rev() {
+            if !is_bracket(i) {
+                continue;
+            }
+            if chars[i] == close {
+                depth += 1;
+            } else if chars[i] == open {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_keeps_relative_indentation() {
+        let text = "\n\t\tif x {\n\t\t\treturn 1;   \n\n\n\t\t}\n\n";
+        assert_eq!(clean(text), "if x {\n    return 1;\n\n}");
+    }
+
+    #[test]
+    fn test_clean_mixed_multibyte_indentation() {
+        let text = "\u{a0}\u{a0}let a = 1;\n  let b = 2;\n\u{3000} c";
+        assert_eq!(clean(text), "let a = 1;\nlet b = 2;\nc");
+    }
+
+    #[test]
+    fn test_highlight_and_brackets() {
+        let text = "fn f(a: &'a str) { \"(\" } // x";
+        let chars: Vec<char> = text.chars().collect();
+        let kinds = highlight(text, CodeLanguage::Rust);
+        assert_eq!(kinds[0], TokenKind::Keyword);
+        assert_eq!(kinds[3], TokenKind::Plain);
+        // The lifetime is not a char literal
+        assert_eq!(kinds[9], TokenKind::Plain);
+        assert_eq!(kinds[19], TokenKind::String);
+        assert_eq!(kinds[text.len() - 1], TokenKind::Comment);
+        // The bracket in the string is skipped
+        assert_eq!(matching_bracket(&chars, &kinds, 17), Some(23));
+        assert_eq!(matching_bracket(&chars, &kinds, 15), Some(4));
+        assert!(bundled_snippets().len() >= CodeLanguage::count());
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use crate::code_snippets::TokenKind;
 use crate::custom_colors::MyColor;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -202,6 +203,16 @@ impl ColorScheme {
         }
         .into()
     }
+
+    /// Color of code that is not typed yet, by its syntax class
+    pub fn syntax_color<C: From<MyColor>>(&self, kind: TokenKind) -> C {
+        match kind {
+            TokenKind::Keyword => self.dimmer_main(),
+            TokenKind::String | TokenKind::Number => self.chart_color(),
+            TokenKind::Comment => self.border_color(),
+            TokenKind::Plain | TokenKind::Bracket => self.ref_color(),
+        }
+    }
 }
 
 impl Default for ColorScheme {
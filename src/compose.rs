@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+use unicode_normalization::char::{compose, is_combining_mark};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::normalization::Strictness;
+
+/// A held letter is typed on its own when no combining mark follows within this time
+const HOLD: Duration = Duration::from_millis(250);
+
+/// Combining mark of a dead key that terminals pass on as a spacing accent
+fn dead_key_mark(c: char) -> Option<char> {
+    match c {
+        '´' => Some('\u{301}'),
+        '¨' => Some('\u{308}'),
+        '¸' => Some('\u{327}'),
+        '¯' => Some('\u{304}'),
+        'ˆ' => Some('\u{302}'),
+        'ˇ' => Some('\u{30c}'),
+        '˘' => Some('\u{306}'),
+        '˙' => Some('\u{307}'),
+        '˚' => Some('\u{30a}'),
+        '˛' => Some('\u{328}'),
+        '˜' => Some('\u{303}'),
+        '˝' => Some('\u{30b}'),
+        _ if is_combining_mark(c) => Some(c),
+        _ => None,
+    }
+}
+
+/// Accented letter made of `first` and `second`, typed in either order:
+/// a dead key or combining mark with a letter, or a letter with a combining mark after it
+fn compose_pair(first: char, second: char) -> Option<char> {
+    if let Some(mark) = dead_key_mark(first) {
+        return compose(second, mark);
+    }
+    if is_combining_mark(second) {
+        return compose(first, second);
+    }
+    None
+}
+
+/// Turns dead keys and decomposed input into single chars before they reach the session.
+///
+/// A dead key or combining mark is held back, and typed together with the next key when the
+/// two compose. Under `Strictness::Exact` so is a letter that is the base of the accented
+/// letter under the cursor, the other strictness levels already accept the plain letter.
+///
+/// Chars come out with the time their key was pressed, so a held key keeps its own timing.
+#[derive(Debug, Clone, Default)]
+pub struct Composer {
+    pending: Option<(char, Instant)>,
+}
+
+impl Composer {
+    /// Chars to type for a key press, `expected` is the reference char under the cursor
+    pub fn feed(&mut self, c: char, expected: Option<char>, strictness: Strictness) -> Vec<(char, Instant)> {
+        let now = Instant::now();
+        let mut chars = Vec::new();
+        if let Some((held, pressed)) = self.pending.take() {
+            if let Some(composed) = compose_pair(held, c) {
+                return vec![(composed, now)];
+            }
+            chars.push((held, pressed));
+        }
+
+        // The letter was typed on its own, its accent doesn't count here
+        if strictness != Strictness::Exact && is_combining_mark(c) {
+            return chars;
+        }
+        let starts_expected = strictness == Strictness::Exact
+            && expected.is_some_and(|e| e != c && e.nfd().next() == Some(c));
+        if Some(c) != expected && (dead_key_mark(c).is_some() || starts_expected) {
+            self.pending = Some((c, now));
+        } else {
+            chars.push((c, now));
+        }
+        chars
+    }
+
+    /// Held letter that no mark followed in time, dead keys wait for the next key
+    pub fn flush_stale(&mut self) -> Option<(char, Instant)> {
+        let (held, pressed) = self.pending?;
+        if dead_key_mark(held).is_some() || pressed.elapsed() < HOLD {
+            return None;
+        }
+        self.pending = None;
+        Some((held, pressed))
+    }
+
+    /// Drops the held key, true when there was one. A backspace then only deletes it.
+    pub fn cancel(&mut self) -> bool {
+        self.pending.take().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds a key under `Strictness::Exact` and drops the press times
+    fn feed(composer: &mut Composer, c: char, expected: char) -> Vec<char> {
+        composer.feed(c, Some(expected), Strictness::Exact).into_iter().map(|(c, _)| c).collect()
+    }
+
+    #[test]
+    fn test_dead_keys_and_marks_compose() {
+        let mut composer = Composer::default();
+        assert!(feed(&mut composer, '´', 'é').is_empty());
+        assert_eq!(feed(&mut composer, 'e', 'é'), vec!['é']);
+
+        // Decomposed input, the letter is held until the mark comes
+        assert!(feed(&mut composer, 'e', 'é').is_empty());
+        assert_eq!(feed(&mut composer, '\u{301}', 'é'), vec!['é']);
+
+        // No composition, both keys are typed
+        assert!(feed(&mut composer, '¨', 'a').is_empty());
+        assert_eq!(feed(&mut composer, 'q', 'a'), vec!['¨', 'q']);
+    }
+
+    #[test]
+    fn test_plain_keys_pass_through() {
+        let mut composer = Composer::default();
+        assert_eq!(feed(&mut composer, 'e', 'e'), vec!['e']);
+        assert_eq!(feed(&mut composer, '´', '´'), vec!['´']);
+        assert!(feed(&mut composer, 'e', 'è').is_empty());
+        assert!(composer.cancel());
+        assert!(!composer.cancel());
+    }
+
+    #[test]
+    fn test_letters_are_only_held_when_exact() {
+        let mut composer = Composer::default();
+        let keys = composer.feed('e', Some('é'), Strictness::Either);
+        assert_eq!(keys.iter().map(|(c, _)| *c).collect::<Vec<_>>(), vec!['e']);
+        // The accent of the letter already typed is dropped
+        assert!(composer.feed('\u{301}', Some(' '), Strictness::Either).is_empty());
+        assert!(!composer.cancel());
+
+        // Dead keys still compose
+        assert!(composer.feed('´', Some('é'), Strictness::AsciiFold).is_empty());
+        assert_eq!(composer.feed('e', Some('é'), Strictness::AsciiFold)[0].0, 'é');
+    }
+
+    #[test]
+    fn test_held_key_keeps_its_press_time() {
+        let mut composer = Composer::default();
+        let before = Instant::now();
+        assert!(feed(&mut composer, 'e', 'é').is_empty());
+        std::thread::sleep(Duration::from_millis(20));
+        let keys = composer.feed('x', Some('é'), Strictness::Exact);
+        assert_eq!(keys[0].0, 'e');
+        assert!(keys[0].1 < keys[1].1 - Duration::from_millis(15));
+        assert!(keys[0].1 >= before);
+    }
+}
@@ -1,5 +1,12 @@
+use crate::code_snippets::CodeLanguage;
 use crate::color_scheme::ColorScheme;
+use crate::keyboard_layout::{KeyboardLayout, DEFAULT_LAYOUT};
 use crate::language::Language;
+use crate::leaderboard::get_config_dir;
+use crate::normalization::Strictness;
+use crate::quotes::QuoteLength;
+use crate::text_library::DEFAULT_CHUNK_SIZE;
+use crate::word_sampling::SamplingMode;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
@@ -20,6 +27,61 @@ pub struct AppConfig {
     pub color_scheme: ColorScheme,
     pub word_number: usize,
     pub top_words: usize,
+    #[serde(default)]
+    pub pace_ghost: bool,
+    /// Target wpm of the pacer caret, 0 when it is off
+    #[serde(default)]
+    pub pacer_wpm: u32,
+    #[serde(default)]
+    pub adaptive_mode: bool,
+    /// Name of a built-in or custom `KeyboardLayout`
+    #[serde(default = "default_keyboard_layout")]
+    pub keyboard_layout: String,
+    /// Types `keyboard_layout` on a QWERTY keyboard by remapping every key
+    #[serde(default)]
+    pub emulate_layout: bool,
+    #[serde(default)]
+    pub sampling_mode: SamplingMode,
+    /// Number of words before a word can come back, 0 allows immediate repeats
+    #[serde(default)]
+    pub no_repeat_within: usize,
+    #[serde(default)]
+    pub code_mode: bool,
+    /// Language of the code snippets, None picks any of them
+    #[serde(default)]
+    pub code_language: Option<CodeLanguage>,
+    /// Indentation at the start of a line is skipped instead of typed
+    #[serde(default = "default_skip_indent")]
+    pub skip_indent: bool,
+    #[serde(default)]
+    pub library_mode: bool,
+    /// Name of the library text being typed through
+    #[serde(default)]
+    pub library_text: Option<String>,
+    /// Words per test when typing a library text
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    /// How curly quotes, dashes and accented letters are matched
+    #[serde(default)]
+    pub strictness: Strictness,
+    /// Mistakes only in the accent of a letter count as half an error
+    #[serde(default)]
+    pub half_accent_errors: bool,
+    /// Length of the quotes in quote mode, or only favorites
+    #[serde(default)]
+    pub quote_length: QuoteLength,
+}
+
+fn default_keyboard_layout() -> String {
+    DEFAULT_LAYOUT.to_string()
+}
+
+fn default_skip_indent() -> bool {
+    true
+}
+
+fn default_chunk_size() -> usize {
+    DEFAULT_CHUNK_SIZE
 }
 
 impl Default for AppConfig {
@@ -39,23 +101,34 @@ impl Default for AppConfig {
             color_scheme: ColorScheme::default(),
             word_number: 50,
             top_words: 500,
+            pace_ghost: false,
+            pacer_wpm: 0,
+            adaptive_mode: false,
+            keyboard_layout: default_keyboard_layout(),
+            emulate_layout: false,
+            sampling_mode: SamplingMode::default(),
+            no_repeat_within: 0,
+            code_mode: false,
+            code_language: None,
+            skip_indent: default_skip_indent(),
+            library_mode: false,
+            library_text: None,
+            chunk_size: default_chunk_size(),
+            strictness: Strictness::default(),
+            half_accent_errors: false,
+            quote_length: QuoteLength::default(),
         }
     }
 }
 
 impl AppConfig {
-    fn get_config_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
-        let home = std::env::var("HOME")
-            .or_else(|_| std::env::var("USERPROFILE"))
-            .map_err(|_| "Unable to find home directory")?;
-
-        let config_dir = PathBuf::from(home).join(".config").join("typeman");
-        fs::create_dir_all(&config_dir)?;
-        Ok(config_dir)
+    /// Layout to emulate on a QWERTY keyboard, None when emulation is off
+    pub fn emulated_layout(&self) -> Option<KeyboardLayout> {
+        self.emulate_layout.then(|| KeyboardLayout::find(&self.keyboard_layout))
     }
 
     fn get_config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
-        Ok(Self::get_config_dir()?.join("config.json"))
+        Ok(get_config_dir()?.join("config.json"))
     }
 
     pub fn load() -> Self {
@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MyColor {
     pub r: u8,
     pub g: u8,
@@ -9,6 +10,18 @@ impl MyColor {
     pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
         Self { r, g, b, a }
     }
+
+    /// Color `t` of the way from `self` to `other`, `t` in 0..=1
+    pub fn mix(self, other: MyColor, t: f64) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let channel = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Self::new(
+            channel(self.r, other.r),
+            channel(self.g, other.g),
+            channel(self.b, other.b),
+            channel(self.a, other.a),
+        )
+    }
 }
 
 #[cfg(feature = "gui")]
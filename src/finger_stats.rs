@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use crate::key_stats::{KeyStat, MAX_DELAY_MS};
+use crate::keyboard_layout::{Finger, KeyboardLayout};
+use crate::keystroke_log::{KeyEvent, LoggedKey};
+
+/// Presses of one finger, split out for the keys typed right after
+/// another key of the same finger
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FingerStat {
+    pub keys: KeyStat,
+    /// Second keys of same-finger bigrams, e.g. "ed" on QWERTY
+    pub same_finger: KeyStat,
+}
+
+/// Per-finger breakdown of a test, using the finger map of a layout
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FingerStats {
+    pub fingers: HashMap<Finger, FingerStat>,
+    /// Second keys of pairs typed with two different fingers, the baseline for same-finger slowdowns
+    pub other_finger: KeyStat,
+}
+
+impl FingerStats {
+    pub fn from_events(events: &[KeyEvent], layout: &KeyboardLayout) -> Self {
+        let mut stats = Self::default();
+        let mut previous: Option<(char, Finger)> = None;
+        for (i, event) in events.iter().enumerate() {
+            let (LoggedKey::Char(_), Some(expected)) = (event.key, event.expected) else {
+                previous = None;
+                continue;
+            };
+            let Some(finger) = layout.finger(expected) else {
+                previous = None;
+                continue;
+            };
+            // The first key has no delay, it only starts the clock
+            let delay = (i > 0 && event.delay_ms <= MAX_DELAY_MS).then_some(event.delay_ms);
+            let stat = stats.fingers.entry(finger).or_default();
+            stat.keys.add(event.state, delay);
+
+            // Pressing the same key twice is not a same-finger bigram
+            if let Some((_, prev_finger)) = previous.filter(|(key, _)| *key != expected) {
+                if prev_finger == finger && finger != Finger::Thumb {
+                    stat.same_finger.add(event.state, delay);
+                } else {
+                    stats.other_finger.add(event.state, delay);
+                }
+            }
+            previous = Some((expected, finger));
+        }
+        stats
+    }
+
+    pub fn get(&self, finger: Finger) -> FingerStat {
+        self.fingers.get(&finger).copied().unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fingers.is_empty()
+    }
+
+    /// Part of `wpm` typed by `finger`, by its share of the correctly typed chars
+    pub fn wpm_share(&self, finger: Finger, wpm: f64) -> f64 {
+        let correct = |stat: &FingerStat| (stat.keys.presses - stat.keys.errors) as f64;
+        let total: f64 = self.fingers.values().map(correct).sum();
+        if total == 0.0 {
+            return 0.0;
+        }
+        wpm * correct(&self.get(finger)) / total
+    }
+
+    /// Extra milliseconds a same-finger bigram of `finger` takes over a pair typed with two fingers
+    pub fn slowdown(&self, finger: Finger) -> Option<f64> {
+        Some(self.get(finger).same_finger.avg_ms()? - self.other_finger.avg_ms()?)
+    }
+
+    /// Extra milliseconds of all same-finger bigrams over pairs typed with two fingers
+    pub fn total_slowdown(&self) -> Option<f64> {
+        let same = self.fingers.values().fold(KeyStat::default(), |mut sum, stat| {
+            sum.timed += stat.same_finger.timed;
+            sum.total_ms += stat.same_finger.total_ms;
+            sum
+        });
+        Some(same.avg_ms()? - self.other_finger.avg_ms()?)
+    }
+
+    /// One line per finger that pressed a key, e.g. "left index   12.5 wpm   4% err   +40ms sfb"
+    pub fn lines(&self, wpm: f64) -> Vec<String> {
+        Finger::all()
+            .iter()
+            .filter(|finger| self.get(**finger).keys.presses > 0)
+            .map(|finger| {
+                let stat = self.get(*finger);
+                let slowdown = match self.slowdown(*finger) {
+                    Some(ms) => format!("{:+.0}ms sfb", ms),
+                    None => "- sfb".to_string(),
+                };
+                format!(
+                    "{:<12} {:>5.1} wpm {:>4.0}% err {:>10}",
+                    finger.to_string(),
+                    self.wpm_share(*finger, wpm),
+                    stat.keys.error_rate() * 100.0,
+                    slowdown,
+                )
+            })
+            .collect()
+    }
+
+    /// Slowdown of all same-finger bigrams, e.g. "same finger bigrams: +35ms"
+    pub fn summary(&self) -> String {
+        match self.total_slowdown() {
+            Some(ms) => format!("same finger bigrams: {:+.0}ms", ms),
+            None => "same finger bigrams: -".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(expected: char, state: i32, delay_ms: u64) -> KeyEvent {
+        KeyEvent { key: LoggedKey::Char(expected), expected: Some(expected), state, delay_ms }
+    }
+
+    #[test]
+    fn test_fingers_and_wpm_share() {
+        let qwerty = KeyboardLayout::default();
+        let events = vec![
+            event('f', 2, 0),
+            event('j', 2, 100),
+            event('f', -1, 100),
+            event('f', 1, 100),
+        ];
+        let stats = FingerStats::from_events(&events, &qwerty);
+        assert_eq!(stats.get(Finger::LeftIndex).keys.presses, 3);
+        assert_eq!(stats.get(Finger::LeftIndex).keys.errors, 1);
+        assert_eq!(stats.get(Finger::RightIndex).keys.presses, 1);
+        assert_eq!(stats.wpm_share(Finger::LeftIndex, 60.0), 40.0);
+        assert_eq!(stats.wpm_share(Finger::RightIndex, 60.0), 20.0);
+    }
+
+    #[test]
+    fn test_same_finger_slowdown() {
+        let qwerty = KeyboardLayout::default();
+        // "ed" is typed with the left middle finger twice, "ek" with two fingers
+        let events = vec![
+            event('e', 2, 0),
+            event('d', 2, 300),
+            event(' ', 2, 100),
+            event('e', 2, 100),
+            event('k', 2, 100),
+            event('k', 2, 500),
+        ];
+        let stats = FingerStats::from_events(&events, &qwerty);
+        assert_eq!(stats.get(Finger::LeftMiddle).same_finger.presses, 1);
+        assert_eq!(stats.other_finger.presses, 3);
+        assert_eq!(stats.slowdown(Finger::LeftMiddle), Some(200.0));
+        assert_eq!(stats.total_slowdown(), Some(200.0));
+        assert_eq!(stats.slowdown(Finger::RightMiddle), None);
+    }
+}
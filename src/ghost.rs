@@ -0,0 +1,187 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::keystroke_log::{self, KeystrokeLog};
+use crate::leaderboard::{self, LeaderboardEntry, LeaderboardError, TestType};
+use crate::replay::Replay;
+use crate::typing_session::{TypingSession, CHARS_PER_WORD};
+
+/// Second cursor that moves through the reference at the pace of an earlier run.
+/// Progress is counted in chars over all batches, so the ghost works on any reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ghost {
+    pub label: String,
+    /// (seconds since the first key, chars typed) after every recorded key
+    timeline: Vec<(f64, usize)>,
+    /// Average pace, used past the end of the timeline and when there is no log
+    chars_per_second: f64,
+}
+
+/// Chars typed in a session, counting the batches already finished
+pub fn progress(session: &TypingSession) -> usize {
+    batch_offset(session) + session.pos
+}
+
+/// Chars in the batches before the current one
+pub fn batch_offset(session: &TypingSession) -> usize {
+    let done = session.batches.len().saturating_sub(1);
+    session.batches[..done].iter().map(|batch| batch.chars().count()).sum()
+}
+
+impl Ghost {
+    /// Ghost moving at a constant pace
+    pub fn from_wpm(wpm: f64, label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            timeline: Vec::new(),
+            chars_per_second: wpm * CHARS_PER_WORD / 60.0,
+        }
+    }
+
+    /// Ghost following the recorded delays of a keystroke log
+    pub fn from_log(log: &KeystrokeLog, label: &str) -> Self {
+        let mut replay = Replay::new(log.clone());
+        let mut timeline = Vec::with_capacity(log.events.len());
+        let mut elapsed_ms = 0.0;
+        for event in &log.events {
+            elapsed_ms += event.delay_ms as f64;
+            replay.advance(event.delay_ms as f64);
+            timeline.push((elapsed_ms / 1000.0, progress(&replay.session)));
+        }
+        let chars_per_second = match timeline.last() {
+            Some(&(secs, chars)) if secs > 0.0 => chars as f64 / secs,
+            _ => log.wpm * CHARS_PER_WORD / 60.0,
+        };
+        Self {
+            label: label.to_string(),
+            timeline,
+            chars_per_second,
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, LeaderboardError> {
+        let log = keystroke_log::load_log(path)?;
+        let label = format!("replay {:.0} wpm", log.wpm);
+        Ok(Self::from_log(&log, &label))
+    }
+
+    /// Uses the keystroke log of a leaderboard entry if it was saved, its wpm otherwise
+    pub fn for_entry(entry: &LeaderboardEntry, label: &str) -> Self {
+        keystroke_log::log_path(&entry.timestamp)
+            .ok()
+            .filter(|path| path.exists())
+            .and_then(|path| keystroke_log::load_log(&path).ok())
+            .map(|log| Self::from_log(&log, label))
+            .unwrap_or_else(|| Self::from_wpm(entry.wpm, label))
+    }
+
+    /// Ghost of the best leaderboard run for the same test type
+    pub fn personal_best(test_type: &TestType) -> Option<Self> {
+        let entries = leaderboard::load_history().ok()?;
+        let best = entries
+            .iter()
+            .filter(|entry| entry.test_type == *test_type)
+            .max_by(|a, b| a.wpm.partial_cmp(&b.wpm).unwrap_or(std::cmp::Ordering::Equal))?;
+        Some(Self::for_entry(best, &format!("best {:.0} wpm", best.wpm)))
+    }
+
+    /// Chars the ghost has typed after `elapsed`
+    pub fn chars_at(&self, elapsed: Duration) -> usize {
+        let secs = elapsed.as_secs_f64();
+        if self.timeline.is_empty() {
+            return (secs * self.chars_per_second) as usize;
+        }
+        match self.timeline.iter().rposition(|(t, _)| *t <= secs) {
+            None => 0,
+            Some(i) if i + 1 < self.timeline.len() => self.timeline[i].1,
+            Some(i) => {
+                let (last_secs, last_chars) = self.timeline[i];
+                last_chars + ((secs - last_secs) * self.chars_per_second) as usize
+            }
+        }
+    }
+
+    /// Seconds the ghost needs to type `chars`
+    pub fn time_to_reach(&self, chars: usize) -> f64 {
+        if let Some(&(secs, _)) = self.timeline.iter().find(|(_, c)| *c >= chars) {
+            return secs;
+        }
+        let (last_secs, last_chars) = self.timeline.last().copied().unwrap_or((0.0, 0));
+        if self.chars_per_second <= 0.0 {
+            return f64::INFINITY;
+        }
+        last_secs + (chars - last_chars) as f64 / self.chars_per_second
+    }
+
+    /// Ghost cursor in the current batch of `session`, None before the start or outside the batch
+    pub fn cursor(&self, session: &TypingSession) -> Option<usize> {
+        if !session.is_started() {
+            return None;
+        }
+        let pos = self.chars_at(session.elapsed()).checked_sub(batch_offset(session))?;
+        (pos < session.ref_chars.len()).then_some(pos)
+    }
+
+    /// Seconds the session finished ahead of the ghost, negative when behind
+    pub fn margin(&self, session: &TypingSession) -> f64 {
+        self.time_to_reach(progress(session)) - session.elapsed().as_secs_f64()
+    }
+
+    pub fn summary(&self, session: &TypingSession) -> String {
+        let margin = self.margin(session);
+        if margin.abs() < 0.05 {
+            format!("Tied with the ghost ({})", self.label)
+        } else if margin > 0.0 {
+            format!("Finished {:.1}s ahead of the ghost ({})", margin, self.label)
+        } else {
+            format!("Finished {:.1}s behind the ghost ({})", -margin, self.label)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::Language;
+    use crate::typing_session::SessionMode;
+
+    fn log_with_delays(reference: &str, delays: &[u64]) -> KeystrokeLog {
+        let mut session = TypingSession::new(reference.to_string(), SessionMode::Text);
+        for c in reference.chars() {
+            session.type_char(c);
+        }
+        let mut log = KeystrokeLog::from_session(&session, TestType::Word(1), "word", Language::English);
+        for (event, delay) in log.events.iter_mut().zip(delays) {
+            event.delay_ms = *delay;
+        }
+        log
+    }
+
+    #[test]
+    fn test_ghost_follows_log() {
+        let ghost = Ghost::from_log(&log_with_delays("abcd", &[0, 500, 500, 1000]), "log");
+        assert_eq!(ghost.chars_at(Duration::from_millis(0)), 1);
+        assert_eq!(ghost.chars_at(Duration::from_millis(700)), 2);
+        assert_eq!(ghost.chars_at(Duration::from_millis(1999)), 3);
+        assert_eq!(ghost.time_to_reach(4), 2.0);
+        // 4 chars in 2s, so the ghost keeps going at 2 chars per second
+        assert_eq!(ghost.time_to_reach(6), 3.0);
+    }
+
+    #[test]
+    fn test_constant_pace_ghost() {
+        let ghost = Ghost::from_wpm(60.0, "60 wpm");
+        assert_eq!(ghost.chars_at(Duration::from_secs(2)), 12);
+        assert_eq!(ghost.time_to_reach(6), 1.0);
+    }
+
+    #[test]
+    fn test_progress_counts_batches() {
+        let mut session = TypingSession::new("ab".to_string(), SessionMode::Words(5));
+        session.type_char('a');
+        session.type_char('b');
+        session.next_batch("cd".to_string());
+        session.type_char('c');
+        assert_eq!(progress(&session), 3);
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::custom_colors::MyColor;
+use crate::keystroke_log::{self, KeyEvent, LoggedKey};
+use crate::leaderboard::LeaderboardError;
+use crate::typing_session::TypingSession;
+
+/// Delays above this are pauses rather than time spent finding the key
+pub const MAX_DELAY_MS: u64 = 2000;
+
+const SHIFTED: &str = "~!@#$%^&*()_+{}|:\"<>?";
+const UNSHIFTED: &str = "`1234567890-=[]\\;',./";
+
+/// Key on the keyboard that types `c`, e.g. 'a' for 'A' and '1' for '!'
+pub fn base_key(c: char) -> char {
+    match SHIFTED.chars().position(|s| s == c) {
+        Some(i) => UNSHIFTED.chars().nth(i).unwrap_or(c),
+        None => c.to_ascii_lowercase(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapMetric {
+    Errors,
+    Latency,
+}
+
+impl HeatmapMetric {
+    pub fn all() -> &'static [HeatmapMetric] {
+        &[HeatmapMetric::Errors, HeatmapMetric::Latency]
+    }
+
+    pub fn count() -> usize {
+        Self::all().len()
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            HeatmapMetric::Errors => HeatmapMetric::Latency,
+            HeatmapMetric::Latency => HeatmapMetric::Errors,
+        }
+    }
+}
+
+impl Display for HeatmapMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeatmapMetric::Errors => write!(f, "error rate"),
+            HeatmapMetric::Latency => write!(f, "time to press"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyStat {
+    pub presses: u32,
+    pub errors: u32,
+    /// Presses with a usable delay, the ones counted in `total_ms`
+    pub timed: u32,
+    pub total_ms: u64,
+}
+
+impl KeyStat {
+    pub fn error_rate(&self) -> f64 {
+        if self.presses == 0 {
+            return 0.0;
+        }
+        self.errors as f64 / self.presses as f64
+    }
+
+    pub fn avg_ms(&self) -> Option<f64> {
+        (self.timed > 0).then(|| self.total_ms as f64 / self.timed as f64)
+    }
+
+    pub fn add(&mut self, state: i32, delay_ms: Option<u64>) {
+        self.presses += 1;
+        if state == -1 {
+            self.errors += 1;
+        }
+        if let Some(delay_ms) = delay_ms {
+            self.timed += 1;
+            self.total_ms += delay_ms;
+        }
+    }
+}
+
+/// State of the heatmap view
+pub struct HeatmapData {
+    pub open: bool,
+    /// Shows `all` instead of the current test
+    pub all_tests: bool,
+    pub metric: HeatmapMetric,
+    /// Stats of every saved test, reloaded when the view opens
+    pub all: KeyStats,
+}
+
+impl Default for HeatmapData {
+    fn default() -> Self {
+        Self {
+            open: false,
+            all_tests: false,
+            metric: HeatmapMetric::Errors,
+            all: KeyStats::default(),
+        }
+    }
+}
+
+impl HeatmapData {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        if self.open {
+            self.all = KeyStats::load_all().unwrap_or_default();
+        }
+    }
+}
+
+/// Presses, errors and delays per key, keyed by `base_key`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyStats {
+    pub keys: HashMap<char, KeyStat>,
+    /// Same stats for the second key of each pair of letters typed in a row, e.g. "th"
+    pub bigrams: HashMap<String, KeyStat>,
+}
+
+impl KeyStats {
+    /// Counts every typed char against the key that was expected
+    pub fn from_events(events: &[KeyEvent]) -> Self {
+        let mut stats = Self::default();
+        stats.add_events(events);
+        stats
+    }
+
+    pub fn from_session(session: &TypingSession) -> Self {
+        Self::from_events(&session.log)
+    }
+
+    /// Stats of every saved keystroke log
+    pub fn load_all() -> Result<Self, LeaderboardError> {
+        let mut stats = Self::default();
+        for path in keystroke_log::list_logs()? {
+            if let Ok(log) = keystroke_log::load_log(&path) {
+                stats.add_events(&log.events);
+            }
+        }
+        Ok(stats)
+    }
+
+    pub fn add_events(&mut self, events: &[KeyEvent]) {
+        let mut previous: Option<char> = None;
+        for (i, event) in events.iter().enumerate() {
+            let (LoggedKey::Char(_), Some(expected)) = (event.key, event.expected) else {
+                previous = None;
+                continue;
+            };
+            let key = base_key(expected);
+            // The first key has no delay, it only starts the clock
+            let delay = (i > 0 && event.delay_ms <= MAX_DELAY_MS).then_some(event.delay_ms);
+            self.keys.entry(key).or_default().add(event.state, delay);
+
+            if let Some(prev) = previous.filter(|p| p.is_alphabetic() && key.is_alphabetic()) {
+                let bigram: String = [prev, key].iter().collect();
+                self.bigrams.entry(bigram).or_default().add(event.state, delay);
+            }
+            // Practice keeps the cursor on a missed key, so the retry must not pair with it
+            previous = (event.state != -1).then_some(key);
+        }
+    }
+
+    pub fn get(&self, key: char) -> KeyStat {
+        self.keys.get(&base_key(key)).copied().unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn value(&self, key: char, metric: HeatmapMetric) -> Option<f64> {
+        let stat = self.get(key);
+        match metric {
+            HeatmapMetric::Errors => (stat.presses > 0).then(|| stat.error_rate()),
+            HeatmapMetric::Latency => stat.avg_ms(),
+        }
+    }
+
+    /// How hot a key is, from 0 (best key) to 1 (worst key), None when it was never pressed
+    pub fn heat(&self, key: char, metric: HeatmapMetric) -> Option<f64> {
+        let value = self.value(key, metric)?;
+        let values = self.keys.keys().filter_map(|k| self.value(*k, metric));
+        let (min, max) = values.fold((f64::MAX, f64::MIN), |(min, max), v| (min.min(v), max.max(v)));
+        // Error rates are shaded from zero, so a key nobody misses stays cold
+        let min = if metric == HeatmapMetric::Errors { 0.0 } else { min };
+        if max - min <= f64::EPSILON {
+            return Some(0.0);
+        }
+        Some((value - min) / (max - min))
+    }
+
+    /// Shade of a key between `cold` and `hot`, `none` for keys that were never pressed
+    pub fn color(&self, key: char, metric: HeatmapMetric, none: MyColor, cold: MyColor, hot: MyColor) -> MyColor {
+        match self.heat(key, metric) {
+            Some(heat) => cold.mix(hot, heat),
+            None => none,
+        }
+    }
+
+    /// Text shown under a key, e.g. "12%" or "180ms"
+    pub fn label(&self, key: char, metric: HeatmapMetric) -> String {
+        match (metric, self.value(key, metric)) {
+            (HeatmapMetric::Errors, Some(rate)) => format!("{:.0}%", rate * 100.0),
+            (HeatmapMetric::Latency, Some(ms)) => format!("{:.0}ms", ms),
+            (_, None) => "-".to_string(),
+        }
+    }
+
+    /// Keys with the highest heat, hottest first
+    pub fn worst_keys(&self, metric: HeatmapMetric, n: usize) -> Vec<char> {
+        let mut keys: Vec<(char, f64)> = self
+            .keys
+            .keys()
+            .filter(|k| **k != ' ')
+            .filter_map(|k| Some((*k, self.value(*k, metric)?)))
+            .collect();
+        keys.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        keys.into_iter().take(n).map(|(k, _)| k).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typing_session::SessionMode;
+
+    #[test]
+    fn test_base_key() {
+        assert_eq!(base_key('A'), 'a');
+        assert_eq!(base_key('!'), '1');
+        assert_eq!(base_key('?'), '/');
+        assert_eq!(base_key('x'), 'x');
+    }
+
+    #[test]
+    fn test_stats_from_session() {
+        let mut session = TypingSession::new("aB a".to_string(), SessionMode::Text);
+        session.type_char('x');
+        session.backspace();
+        for c in "aB a".chars() {
+            session.type_char(c);
+        }
+        let mut events = session.log.clone();
+        for event in events.iter_mut() {
+            event.delay_ms = 100;
+        }
+        let stats = KeyStats::from_events(&events);
+        let a = stats.get('a');
+        assert_eq!(a.presses, 3);
+        assert_eq!(a.errors, 1);
+        // The first press carries no delay
+        assert_eq!(a.timed, 2);
+        assert_eq!(stats.get('B').presses, 1);
+        assert_eq!(stats.get('a').error_rate(), 1.0 / 3.0);
+        // The backspace and the space break the other pairs
+        assert_eq!(stats.bigrams.get("ab").map(|b| b.presses), Some(1));
+        assert_eq!(stats.bigrams.len(), 1);
+    }
+
+    #[test]
+    fn test_practice_retry_is_not_a_bigram() {
+        let mut session = TypingSession::new("ab".to_string(), SessionMode::Practice);
+        for c in "axb".chars() {
+            session.type_char(c);
+        }
+        let stats = KeyStats::from_events(&session.log);
+        assert_eq!(stats.get('b').presses, 2);
+        assert_eq!(stats.bigrams.get("ab").map(|b| (b.presses, b.errors)), Some((1, 1)));
+        assert_eq!(stats.bigrams.get("bb"), None);
+        assert_eq!(stats.bigrams.len(), 1);
+    }
+
+    #[test]
+    fn test_heat_is_relative() {
+        let mut stats = KeyStats::default();
+        stats.keys.insert('a', KeyStat { presses: 10, errors: 0, timed: 10, total_ms: 1000 });
+        stats.keys.insert('b', KeyStat { presses: 10, errors: 5, timed: 10, total_ms: 3000 });
+        stats.keys.insert('c', KeyStat { presses: 10, errors: 1, timed: 10, total_ms: 2000 });
+        assert_eq!(stats.heat('b', HeatmapMetric::Errors), Some(1.0));
+        assert_eq!(stats.heat('c', HeatmapMetric::Latency), Some(0.5));
+        assert_eq!(stats.heat('z', HeatmapMetric::Errors), None);
+        assert_eq!(stats.worst_keys(HeatmapMetric::Latency, 2), vec!['b', 'c']);
+    }
+}
@@ -0,0 +1,340 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::leaderboard::{get_config_dir, LeaderboardError};
+
+pub const DEFAULT_LAYOUT: &str = "QWERTY";
+
+/// Unshifted keys of a QWERTY keyboard, number row first. Every layout lists its keys
+/// in the same physical positions.
+pub const QWERTY_ROWS: [&str; 5] = [
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+    " ",
+];
+
+const QWERTY_SHIFTED_ROWS: [&str; 5] = [
+    "~!@#$%^&*()_+",
+    "QWERTYUIOP{}|",
+    "ASDFGHJKL:\"",
+    "ZXCVBNM<>?",
+    " ",
+];
+
+/// Name, unshifted rows and shifted rows of the built-in layouts
+const BUILTIN_LAYOUTS: [(&str, [&str; 5], [&str; 5]); 7] = [
+    ("QWERTY", QWERTY_ROWS, QWERTY_SHIFTED_ROWS),
+    (
+        "Dvorak",
+        ["`1234567890[]", "',.pyfgcrl/=\\", "aoeuidhtns-", ";qjkxbmwvz", " "],
+        ["~!@#$%^&*(){}", "\"<>PYFGCRL?+|", "AOEUIDHTNS_", ":QJKXBMWVZ", " "],
+    ),
+    (
+        "Colemak",
+        ["`1234567890-=", "qwfpgjluy;[]\\", "arstdhneio'", "zxcvbkm,./", " "],
+        ["~!@#$%^&*()_+", "QWFPGJLUY:{}|", "ARSTDHNEIO\"", "ZXCVBKM<>?", " "],
+    ),
+    (
+        "Workman",
+        ["`1234567890-=", "qdrwbjfup;[]\\", "ashtgyneoi'", "zxmcvkl,./", " "],
+        ["~!@#$%^&*()_+", "QDRWBJFUP:{}|", "ASHTGYNEOI\"", "ZXMCVKL<>?", " "],
+    ),
+    (
+        "AZERTY",
+        ["²&é\"'(-è_çà)=", "azertyuiop^$*", "qsdfghjklmù", "wxcvbn,;:!", " "],
+        ["²1234567890°+", "AZERTYUIOP¨£µ", "QSDFGHJKLM%", "WXCVBN?./§", " "],
+    ),
+    (
+        "QWERTZ",
+        ["^1234567890ß´", "qwertzuiopü+#", "asdfghjklöä", "yxcvbnm,.-", " "],
+        ["°!\"§$%&/()=?`", "QWERTZUIOPÜ*'", "ASDFGHJKLÖÄ", "YXCVBNM;:_", " "],
+    ),
+    (
+        "ЙЦУКЕН",
+        ["ё1234567890-=", "йцукенгшщзхъ\\", "фывапролджэ", "ячсмитьбю.", " "],
+        ["Ё!\"№;%:?*()_+", "ЙЦУКЕНГШЩЗХЪ/", "ФЫВАПРОЛДЖЭ", "ЯЧСМИТЬБЮ,", " "],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Finger {
+    LeftPinky,
+    LeftRing,
+    LeftMiddle,
+    LeftIndex,
+    Thumb,
+    RightIndex,
+    RightMiddle,
+    RightRing,
+    RightPinky,
+}
+
+impl Finger {
+    pub fn all() -> &'static [Finger] {
+        &[
+            Finger::LeftPinky,
+            Finger::LeftRing,
+            Finger::LeftMiddle,
+            Finger::LeftIndex,
+            Finger::Thumb,
+            Finger::RightIndex,
+            Finger::RightMiddle,
+            Finger::RightRing,
+            Finger::RightPinky,
+        ]
+    }
+
+    pub fn count() -> usize {
+        Self::all().len()
+    }
+
+    /// Finger that touch typists use for a key in the given QWERTY position
+    pub fn standard(row: usize, col: usize) -> Finger {
+        if row == 4 {
+            return Finger::Thumb;
+        }
+        // The number row starts one key further left than the letter rows
+        let col = if row == 0 { col.saturating_sub(1) } else { col };
+        match col {
+            0 => Finger::LeftPinky,
+            1 => Finger::LeftRing,
+            2 => Finger::LeftMiddle,
+            3 | 4 => Finger::LeftIndex,
+            5 | 6 => Finger::RightIndex,
+            7 => Finger::RightMiddle,
+            8 => Finger::RightRing,
+            _ => Finger::RightPinky,
+        }
+    }
+}
+
+impl Display for Finger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Finger::LeftPinky => write!(f, "left pinky"),
+            Finger::LeftRing => write!(f, "left ring"),
+            Finger::LeftMiddle => write!(f, "left middle"),
+            Finger::LeftIndex => write!(f, "left index"),
+            Finger::Thumb => write!(f, "thumb"),
+            Finger::RightIndex => write!(f, "right index"),
+            Finger::RightMiddle => write!(f, "right middle"),
+            Finger::RightRing => write!(f, "right ring"),
+            Finger::RightPinky => write!(f, "right pinky"),
+        }
+    }
+}
+
+/// Keys of a keyboard layout in the physical positions of `QWERTY_ROWS`,
+/// built in or loaded from a JSON file in `~/.config/typeman/layouts/`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyboardLayout {
+    pub name: String,
+    pub rows: Vec<String>,
+    /// Keys typed with shift, uppercase `rows` when left out
+    #[serde(default)]
+    pub shifted_rows: Vec<String>,
+    /// Finger for every key in `rows`, the standard touch typing fingers when left out
+    #[serde(default)]
+    pub fingers: Vec<Vec<Finger>>,
+}
+
+impl Default for KeyboardLayout {
+    fn default() -> Self {
+        Self::builtin().remove(0)
+    }
+}
+
+impl Display for KeyboardLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl KeyboardLayout {
+    pub fn builtin() -> Vec<KeyboardLayout> {
+        BUILTIN_LAYOUTS
+            .iter()
+            .map(|(name, rows, shifted_rows)| KeyboardLayout {
+                name: name.to_string(),
+                rows: rows.iter().map(|row| row.to_string()).collect(),
+                shifted_rows: shifted_rows.iter().map(|row| row.to_string()).collect(),
+                fingers: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Valid layouts from the layouts directory, sorted by file name
+    pub fn custom() -> Vec<KeyboardLayout> {
+        let Ok(dir) = get_layouts_dir() else {
+            return Vec::new();
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        paths
+            .iter()
+            .filter_map(|path| load_layout(path).ok())
+            .filter(|layout| layout.is_valid())
+            .collect()
+    }
+
+    /// Built-in layouts followed by the custom ones
+    pub fn all() -> Vec<KeyboardLayout> {
+        let mut layouts = Self::builtin();
+        for layout in Self::custom() {
+            if !layouts.iter().any(|l| l.name.eq_ignore_ascii_case(&layout.name)) {
+                layouts.push(layout);
+            }
+        }
+        layouts
+    }
+
+    /// Layout with the given name, QWERTY when there is none
+    pub fn find(name: &str) -> KeyboardLayout {
+        Self::all()
+            .into_iter()
+            .find(|layout| layout.name.eq_ignore_ascii_case(name))
+            .unwrap_or_default()
+    }
+
+    /// Name of the layout after `name`, wrapping around to the first one
+    pub fn next_name(name: &str) -> String {
+        let layouts = Self::all();
+        let next = layouts
+            .iter()
+            .position(|layout| layout.name.eq_ignore_ascii_case(name))
+            .map_or(0, |i| (i + 1) % layouts.len());
+        layouts[next].name.clone()
+    }
+
+    /// Every row has a key for each QWERTY position
+    pub fn is_valid(&self) -> bool {
+        let same_shape = |rows: &[String]| {
+            rows.len() == QWERTY_ROWS.len()
+                && rows.iter().zip(QWERTY_ROWS).all(|(row, qwerty)| row.chars().count() == qwerty.chars().count())
+        };
+        let same_fingers = self.fingers.len() == QWERTY_ROWS.len()
+            && self.fingers.iter().zip(QWERTY_ROWS).all(|(row, qwerty)| row.len() == qwerty.chars().count());
+        same_shape(&self.rows)
+            && (self.shifted_rows.is_empty() || same_shape(&self.shifted_rows))
+            && (self.fingers.is_empty() || same_fingers)
+    }
+
+    /// Key at the given position, None when the position does not exist
+    pub fn key_at(&self, row: usize, col: usize, shifted: bool) -> Option<char> {
+        let key = self.rows.get(row)?.chars().nth(col)?;
+        if !shifted {
+            return Some(key);
+        }
+        match self.shifted_rows.get(row) {
+            Some(shifted_row) => shifted_row.chars().nth(col),
+            None => key.to_uppercase().next(),
+        }
+    }
+
+    /// Row, column and shift state of the key that types `c`
+    pub fn position(&self, c: char) -> Option<(usize, usize, bool)> {
+        for (row, keys) in self.rows.iter().enumerate() {
+            if let Some(col) = keys.chars().position(|k| k == c) {
+                return Some((row, col, false));
+            }
+        }
+        for row in 0..self.rows.len() {
+            for col in 0..self.rows[row].chars().count() {
+                if self.key_at(row, col, true) == Some(c) {
+                    return Some((row, col, true));
+                }
+            }
+        }
+        None
+    }
+
+    /// Char this layout types on the key where QWERTY types `c`, `c` itself when QWERTY has no such key.
+    /// Also used to emulate the layout on a QWERTY keyboard.
+    pub fn from_qwerty(&self, c: char) -> char {
+        qwerty_position(c)
+            .and_then(|(row, col, shifted)| self.key_at(row, col, shifted))
+            .unwrap_or(c)
+    }
+
+    pub fn finger(&self, c: char) -> Option<Finger> {
+        let (row, col, _) = self.position(c)?;
+        match self.fingers.get(row).and_then(|fingers| fingers.get(col)) {
+            Some(finger) => Some(*finger),
+            None => Some(Finger::standard(row, col)),
+        }
+    }
+}
+
+fn qwerty_position(c: char) -> Option<(usize, usize, bool)> {
+    for (shifted, rows) in [(false, QWERTY_ROWS), (true, QWERTY_SHIFTED_ROWS)] {
+        for (row, keys) in rows.iter().enumerate() {
+            if let Some(col) = keys.chars().position(|k| k == c) {
+                return Some((row, col, shifted));
+            }
+        }
+    }
+    None
+}
+
+pub fn get_layouts_dir() -> Result<PathBuf, LeaderboardError> {
+    let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
+        std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+    ))?;
+    let layouts_dir = config_dir.join("layouts");
+    fs::create_dir_all(&layouts_dir)?;
+    Ok(layouts_dir)
+}
+
+pub fn load_layout(path: &Path) -> Result<KeyboardLayout, LeaderboardError> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_layouts_are_valid() {
+        for layout in KeyboardLayout::builtin() {
+            assert!(layout.is_valid(), "{} has the wrong shape", layout.name);
+        }
+    }
+
+    #[test]
+    fn test_from_qwerty() {
+        let colemak = KeyboardLayout::builtin().into_iter().find(|l| l.name == "Colemak").unwrap();
+        assert_eq!(colemak.from_qwerty('f'), 't');
+        assert_eq!(colemak.from_qwerty('j'), 'n');
+        assert_eq!(colemak.from_qwerty('J'), 'N');
+        assert_eq!(colemak.from_qwerty(':'), 'O');
+        let azerty = KeyboardLayout::builtin().into_iter().find(|l| l.name == "AZERTY").unwrap();
+        assert_eq!(azerty.from_qwerty('q'), 'a');
+        assert_eq!(azerty.from_qwerty('!'), '1');
+    }
+
+    #[test]
+    fn test_fingers() {
+        let dvorak = KeyboardLayout::builtin().into_iter().find(|l| l.name == "Dvorak").unwrap();
+        assert_eq!(dvorak.finger('u'), Some(Finger::LeftIndex));
+        assert_eq!(dvorak.finger('h'), Some(Finger::RightIndex));
+        assert_eq!(dvorak.finger('S'), Some(Finger::RightPinky));
+        assert_eq!(dvorak.finger(' '), Some(Finger::Thumb));
+        assert_eq!(dvorak.finger('1'), Some(Finger::LeftPinky));
+
+        let json = r#"{"name": "Mine", "rows": ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./", " "]}"#;
+        let custom: KeyboardLayout = serde_json::from_str(json).unwrap();
+        assert!(custom.is_valid());
+        assert_eq!(custom.key_at(2, 0, true), Some('A'));
+    }
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::language::Language;
+use crate::leaderboard::{get_config_dir, write_atomic, LeaderboardError, TestType};
+use crate::normalization::Strictness;
+use crate::typing_session::TypingSession;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoggedKey {
+    Char(char),
+    Backspace,
+}
+
+/// One key press as seen by the typing session
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: LoggedKey,
+    /// Reference char under the cursor when the key was pressed
+    pub expected: Option<char>,
+    /// State of the position after the key, same values as `TypingSession::is_correct`
+    pub state: i32,
+    /// Milliseconds since the previous key (0 for the first one)
+    pub delay_ms: u64,
+}
+
+/// Everything needed to rebuild a finished test, saved in `keystrokes/` next to `history.jsonl`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeystrokeLog {
+    pub timestamp: String,
+    pub test_type: TestType,
+    pub test_mode: String,
+    pub language: Language,
+    pub wpm: f64,
+    pub accuracy: f64,
+    pub test_duration: f64,
+    /// Reference texts in the order they were typed
+    pub batches: Vec<String>,
+    pub events: Vec<KeyEvent>,
+    /// Indentation was skipped after newlines, see `TypingSession::auto_indent`
+    #[serde(default)]
+    pub auto_indent: bool,
+    /// Older logs were always matched exactly
+    #[serde(default = "default_strictness")]
+    pub strictness: Strictness,
+    #[serde(default)]
+    pub half_accent_errors: bool,
+}
+
+fn default_strictness() -> Strictness {
+    Strictness::Exact
+}
+
+impl KeystrokeLog {
+    pub fn from_session(
+        session: &TypingSession,
+        test_type: TestType,
+        test_mode: &str,
+        language: Language,
+    ) -> Self {
+        let stats = session.stats();
+        Self {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            test_type,
+            test_mode: test_mode.to_string(),
+            language,
+            wpm: stats.wpm,
+            accuracy: stats.accuracy,
+            test_duration: stats.elapsed,
+            batches: session.batches.clone(),
+            events: session.log.clone(),
+            auto_indent: session.auto_indent,
+            strictness: session.strictness,
+            half_accent_errors: session.half_accent_errors,
+        }
+    }
+
+    /// Number of words across all typed batches
+    pub fn word_count(&self) -> usize {
+        self.batches.iter().map(|batch| batch.split_whitespace().count()).sum()
+    }
+}
+
+pub fn get_logs_dir() -> Result<PathBuf, LeaderboardError> {
+    let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
+        std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+    ))?;
+    let logs_dir = config_dir.join("keystrokes");
+    fs::create_dir_all(&logs_dir)?;
+    Ok(logs_dir)
+}
+
+/// File of the log with the given RFC3339 timestamp, which is also the timestamp
+/// of the leaderboard entry saved for the same test
+pub fn log_path(timestamp: &str) -> Result<PathBuf, LeaderboardError> {
+    let file_name = chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.format("%Y%m%d-%H%M%S%.3f").to_string())
+        .unwrap_or_else(|_| chrono::Local::now().format("%Y%m%d-%H%M%S%.3f").to_string());
+    Ok(get_logs_dir()?.join(format!("{}.json", file_name)))
+}
+
+/// Writes the log to its own timestamped file and returns the path
+pub fn save_log(log: &KeystrokeLog) -> Result<PathBuf, LeaderboardError> {
+    let path = log_path(&log.timestamp)?;
+    write_atomic(&path, &serde_json::to_string(log)?)?;
+    Ok(path)
+}
+
+pub fn load_log(path: &Path) -> Result<KeystrokeLog, LeaderboardError> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Paths of all saved logs, oldest first
+pub fn list_logs() -> Result<Vec<PathBuf>, LeaderboardError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(get_logs_dir()?)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Saves the log of a finished session, reporting failures on stderr
+pub fn save_session(session: &TypingSession, test_type: TestType, test_mode: &str, language: Language) {
+    save_session_at(session, test_type, test_mode, language, &chrono::Local::now().to_rfc3339());
+}
+
+/// Like `save_session`, with the timestamp of the matching leaderboard entry
+pub fn save_session_at(session: &TypingSession, test_type: TestType, test_mode: &str, language: Language, timestamp: &str) {
+    if !session.is_finished() || session.log.is_empty() {
+        return;
+    }
+    let mut log = KeystrokeLog::from_session(session, test_type, test_mode, language);
+    log.timestamp = timestamp.to_string();
+    if let Err(e) = save_log(&log) {
+        eprintln!("Failed to save keystroke log: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typing_session::SessionMode;
+
+    #[test]
+    fn test_session_log_roundtrip() {
+        let mut session = TypingSession::new("ab".to_string(), SessionMode::Text);
+        session.type_char('x');
+        session.backspace();
+        session.type_char('a');
+        session.type_char('b');
+
+        let mut log = KeystrokeLog::from_session(&session, TestType::Quote, "quote", Language::English);
+        // Typed in no time, so keep the stats to values json stores exactly
+        log.wpm = 60.0;
+        log.accuracy = 75.0;
+        log.test_duration = 2.0;
+        assert_eq!(log.events.len(), 4);
+        assert_eq!(log.events[0], KeyEvent { key: LoggedKey::Char('x'), expected: Some('a'), state: -1, delay_ms: 0 });
+        assert_eq!(log.events[1].key, LoggedKey::Backspace);
+        assert_eq!(log.events[2].state, 1);
+        assert_eq!(log.batches, vec!["ab".to_string()]);
+
+        let path = save_log(&log).unwrap();
+        assert_eq!(load_log(&path).unwrap(), log);
+        assert!(list_logs().unwrap().contains(&path));
+        fs::remove_file(path).ok();
+    }
+}
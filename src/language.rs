@@ -1,11 +1,27 @@
+use std::collections::HashSet;
 use std::fmt::Display;
-use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+use crate::word_source::{self, Lesson, WordSource, BUILTIN_WORD_LISTS, BUNDLED_PACKS};
+
+/// Word list used to generate tests, one of the built-in lists, a bundled language pack
+/// or a `WordSource` found in the languages directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Language {
     English,
     Indonesian,
     Italian,
+    German,
+    French,
+    Spanish,
+    Portuguese,
+    Polish,
+    Russian,
+    /// Keywords of Rust, Python, JavaScript, Go and C
+    Code,
+    /// Name of a user word list
+    Custom(&'static str),
 }
 
 impl Display for Language {
@@ -14,10 +30,45 @@ impl Display for Language {
             Language::English => write!(f, "English"),
             Language::Indonesian => write!(f, "Indonesian"),
             Language::Italian => write!(f, "Italian"),
+            Language::German => write!(f, "German"),
+            Language::French => write!(f, "French"),
+            Language::Spanish => write!(f, "Spanish"),
+            Language::Portuguese => write!(f, "Portuguese"),
+            Language::Polish => write!(f, "Polish"),
+            Language::Russian => write!(f, "Russian"),
+            Language::Code => write!(f, "Code"),
+            Language::Custom(name) => write!(f, "{}", name),
         }
     }
 }
 
+// Saved as the plain name, so entries written before custom lists existed still load
+impl Serialize for Language {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Language::from_name(&name).unwrap_or_else(|| Language::Custom(intern(name))))
+    }
+}
+
+/// Names of languages no pack provides anymore, e.g. in old results. Each one is
+/// leaked once, however many entries mention it.
+fn intern(name: String) -> &'static str {
+    static NAMES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut names = NAMES.get_or_init(Default::default).lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(interned) = names.get(name.as_str()) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
 impl Language {
     #[cfg(feature = "cli")]
     pub fn from_str(s: &str) -> Option<Language> {
@@ -25,32 +76,110 @@ impl Language {
             "english" | "en" => Some(Language::English),
             "indonesian" | "indonesia" | "id" | "indo" => Some(Language::Indonesian),
             "italian" | "ita" | "it" => Some(Language::Italian),
-            _ => None,
+            "german" | "deutsch" | "de" => Some(Language::German),
+            "french" | "français" | "francais" | "fr" => Some(Language::French),
+            "spanish" | "español" | "espanol" | "es" => Some(Language::Spanish),
+            "portuguese" | "português" | "portugues" | "pt" => Some(Language::Portuguese),
+            "polish" | "polski" | "pl" => Some(Language::Polish),
+            "russian" | "русский" | "ru" => Some(Language::Russian),
+            "code" | "keywords" => Some(Language::Code),
+            _ => Language::from_name(s),
         }
     }
 
-    pub fn word_list(&self) -> &'static str {
-        match self {
-            Language::English => include_str!("../assets/common_eng_words.txt"),
-            Language::Indonesian => include_str!("../assets/common_ind_words.txt"),
-            Language::Italian=> include_str!("../assets/common_ita_words.txt"),
-        }
+    /// Language with the given source name, ignoring case
+    pub fn from_name(name: &str) -> Option<Language> {
+        Language::all().into_iter().find(|language| language.to_string().eq_ignore_ascii_case(name))
+    }
+
+    pub fn source(&self) -> &'static dyn WordSource {
+        word_source::registry()
+            .get(&self.to_string())
+            .unwrap_or(&BUILTIN_WORD_LISTS[0])
     }
 
     pub fn get_words(&self, n: usize) -> Vec<String> {
-        self.word_list()
-            .lines()
-            .take(n.min(1000))
+        let source = self.source();
+        // Without a frequency order the top n words are no more common than the rest
+        let n = if source.frequency_ordered() { n } else { usize::MAX };
+        source
+            .words()
+            .into_iter()
+            .take(n)
             .map(|s| s.trim().to_string())
             .filter(|s| !s.is_empty() && s.chars().all(|c| c.is_alphabetic() || c.is_whitespace()))
             .collect()
     }
-    pub fn all() -> &'static [Language] {
-        &[
+
+    /// Words tests are made of. A list without a frequency order always uses all of
+    /// its words, so it never has more top words than this.
+    pub fn word_count(&self) -> usize {
+        self.get_words(usize::MAX).len()
+    }
+
+    /// Top words setting clamped to the words the list has
+    pub fn top_words(&self, requested: usize) -> usize {
+        requested.min(self.word_count())
+    }
+
+    pub fn is_right_to_left(&self) -> bool {
+        self.source().right_to_left()
+    }
+
+    /// Practice levels the language adds after the standard ones
+    pub fn lessons(&self) -> Vec<Lesson> {
+        self.source().lessons()
+    }
+
+    /// Keyboard layout the language is usually typed on
+    pub fn layout_hint(&self) -> Option<&'static str> {
+        self.source().layout_hint()
+    }
+
+    /// Name shown in the language popups, with the layout hint, e.g. "German (QWERTZ)"
+    pub fn label(&self) -> String {
+        match self.layout_hint() {
+            Some(layout) => format!("{} ({})", self, layout),
+            None => self.to_string(),
+        }
+    }
+
+    /// Two letter code shown in narrow places like the leaderboard
+    pub fn short_name(&self) -> String {
+        match self {
+            Language::English => "EN".to_string(),
+            Language::Indonesian => "ID".to_string(),
+            Language::Italian => "IT".to_string(),
+            Language::German => "DE".to_string(),
+            Language::French => "FR".to_string(),
+            Language::Spanish => "ES".to_string(),
+            Language::Portuguese => "PT".to_string(),
+            Language::Polish => "PL".to_string(),
+            Language::Russian => "RU".to_string(),
+            Language::Code => "</>".to_string(),
+            Language::Custom(name) => name.chars().take(2).collect::<String>().to_uppercase(),
+        }
+    }
+
+    /// Built-in languages and bundled packs followed by every user pack
+    pub fn all() -> Vec<Language> {
+        let mut languages = vec![
             Language::English,
             Language::Indonesian,
             Language::Italian,
-        ]
+            Language::German,
+            Language::French,
+            Language::Spanish,
+            Language::Portuguese,
+            Language::Polish,
+            Language::Russian,
+            Language::Code,
+        ];
+        let bundled = BUILTIN_WORD_LISTS.len() + BUNDLED_PACKS.len();
+        for name in word_source::registry().names().into_iter().skip(bundled) {
+            languages.push(Language::Custom(name));
+        }
+        languages
     }
 
     pub fn count() -> usize {
@@ -63,3 +192,19 @@ impl Default for Language {
         Language::English
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_names_are_interned() {
+        let first: Language = serde_json::from_str(r#""Klingon""#).unwrap();
+        let second: Language = serde_json::from_str(r#""Klingon""#).unwrap();
+        let (Language::Custom(a), Language::Custom(b)) = (first, second) else {
+            panic!("unknown names should load as custom languages");
+        };
+        assert!(std::ptr::eq(a, b));
+        assert_eq!(serde_json::from_str::<Language>(r#""german""#).unwrap(), Language::German);
+    }
+}
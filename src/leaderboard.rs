@@ -1,12 +1,26 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use std::io::Write;
 use std::time::{Duration, Instant, SystemTime};
+use chrono::{DateTime, FixedOffset, Local, TimeDelta};
 use fs2::FileExt;
 use crate::language::Language;
+use crate::typing_session::TypingSession;
 
-/// Cached leaderboard data with timestamp for invalidation
+/// Completed tests, one JSON entry per line, oldest first
+const HISTORY_FILE: &str = "history.jsonl";
+/// Top 100 runs file of older versions, migrated into the history
+const LEGACY_LEADERBOARD_FILE: &str = "leaderboard.json";
+const LOCK_FILE: &str = "leaderboard.lock";
+/// Environment variable that moves the config directory, used by the tests
+pub const CONFIG_DIR_VAR: &str = "TYPEMAN_CONFIG_DIR";
+/// Number of runs shown on the leaderboard
+pub const LEADERBOARD_SIZE: usize = 100;
+
+/// Cached history data with timestamp for invalidation
 #[derive(Debug, Clone)]
 struct LeaderboardCache {
     entries: Vec<LeaderboardEntry>,
@@ -16,13 +30,53 @@ struct LeaderboardCache {
 
 pub struct LeaderboardData {
     pub open: bool,
+    /// Rows shown, `query` applied to `history`
     pub entries: Vec<LeaderboardEntry>,
     pub selected: usize,
+    /// Every completed test, reloaded when the leaderboard opens
+    pub history: Vec<LeaderboardEntry>,
+    pub query: LeaderboardQuery,
+}
+
+impl Default for LeaderboardData {
+    fn default() -> Self {
+        let mut data = Self {
+            open: false,
+            entries: Vec::new(),
+            selected: 0,
+            history: Vec::new(),
+            query: LeaderboardQuery::default(),
+        };
+        data.reload();
+        data
+    }
+}
+
+impl LeaderboardData {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        if self.open {
+            self.reload();
+            self.selected = 0;
+        }
+    }
+
+    /// Reads the history again and applies the query
+    pub fn reload(&mut self) {
+        self.history = load_history().unwrap_or_default();
+        self.refresh();
+    }
+
+    /// Applies the query after it changed
+    pub fn refresh(&mut self) {
+        self.entries = self.query.apply(&self.history);
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
 }
 
 impl LeaderboardCache {
     /// Check if cache is still valid (less than 30 seconds old and file hasn't changed)
-    fn is_valid(&self, file_path: &PathBuf) -> bool {
+    fn is_valid(&self, file_path: &Path) -> bool {
         // Cache expires after 30 seconds
         if self.cached_at.elapsed() > Duration::from_secs(30) {
             return false;
@@ -41,7 +95,7 @@ impl LeaderboardCache {
 
 use std::sync::{Mutex, OnceLock};
 
-/// Global cache for leaderboard data
+/// Global cache for history data
 static LEADERBOARD_CACHE: OnceLock<Mutex<Option<LeaderboardCache>>> = OnceLock::new();
 
 /// Initialize the global cache
@@ -56,8 +110,28 @@ fn invalidate_cache() {
     }
 }
 
+/// Schema version of new entries, see `LeaderboardEntry::version`
+pub const ENTRY_VERSION: u32 = 2;
+
+fn legacy_version() -> u32 {
+    1
+}
+
+/// Settings of a test that change how results compare
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TestConfig {
+    pub punctuation: bool,
+    pub digits: bool,
+    pub top_words: usize,
+    pub batch_size: usize,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct LeaderboardEntry {
+    /// 1 for entries saved before the test configuration and detailed stats were
+    /// recorded, their new fields are read as unknown
+    #[serde(default = "legacy_version")]
+    pub version: u32,
     pub wpm: f64,
     pub accuracy: f64,
     pub test_type: TestType,
@@ -66,9 +140,65 @@ pub struct LeaderboardEntry {
     pub test_duration: f64,
     pub timestamp: String,
     pub language: Language,
+    /// Quote typed in quote mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+    #[serde(default)]
+    pub punctuation: bool,
+    #[serde(default)]
+    pub digits: bool,
+    /// Most common words the test was drawn from, only for generated words
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_words: Option<usize>,
+    /// Words loaded at a time, only for generated words
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_wpm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consistency: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errors: Option<usize>,
 }
 
 impl LeaderboardEntry {
+    /// Entry of a finished test, filled the same way by every frontend. Punctuation,
+    /// digits, top words and batch size are only kept for tests of generated words.
+    pub fn from_session(
+        session: &TypingSession,
+        test_type: TestType,
+        test_mode: &str,
+        language: Language,
+        config: TestConfig,
+    ) -> Self {
+        let stats = session.stats();
+        let generated = matches!(test_type, TestType::Time(_) | TestType::Word(_));
+        Self {
+            version: ENTRY_VERSION,
+            wpm: stats.wpm,
+            accuracy: stats.accuracy,
+            quote: (test_type == TestType::Quote).then(|| session.reference.clone()),
+            test_type,
+            test_mode: test_mode.to_string(),
+            word_count: session.words_done,
+            test_duration: stats.elapsed,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            language,
+            punctuation: generated && config.punctuation,
+            digits: generated && config.digits,
+            top_words: generated.then(|| language.top_words(config.top_words)),
+            batch_size: generated.then_some(config.batch_size),
+            raw_wpm: Some(stats.raw_wpm),
+            consistency: Some(stats.consistency),
+            errors: Some(stats.errors),
+        }
+    }
+
+    /// Runs of the same category are compared against each other for personal bests
+    pub fn category(&self) -> (&TestType, Language, bool, bool) {
+        (&self.test_type, self.language, self.punctuation, self.digits)
+    }
+
     /// Validates all fields of the leaderboard entry
     pub fn validate(&self) -> Result<(), ValidationError> {
         // WPM validation (0.0-300.0 reasonable range for human typing)
@@ -81,6 +211,37 @@ impl LeaderboardEntry {
             return Err(ValidationError::InvalidAccuracy(self.accuracy));
         }
         
+        if self.version == 0 || self.version > ENTRY_VERSION {
+            return Err(ValidationError::InvalidVersion(self.version));
+        }
+        
+        // Entries since version 2 always record the detailed stats
+        if self.version >= 2 {
+            if self.raw_wpm.is_none() {
+                return Err(ValidationError::MissingField("raw_wpm".to_string()));
+            }
+            if self.consistency.is_none() {
+                return Err(ValidationError::MissingField("consistency".to_string()));
+            }
+            if self.errors.is_none() {
+                return Err(ValidationError::MissingField("errors".to_string()));
+            }
+        }
+        
+        // Raw WPM counts mistyped words too, so it may go a bit beyond the WPM range
+        if let Some(raw_wpm) = self.raw_wpm.filter(|raw_wpm| *raw_wpm < 0.0 || *raw_wpm > 400.0) {
+            return Err(ValidationError::InvalidWpm(raw_wpm));
+        }
+        
+        if let Some(consistency) = self.consistency.filter(|consistency| *consistency < 0.0 || *consistency > 100.0) {
+            return Err(ValidationError::InvalidConsistency(consistency));
+        }
+        
+        // Top words and batch size come from the selection popups, both are at least one word
+        if self.top_words == Some(0) || self.batch_size == Some(0) {
+            return Err(ValidationError::InvalidWordCount(0));
+        }
+        
         // Test duration validation (must be positive, max 24 hours = 86400 seconds)
         if self.test_duration < 0.0 || self.test_duration > 86400.0 {
             return Err(ValidationError::InvalidTestDuration(self.test_duration));
@@ -99,12 +260,18 @@ impl LeaderboardEntry {
         }
         
         // Timestamp format validation (RFC3339 format)
-        if let Err(_) = chrono::DateTime::parse_from_rfc3339(&self.timestamp) {
+        if self.taken_at().is_none() {
             return Err(ValidationError::InvalidTimestamp(self.timestamp.clone()));
         }
         
         Ok(())
     }
+
+    /// When the test was taken, None for a timestamp that isn't RFC3339.
+    /// Timestamps carry the local offset, so they can't be compared as strings.
+    pub fn taken_at(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.timestamp).ok()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -114,6 +281,226 @@ pub enum TestType {
     Quote,
     Practice(usize),
     Wiki,
+    Custom,
+    Adaptive,
+    Code,
+}
+
+impl Display for TestType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestType::Time(secs) => write!(f, "{}s", secs),
+            TestType::Word(words) => write!(f, "{}w", words),
+            TestType::Quote => write!(f, "Quote"),
+            TestType::Practice(level) => write!(f, "L{}", level),
+            TestType::Wiki => write!(f, "Wiki"),
+            TestType::Custom => write!(f, "Custom"),
+            TestType::Adaptive => write!(f, "Adapt"),
+            TestType::Code => write!(f, "Code"),
+        }
+    }
+}
+
+/// How far back the leaderboard looks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateRange {
+    #[default]
+    All,
+    Today,
+    Week,
+    Month,
+    Year,
+}
+
+impl DateRange {
+    pub fn all() -> &'static [DateRange] {
+        &[DateRange::All, DateRange::Today, DateRange::Week, DateRange::Month, DateRange::Year]
+    }
+
+    pub fn next(&self) -> DateRange {
+        let all = Self::all();
+        let i = all.iter().position(|range| range == self).unwrap_or(0);
+        all[(i + 1) % all.len()]
+    }
+
+    /// A test taken at `timestamp` is in the range ending `now`
+    pub fn contains(&self, timestamp: &str, now: DateTime<Local>) -> bool {
+        let Ok(taken) = DateTime::parse_from_rfc3339(timestamp) else {
+            return *self == DateRange::All;
+        };
+        let taken = taken.with_timezone(&Local);
+        match self {
+            DateRange::All => true,
+            DateRange::Today => taken.date_naive() == now.date_naive(),
+            DateRange::Week => now - taken <= TimeDelta::days(7),
+            DateRange::Month => now - taken <= TimeDelta::days(30),
+            DateRange::Year => now - taken <= TimeDelta::days(365),
+        }
+    }
+}
+
+impl Display for DateRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DateRange::All => write!(f, "all time"),
+            DateRange::Today => write!(f, "today"),
+            DateRange::Week => write!(f, "last 7 days"),
+            DateRange::Month => write!(f, "last 30 days"),
+            DateRange::Year => write!(f, "last year"),
+        }
+    }
+}
+
+/// Order of the leaderboard rows, best or newest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    Wpm,
+    Accuracy,
+    Date,
+    Duration,
+}
+
+impl SortKey {
+    pub fn all() -> &'static [SortKey] {
+        &[SortKey::Wpm, SortKey::Accuracy, SortKey::Date, SortKey::Duration]
+    }
+
+    pub fn next(&self) -> SortKey {
+        let all = Self::all();
+        let i = all.iter().position(|key| key == self).unwrap_or(0);
+        all[(i + 1) % all.len()]
+    }
+
+    fn compare(&self, a: &LeaderboardEntry, b: &LeaderboardEntry) -> Ordering {
+        let by_wpm = b.wpm.partial_cmp(&a.wpm).unwrap_or(Ordering::Equal);
+        match self {
+            SortKey::Wpm => by_wpm,
+            SortKey::Accuracy => b.accuracy.partial_cmp(&a.accuracy).unwrap_or(Ordering::Equal).then(by_wpm),
+            SortKey::Date => b.taken_at().cmp(&a.taken_at()),
+            SortKey::Duration => b.test_duration.partial_cmp(&a.test_duration).unwrap_or(Ordering::Equal).then(by_wpm),
+        }
+    }
+}
+
+impl Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortKey::Wpm => write!(f, "WPM"),
+            SortKey::Accuracy => write!(f, "accuracy"),
+            SortKey::Date => write!(f, "date"),
+            SortKey::Duration => write!(f, "duration"),
+        }
+    }
+}
+
+/// Filters, order and view of the leaderboard, `None` filters match everything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LeaderboardQuery {
+    pub test_type: Option<TestType>,
+    pub language: Option<Language>,
+    pub punctuation: Option<bool>,
+    pub digits: Option<bool>,
+    pub dates: DateRange,
+    pub sort: SortKey,
+    /// Only the best run of every category, see `LeaderboardEntry::category`
+    pub personal_bests: bool,
+}
+
+impl LeaderboardQuery {
+    pub fn matches(&self, entry: &LeaderboardEntry, now: DateTime<Local>) -> bool {
+        self.test_type.as_ref().is_none_or(|test_type| entry.test_type == *test_type)
+            && self.language.is_none_or(|language| entry.language == language)
+            && self.punctuation.is_none_or(|punctuation| entry.punctuation == punctuation)
+            && self.digits.is_none_or(|digits| entry.digits == digits)
+            && self.dates.contains(&entry.timestamp, now)
+    }
+
+    /// Rows of the leaderboard, at most `LEADERBOARD_SIZE`
+    pub fn apply(&self, history: &[LeaderboardEntry]) -> Vec<LeaderboardEntry> {
+        let now = Local::now();
+        let mut entries: Vec<LeaderboardEntry> = Vec::new();
+        for entry in history.iter().filter(|entry| self.matches(entry, now)) {
+            if !self.personal_bests {
+                entries.push(entry.clone());
+                continue;
+            }
+            match entries.iter_mut().find(|best| best.category() == entry.category()) {
+                Some(best) if best.wpm < entry.wpm => *best = entry.clone(),
+                Some(_) => {}
+                None => entries.push(entry.clone()),
+            }
+        }
+        entries.sort_by(|a, b| self.sort.compare(a, b));
+        entries.truncate(LEADERBOARD_SIZE);
+        entries
+    }
+
+    /// Cycles the test type filter through the types found in `history`
+    pub fn next_test_type(&mut self, history: &[LeaderboardEntry]) {
+        let mut types: Vec<TestType> = Vec::new();
+        for entry in history {
+            if !types.contains(&entry.test_type) {
+                types.push(entry.test_type.clone());
+            }
+        }
+        types.sort_by_key(|test_type| test_type.to_string());
+        self.test_type = next_filter(&types, self.test_type.as_ref()).cloned();
+    }
+
+    /// Cycles the language filter through the languages found in `history`
+    pub fn next_language(&mut self, history: &[LeaderboardEntry]) {
+        let mut languages: Vec<Language> = Vec::new();
+        for entry in history {
+            if !languages.contains(&entry.language) {
+                languages.push(entry.language);
+            }
+        }
+        languages.sort_by_key(|language| language.to_string());
+        self.language = next_filter(&languages, self.language.as_ref()).copied();
+    }
+
+    /// Any, with, without
+    pub fn next_punctuation(&mut self) {
+        self.punctuation = next_filter(&[true, false], self.punctuation.as_ref()).copied();
+    }
+
+    /// Any, with, without
+    pub fn next_digits(&mut self) {
+        self.digits = next_filter(&[true, false], self.digits.as_ref()).copied();
+    }
+
+    /// Keeps the sort key and view, drops the filters
+    pub fn clear_filters(&mut self) {
+        *self = Self { sort: self.sort, personal_bests: self.personal_bests, ..Self::default() };
+    }
+
+    /// One line summary, e.g. "Personal bests · 30s · English · punct: any · digits: off · today · by WPM"
+    pub fn describe(&self) -> String {
+        let toggle = |filter: Option<bool>| match filter {
+            None => "any",
+            Some(true) => "on",
+            Some(false) => "off",
+        };
+        [
+            if self.personal_bests { "Personal bests".to_string() } else { "All runs".to_string() },
+            self.test_type.as_ref().map_or("all types".to_string(), |test_type| test_type.to_string()),
+            self.language.map_or("all languages".to_string(), |language| language.to_string()),
+            format!("punct: {}", toggle(self.punctuation)),
+            format!("digits: {}", toggle(self.digits)),
+            self.dates.to_string(),
+            format!("by {}", self.sort),
+        ]
+        .join(" · ")
+    }
+}
+
+/// Value after `current` in `options`, `None` before the first and after the last
+fn next_filter<'a, T: PartialEq>(options: &'a [T], current: Option<&T>) -> Option<&'a T> {
+    match current.and_then(|current| options.iter().position(|option| option == current)) {
+        None => options.first(),
+        Some(i) => options.get(i + 1),
+    }
 }
 
 #[derive(Debug)]
@@ -124,6 +511,9 @@ pub enum ValidationError {
     FieldTooLong(String),
     InvalidTestDuration(f64),
     InvalidWordCount(usize),
+    InvalidConsistency(f64),
+    InvalidVersion(u32),
+    MissingField(String),
 }
 
 #[derive(Debug)]
@@ -142,7 +532,7 @@ pub struct FileLockGuard {
 
 impl FileLockGuard {
     /// Acquires an exclusive lock on the specified file with timeout
-    pub fn acquire(lock_path: &PathBuf, timeout: Duration) -> Result<Self, LeaderboardError> {
+    pub fn acquire(lock_path: &Path, timeout: Duration) -> Result<Self, LeaderboardError> {
         let file = fs::OpenOptions::new()
             .create(true)
             .write(true)
@@ -189,17 +579,37 @@ impl From<ValidationError> for LeaderboardError {
     }
 }
 
-/// Creates a backup of the leaderboard file before modifications with rotation
-/// Maintains up to 3 backup files: .json.bak, .json.bak2, .json.bak3
-fn create_backup(leaderboard_path: &PathBuf) -> Result<PathBuf, std::io::Error> {
-    if !leaderboard_path.exists() {
-        return Ok(leaderboard_path.with_extension("json.bak"));
+/// Path of a file next to `path` with `suffix` added to its name, e.g. `history.jsonl.bak`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Writes `contents` to a temporary file next to `path` first, then moves it over
+/// `path`, so a crash or a full disk never leaves a half written file behind
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), LeaderboardError> {
+    let temp_path = with_suffix(path, ".tmp");
+    {
+        let mut temp_file = fs::File::create(&temp_path)?;
+        temp_file.write_all(contents.as_bytes())?;
+        temp_file.sync_all()?;
+    }
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Creates a backup of the history file before modifications with rotation
+/// Maintains up to 3 backup files: .bak, .bak2, .bak3
+fn create_backup(history_path: &Path) -> Result<PathBuf, std::io::Error> {
+    let backup1_path = with_suffix(history_path, ".bak");
+    if !history_path.exists() {
+        return Ok(backup1_path);
     }
     
     // Rotate existing backups: .bak2 -> .bak3, .bak -> .bak2
-    let backup3_path = leaderboard_path.with_extension("json.bak3");
-    let backup2_path = leaderboard_path.with_extension("json.bak2");
-    let backup1_path = leaderboard_path.with_extension("json.bak");
+    let backup3_path = with_suffix(history_path, ".bak3");
+    let backup2_path = with_suffix(history_path, ".bak2");
     
     // Remove oldest backup if it exists
     if backup3_path.exists() {
@@ -217,25 +627,39 @@ fn create_backup(leaderboard_path: &PathBuf) -> Result<PathBuf, std::io::Error>
     }
     
     // Create new backup from current file
-    fs::copy(leaderboard_path, &backup1_path)?;
+    fs::copy(history_path, &backup1_path)?;
     
     Ok(backup1_path)
 }
 
-/// Performs atomic write by writing to a temporary file first, then moving
-fn atomic_write(path: &PathBuf, entries: &[LeaderboardEntry]) -> Result<(), LeaderboardError> {
-    let temp_path = path.with_extension("json.tmp");
+/// Age of the newest backup after which an append makes a new one
+const BACKUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The history has no backup yet or the newest one is older than `BACKUP_INTERVAL`
+fn backup_is_due(history_path: &Path) -> bool {
+    fs::metadata(with_suffix(history_path, ".bak"))
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| modified.elapsed().map_or(true, |age| age > BACKUP_INTERVAL))
+        .unwrap_or(true)
+}
+
+/// Performs atomic write of the whole history by writing to a temporary file first, then moving.
+/// The file being replaced is backed up first.
+fn atomic_write(path: &Path, entries: &[LeaderboardEntry]) -> Result<(), LeaderboardError> {
+    let temp_path = with_suffix(path, ".tmp");
+    create_backup(path)?;
     
     // Write to temporary file first
-    let json = serde_json::to_string_pretty(entries)?;
     {
         let mut temp_file = fs::File::create(&temp_path)?;
-        temp_file.write_all(json.as_bytes())?;
+        for entry in entries {
+            writeln!(temp_file, "{}", serde_json::to_string(entry)?)?;
+        }
         temp_file.sync_all()?; // Ensure data is written to disk
     }
     
-    // Verify the written data is valid JSON
-    if let Err(_) = validate_json_file(&temp_path) {
+    // Verify the written data is valid history
+    if let Err(_) = validate_history_file(&temp_path) {
         fs::remove_file(&temp_path).ok(); // Clean up temp file
         return Err(LeaderboardError::IoError(
             std::io::Error::new(
@@ -251,29 +675,49 @@ fn atomic_write(path: &PathBuf, entries: &[LeaderboardEntry]) -> Result<(), Lead
     Ok(())
 }
 
-/// Validates that a JSON file contains valid leaderboard data
-fn validate_json_file(path: &PathBuf) -> Result<(), LeaderboardError> {
+/// Appends one entry as a new line of the history
+fn append_entry(path: &Path, entry: &LeaderboardEntry) -> Result<(), LeaderboardError> {
+    let line = serde_json::to_string(entry)?;
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Parses history in JSON Lines, one entry per line. Lines that don't parse, like one
+/// cut short by a crash, are skipped and counted.
+fn parse_history(content: &str) -> (Vec<LeaderboardEntry>, usize) {
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => skipped += 1,
+        }
+    }
+    (entries, skipped)
+}
+
+/// Validates that every line of a history file is a valid entry
+fn validate_history_file(path: &Path) -> Result<(), LeaderboardError> {
     if !path.exists() {
-        return Ok(()); // Non-existent file is valid (empty leaderboard)
+        return Ok(()); // Non-existent file is valid (empty history)
     }
     
     let content = fs::read_to_string(path)?;
-    if content.trim().is_empty() {
-        return Ok(()); // Empty file is valid
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let _entry: LeaderboardEntry = serde_json::from_str(line)?;
     }
-    
-    // Try to parse as leaderboard entries
-    let _entries: Vec<LeaderboardEntry> = serde_json::from_str(&content)?;
     Ok(())
 }
 
-/// Attempts to recover from backup files if main file is corrupted
+/// Attempts to recover from backup files if main file is unreadable
 /// Tries backup files in order: .bak, .bak2, .bak3
-fn recover_from_backup(leaderboard_path: &PathBuf) -> Result<Vec<LeaderboardEntry>, LeaderboardError> {
+fn recover_from_backup(history_path: &Path) -> Result<Vec<LeaderboardEntry>, LeaderboardError> {
     let backup_files = [
-        leaderboard_path.with_extension("json.bak"),
-        leaderboard_path.with_extension("json.bak2"), 
-        leaderboard_path.with_extension("json.bak3"),
+        with_suffix(history_path, ".bak"),
+        with_suffix(history_path, ".bak2"),
+        with_suffix(history_path, ".bak3"),
     ];
     
     for (i, backup_path) in backup_files.iter().enumerate() {
@@ -281,34 +725,77 @@ fn recover_from_backup(leaderboard_path: &PathBuf) -> Result<Vec<LeaderboardEntr
             continue;
         }
         
-        eprintln!("Attempting to recover leaderboard from backup {}...", i + 1);
+        eprintln!("Attempting to recover history from backup {}...", i + 1);
         
-        if let Ok(()) = validate_json_file(backup_path) {
-            let content = fs::read_to_string(backup_path)?;
-            if content.trim().is_empty() {
+        match fs::read_to_string(backup_path) {
+            Ok(content) if content.trim().is_empty() => {
                 eprintln!("Backup {} is empty, trying next backup...", i + 1);
-                continue;
             }
-            
-            match serde_json::from_str::<Vec<LeaderboardEntry>>(&content) {
-                Ok(entries) => {
+            Ok(content) => match parse_history(&content) {
+                (entries, 0) => {
                     // Restore this backup as main file
-                    fs::copy(backup_path, leaderboard_path)?;
-                    eprintln!("Successfully recovered leaderboard from backup {}", i + 1);
+                    fs::copy(backup_path, history_path)?;
+                    eprintln!("Successfully recovered history from backup {}", i + 1);
                     return Ok(entries);
                 }
-                Err(_) => {
-                    eprintln!("Backup {} is corrupted, trying next backup...", i + 1);
-                    continue;
+                _ => eprintln!("Backup {} is corrupted, trying next backup...", i + 1),
+            },
+            Err(_) => eprintln!("Backup {} can't be read, trying next backup...", i + 1),
+        }
+    }
+    
+    eprintln!("No valid backup found, starting with empty history");
+    Ok(Vec::new())
+}
+
+/// Entries of the old leaderboard file, from its `.bak` copies when it is corrupted and
+/// none when no copy can be read, so a broken old file never blocks the history
+fn load_legacy_entries(legacy_path: &Path) -> Vec<LeaderboardEntry> {
+    let candidates = [
+        legacy_path.to_path_buf(),
+        with_suffix(legacy_path, ".bak"),
+        with_suffix(legacy_path, ".bak2"),
+        with_suffix(legacy_path, ".bak3"),
+    ];
+    
+    for (i, path) in candidates.iter().enumerate() {
+        let Ok(content) = fs::read_to_string(path) else {
+            continue;
+        };
+        if content.trim().is_empty() {
+            if i == 0 {
+                return Vec::new();
+            }
+            continue;
+        }
+        match serde_json::from_str(&content) {
+            Ok(entries) => {
+                if i > 0 {
+                    eprintln!("Recovered old leaderboard from backup {}", i);
                 }
+                return entries;
             }
-        } else {
-            eprintln!("Backup {} failed validation, trying next backup...", i + 1);
+            Err(_) => eprintln!("Old leaderboard file {} is corrupted, trying the next backup...", path.display()),
         }
     }
     
-    eprintln!("No valid backup found, starting with empty leaderboard");
-    Ok(Vec::new())
+    eprintln!("No readable old leaderboard found, starting with empty history");
+    Vec::new()
+}
+
+/// Moves entries of the old `leaderboard.json`, which kept only the top 100 runs, into
+/// the history once. The old file is kept as it was for older versions.
+fn migrate_legacy_leaderboard(config_dir: &Path) -> Result<(), LeaderboardError> {
+    let legacy_path = config_dir.join(LEGACY_LEADERBOARD_FILE);
+    let history_path = config_dir.join(HISTORY_FILE);
+    if history_path.exists() || !legacy_path.exists() {
+        return Ok(());
+    }
+    
+    let mut entries = load_legacy_entries(&legacy_path);
+    // The leaderboard was sorted by WPM, the history is in the order tests were taken
+    entries.sort_by_key(LeaderboardEntry::taken_at);
+    atomic_write(&history_path, &entries)
 }
 
 /// Retry helper for operations that might fail temporarily
@@ -339,50 +826,55 @@ where
     Err(last_error.unwrap())
 }
 
+/// `~/.config/typeman`, unless `TYPEMAN_CONFIG_DIR` is set.
+/// Unit tests use a temporary directory so they never touch the real files.
 pub fn get_config_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let home = std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .map_err(|_| "Unable to find home directory")?;
-    
-    let config_dir = PathBuf::from(home).join(".config").join("typeman");
+    let config_dir = match std::env::var_os(CONFIG_DIR_VAR) {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(test) => std::env::temp_dir().join(format!("typeman-test-{}", std::process::id())),
+        None => {
+            let home = std::env::var("HOME")
+                .or_else(|_| std::env::var("USERPROFILE"))
+                .map_err(|_| "Unable to find home directory")?;
+            PathBuf::from(home).join(".config").join("typeman")
+        }
+    };
     fs::create_dir_all(&config_dir)?;
     Ok(config_dir)
 }
 
+/// Path of the history file, migrating the old leaderboard file the first time
+fn get_history_path() -> Result<PathBuf, LeaderboardError> {
+    let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
+        std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+    ))?;
+    let history_path = config_dir.join(HISTORY_FILE);
+    if !history_path.exists() && config_dir.join(LEGACY_LEADERBOARD_FILE).exists() {
+        let _lock_guard = FileLockGuard::acquire(&config_dir.join(LOCK_FILE), Duration::from_secs(5))?;
+        migrate_legacy_leaderboard(&config_dir)?;
+    }
+    Ok(history_path)
+}
+
+/// Appends a completed test to the history, every test is kept
 pub fn save_entry(entry: &LeaderboardEntry) -> Result<(), LeaderboardError> {
     // Validate entry before saving
     entry.validate()?;
     
-    let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
-        std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
-    ))?;
-    let leaderboard_path = config_dir.join("leaderboard.json");
-    let lock_path = config_dir.join("leaderboard.lock");
+    let history_path = get_history_path()?;
+    let lock_path = history_path.with_file_name(LOCK_FILE);
     
     // Retry the entire save operation up to 3 times for temporary failures
     retry_operation(|| -> Result<(), LeaderboardError> {
         // Acquire file lock with 5-second timeout
         let _lock_guard = FileLockGuard::acquire(&lock_path, Duration::from_secs(5))?;
         
-        // Create backup before making changes
-        create_backup(&leaderboard_path)?;
-        
-        // Load existing entries
-        let mut entries = load_entries().unwrap_or_default();
-        
-        // Add new entry
-        entries.push(entry.clone());
-        
-        // Sort by WPM (highest first)
-        entries.sort_by(|a, b| b.wpm.partial_cmp(&a.wpm).unwrap_or(std::cmp::Ordering::Equal));
-        
-        // Limit to top 100 entries
-        entries.truncate(100);
-        
-        // Save to file using atomic write
-        atomic_write(&leaderboard_path, &entries)?;
+        // Appends only add a line, the whole history is copied now and then
+        if backup_is_due(&history_path) {
+            create_backup(&history_path)?;
+        }
         
-        Ok(())
+        append_entry(&history_path, entry)
     }, 2, Duration::from_millis(100))?; // Retry up to 2 times with 100ms delay
     
     // Invalidate cache after successful save
@@ -391,31 +883,41 @@ pub fn save_entry(entry: &LeaderboardEntry) -> Result<(), LeaderboardError> {
     Ok(())
 }
 
+/// Best runs of the history, highest WPM first
 pub fn load_entries() -> Result<Vec<LeaderboardEntry>, LeaderboardError> {
-    let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
-        std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
-    ))?;
-    let leaderboard_path = config_dir.join("leaderboard.json");
+    Ok(top_entries(load_history()?, LEADERBOARD_SIZE))
+}
+
+/// The `limit` entries with the highest WPM
+pub fn top_entries(mut entries: Vec<LeaderboardEntry>, limit: usize) -> Vec<LeaderboardEntry> {
+    entries.sort_by(|a, b| b.wpm.partial_cmp(&a.wpm).unwrap_or(std::cmp::Ordering::Equal));
+    entries.truncate(limit);
+    entries
+}
+
+/// Every completed test, oldest first
+pub fn load_history() -> Result<Vec<LeaderboardEntry>, LeaderboardError> {
+    let history_path = get_history_path()?;
     
     // Return empty vec if file doesn't exist
-    if !leaderboard_path.exists() {
+    if !history_path.exists() {
         return Ok(Vec::new());
     }
     
     // Check cache first
     if let Ok(cache_guard) = get_cache().lock() {
         if let Some(ref cache) = *cache_guard {
-            if cache.is_valid(&leaderboard_path) {
+            if cache.is_valid(&history_path) {
                 return Ok(cache.entries.clone());
             }
         }
     }
     
     // Cache miss or invalid, load from file
-    let entries = load_entries_from_file(&leaderboard_path)?;
+    let entries = load_history_from_file(&history_path)?;
     
     // Update cache
-    if let Ok(metadata) = fs::metadata(&leaderboard_path) {
+    if let Ok(metadata) = fs::metadata(&history_path) {
         if let Ok(modified) = metadata.modified() {
             if let Ok(mut cache_guard) = get_cache().lock() {
                 *cache_guard = Some(LeaderboardCache {
@@ -431,26 +933,26 @@ pub fn load_entries() -> Result<Vec<LeaderboardEntry>, LeaderboardError> {
 }
 
 /// Load entries directly from file without caching
-fn load_entries_from_file(leaderboard_path: &PathBuf) -> Result<Vec<LeaderboardEntry>, LeaderboardError> {
-    // First try to validate and load the main file
-    if let Ok(()) = validate_json_file(leaderboard_path) {
-        let content = fs::read_to_string(leaderboard_path)?;
-        if content.trim().is_empty() {
-            return Ok(Vec::new());
+fn load_history_from_file(history_path: &Path) -> Result<Vec<LeaderboardEntry>, LeaderboardError> {
+    let content = match fs::read_to_string(history_path) {
+        Ok(content) => content,
+        Err(_) => {
+            eprintln!("History file can't be read, attempting recovery...");
+            return recover_from_backup(history_path);
         }
-        
-        match serde_json::from_str(&content) {
-            Ok(entries) => return Ok(entries),
-            Err(_) => {
-                eprintln!("Main leaderboard file appears corrupted, attempting recovery...");
-            }
+    };
+    
+    match parse_history(&content) {
+        (entries, 0) => Ok(entries),
+        (entries, skipped) if !entries.is_empty() => {
+            eprintln!("Skipped {} corrupted history lines", skipped);
+            Ok(entries)
+        }
+        _ => {
+            eprintln!("History file appears corrupted, attempting recovery...");
+            recover_from_backup(history_path)
         }
-    } else {
-        eprintln!("Main leaderboard file validation failed, attempting recovery...");
     }
-    
-    // If main file is corrupted, try to recover from backup
-    recover_from_backup(leaderboard_path)
 }
 
 #[cfg(test)]
@@ -460,6 +962,7 @@ mod tests {
     #[test]
     fn test_leaderboard_entry_serialization() {
         let entry = LeaderboardEntry {
+            version: ENTRY_VERSION,
             wpm: 85.5,
             accuracy: 98.2,
             test_type: TestType::Time(30),
@@ -468,6 +971,14 @@ mod tests {
             test_duration: 30.0,
             timestamp: "2025-09-11T10:30:00Z".to_string(),
             language: Language::English,
+            quote: None,
+            punctuation: false,
+            digits: false,
+            top_words: Some(500),
+            batch_size: Some(50),
+            raw_wpm: Some(90.0),
+            consistency: Some(80.0),
+            errors: Some(3),
         };
 
         // Test serialization
@@ -511,6 +1022,7 @@ mod tests {
     fn test_save_and_load_entries() {
         // This test will fail until implementation
         let entry = LeaderboardEntry {
+            version: ENTRY_VERSION,
             wpm: 85.5,
             accuracy: 98.2,
             test_type: TestType::Time(30),
@@ -519,6 +1031,14 @@ mod tests {
             test_duration: 30.0,
             timestamp: "2025-09-11T10:30:00Z".to_string(),
             language: Language::English,
+            quote: None,
+            punctuation: false,
+            digits: false,
+            top_words: Some(500),
+            batch_size: Some(50),
+            raw_wpm: Some(90.0),
+            consistency: Some(80.0),
+            errors: Some(3),
         };
 
         // Test saving entry
@@ -534,6 +1054,7 @@ mod tests {
         
         // Find our entry in the list (entries are sorted by WPM, so it might not be first)
         let found_entry = entries.iter().find(|e| {
+            e.version == entry.version &&
             e.wpm == entry.wpm && 
             e.accuracy == entry.accuracy &&
             e.test_mode == entry.test_mode &&
@@ -555,4 +1076,202 @@ mod tests {
         // Could be empty on first run - just verify it's a valid vector
         assert!(entries.is_empty() || !entries.is_empty(), "Should return valid entries vector");
     }
-}
\ No newline at end of file
+
+    fn entry_at(wpm: f64, timestamp: &str) -> LeaderboardEntry {
+        LeaderboardEntry {
+            version: ENTRY_VERSION,
+            wpm,
+            accuracy: 97.0,
+            test_type: TestType::Word(25),
+            test_mode: "word".to_string(),
+            word_count: 25,
+            test_duration: 20.0,
+            timestamp: timestamp.to_string(),
+            language: Language::English,
+            quote: None,
+            punctuation: false,
+            digits: false,
+            top_words: Some(500),
+            batch_size: Some(50),
+            raw_wpm: Some(90.0),
+            consistency: Some(80.0),
+            errors: Some(3),
+        }
+    }
+
+    #[test]
+    fn test_parse_history_skips_broken_lines() {
+        let first = serde_json::to_string(&entry_at(60.0, "2025-09-11T10:00:00Z")).unwrap();
+        let second = serde_json::to_string(&entry_at(70.0, "2025-09-11T11:00:00Z")).unwrap();
+        let content = format!("{}\n\n{}\n{}", first, second, &second[..second.len() / 2]);
+        let (entries, skipped) = parse_history(&content);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(skipped, 1);
+        assert_eq!(entries[1].wpm, 70.0);
+    }
+
+    #[test]
+    fn test_leaderboard_is_top_of_history() {
+        let history: Vec<LeaderboardEntry> = (0..150)
+            .map(|i| entry_at(i as f64, "2025-09-11T10:00:00Z"))
+            .collect();
+        let top = top_entries(history, LEADERBOARD_SIZE);
+        assert_eq!(top.len(), LEADERBOARD_SIZE);
+        assert_eq!(top[0].wpm, 149.0);
+        assert_eq!(top.last().unwrap().wpm, 50.0);
+    }
+
+    #[test]
+    fn test_migrate_legacy_leaderboard() {
+        let dir = std::env::temp_dir().join(format!("typeman-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = vec![
+            entry_at(90.0, "2025-09-12T10:00:00Z"),
+            entry_at(80.0, "2025-09-11T10:00:00Z"),
+        ];
+        fs::write(dir.join(LEGACY_LEADERBOARD_FILE), serde_json::to_string(&legacy).unwrap()).unwrap();
+
+        migrate_legacy_leaderboard(&dir).unwrap();
+        let history = load_history_from_file(&dir.join(HISTORY_FILE)).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].wpm, 80.0, "history should be oldest first");
+        assert!(dir.join(LEGACY_LEADERBOARD_FILE).exists());
+
+        // Appending keeps every run, migrating again doesn't duplicate them
+        append_entry(&dir.join(HISTORY_FILE), &entry_at(40.0, "2025-09-13T10:00:00Z")).unwrap();
+        migrate_legacy_leaderboard(&dir).unwrap();
+        assert_eq!(load_history_from_file(&dir.join(HISTORY_FILE)).unwrap().len(), 3);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_query_filters_and_sorts() {
+        let mut history = vec![
+            entry_at(60.0, "2025-09-11T10:00:00Z"),
+            entry_at(80.0, "2025-09-12T10:00:00Z"),
+            entry_at(70.0, "2025-09-13T10:00:00Z"),
+        ];
+        history[1].test_type = TestType::Time(15);
+        history[2].punctuation = true;
+        history[2].accuracy = 100.0;
+
+        let mut query = LeaderboardQuery::default();
+        let wpm: Vec<f64> = query.apply(&history).iter().map(|entry| entry.wpm).collect();
+        assert_eq!(wpm, vec![80.0, 70.0, 60.0]);
+
+        query.sort = SortKey::Date;
+        assert_eq!(query.apply(&history)[0].wpm, 70.0);
+        // Taken at 09:30 UTC, before the 10:00 UTC run even though the string sorts after it
+        history.push(entry_at(50.0, "2025-09-13T11:30:00+02:00"));
+        let wpm: Vec<f64> = query.apply(&history).iter().map(|entry| entry.wpm).collect();
+        assert_eq!(wpm, vec![70.0, 50.0, 80.0, 60.0]);
+        history.pop();
+        query.sort = SortKey::Accuracy;
+        assert_eq!(query.apply(&history)[0].wpm, 70.0);
+
+        query.next_test_type(&history);
+        assert_eq!(query.test_type, Some(TestType::Time(15)));
+        assert_eq!(query.apply(&history).len(), 1);
+        query.next_test_type(&history);
+        query.next_punctuation();
+        assert_eq!(query.apply(&history).len(), 1);
+        query.next_punctuation();
+        query.next_punctuation();
+        query.next_test_type(&history);
+        assert_eq!(query, LeaderboardQuery { sort: SortKey::Accuracy, ..Default::default() });
+
+        query.dates = DateRange::Today;
+        assert!(query.apply(&history).is_empty());
+    }
+
+    #[test]
+    fn test_personal_bests_per_category() {
+        let history = vec![
+            entry_at(60.0, "2025-09-11T10:00:00Z"),
+            entry_at(75.0, "2025-09-12T10:00:00Z"),
+            LeaderboardEntry { test_type: TestType::Time(120), ..entry_at(55.0, "2025-09-13T10:00:00Z") },
+            LeaderboardEntry { digits: true, ..entry_at(50.0, "2025-09-14T10:00:00Z") },
+        ];
+        let query = LeaderboardQuery { personal_bests: true, ..Default::default() };
+        let wpm: Vec<f64> = query.apply(&history).iter().map(|entry| entry.wpm).collect();
+        assert_eq!(wpm, vec![75.0, 55.0, 50.0]);
+    }
+
+    #[test]
+    fn test_legacy_entries_read_as_version_one() {
+        let json = r#"{"wpm":70.0,"accuracy":96.0,"test_type":{"Time":30},"test_mode":"time","word_count":35,"test_duration":30.0,"timestamp":"2025-09-11T10:00:00Z","language":"English"}"#;
+        let entry: LeaderboardEntry = serde_json::from_str(json).unwrap();
+        assert_eq!(entry.version, 1);
+        assert_eq!(entry.raw_wpm, None);
+        assert_eq!(entry.top_words, None);
+        assert!(entry.validate().is_ok());
+
+        // The same entry claiming the current version misses its stats
+        let entry = LeaderboardEntry { version: ENTRY_VERSION, ..entry };
+        assert!(matches!(entry.validate(), Err(ValidationError::MissingField(_))));
+    }
+
+    #[test]
+    fn test_entry_from_session() {
+        use crate::typing_session::SessionMode;
+
+        let config = TestConfig { punctuation: true, digits: true, top_words: 200, batch_size: 25 };
+        let mut session = TypingSession::new("ab cd".to_string(), SessionMode::Words(2));
+        for c in "ax cd".chars() {
+            session.type_char(c);
+        }
+        session.start_time = Some(Instant::now() - Duration::from_secs(10));
+        let entry = LeaderboardEntry::from_session(&session, TestType::Word(2), "word", Language::English, config);
+        assert_eq!(entry.version, ENTRY_VERSION);
+        assert_eq!(entry.errors, Some(1));
+        assert_eq!((entry.punctuation, entry.top_words, entry.batch_size), (true, Some(200), Some(25)));
+        assert!(entry.validate().is_ok());
+
+        let entry = LeaderboardEntry::from_session(&session, TestType::Quote, "quote", Language::English, config);
+        assert_eq!(entry.quote.as_deref(), Some("ab cd"));
+        assert_eq!((entry.punctuation, entry.digits, entry.top_words), (false, false, None));
+
+        // Packs without a frequency order record the words they actually have
+        let config = TestConfig { top_words: 1000, ..config };
+        let entry = LeaderboardEntry::from_session(&session, TestType::Word(2), "word", Language::French, config);
+        assert_eq!(entry.top_words, Some(Language::French.word_count()));
+        assert!(Language::French.word_count() < 1000);
+    }
+
+    #[test]
+    fn test_migrate_corrupted_legacy_leaderboard() {
+        let dir = std::env::temp_dir().join(format!("typeman-migrate-broken-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let legacy_path = dir.join(LEGACY_LEADERBOARD_FILE);
+        fs::write(&legacy_path, "[{\"wpm\": 80.0, \"accur").unwrap();
+        let backup = vec![entry_at(70.0, "2025-09-11T10:00:00Z")];
+        fs::write(with_suffix(&legacy_path, ".bak"), serde_json::to_string(&backup).unwrap()).unwrap();
+
+        migrate_legacy_leaderboard(&dir).unwrap();
+        assert_eq!(load_history_from_file(&dir.join(HISTORY_FILE)).unwrap(), backup);
+
+        // Without a readable backup the history starts empty instead of failing
+        fs::remove_file(dir.join(HISTORY_FILE)).unwrap();
+        fs::write(with_suffix(&legacy_path, ".bak"), "not json").unwrap();
+        migrate_legacy_leaderboard(&dir).unwrap();
+        assert!(dir.join(HISTORY_FILE).exists());
+        assert!(load_history_from_file(&dir.join(HISTORY_FILE)).unwrap().is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_backups_are_not_made_on_every_append() {
+        let dir = std::env::temp_dir().join(format!("typeman-backups-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let history_path = dir.join(HISTORY_FILE);
+        append_entry(&history_path, &entry_at(60.0, "2025-09-11T10:00:00Z")).unwrap();
+        assert!(backup_is_due(&history_path));
+
+        create_backup(&history_path).unwrap();
+        assert!(!backup_is_due(&history_path));
+        // A rewrite always backs up the file it replaces
+        atomic_write(&history_path, &[]).unwrap();
+        assert!(with_suffix(&history_path, ".bak2").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -14,6 +14,8 @@ pub mod ui {
     #[cfg(feature = "gui")]
     pub mod gui {
         pub mod config;
+        pub mod heatmap;
+        pub mod leaderboard;
         pub mod main;
         pub mod popup;
         pub mod practice;
@@ -42,6 +44,23 @@ pub mod word_number_selection;
 pub mod settings;
 pub mod batch_size_selection;
 pub mod top_words_selection;
+pub mod typing_session;
+pub mod keystroke_log;
+pub mod replay;
+pub mod ghost;
+pub mod pacer;
+pub mod key_stats;
+pub mod adaptive;
+pub mod keyboard_layout;
+pub mod finger_stats;
+pub mod word_source;
+pub mod word_sampling;
+pub mod code_snippets;
+pub mod text_library;
+pub mod text_import;
+pub mod normalization;
+pub mod compose;
+pub mod quotes;
 
 // Re-export types needed by modules
 #[derive(Parser)]
@@ -82,9 +101,48 @@ pub struct Cli {
 
     #[arg(long = "lang", value_name = "LANGUAGE")]
     pub language: Option<String>,
+
+    #[arg(long = "adaptive")]
+    pub adaptive: bool,
+
+    #[arg(long = "pace", value_name = "WPM")]
+    pub pace: Option<u32>,
+
+    #[arg(long = "layout", value_name = "LAYOUT")]
+    pub layout: Option<String>,
+
+    #[arg(long = "sampling", value_name = "MODE")]
+    pub sampling: Option<String>,
+
+    #[arg(long = "no-repeat", value_name = "N")]
+    pub no_repeat: Option<usize>,
+
+    #[arg(long = "import", value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub import: Option<PathBuf>,
+
+    #[arg(long = "texts")]
+    pub texts: bool,
+
+    #[arg(long = "delete-text", value_name = "NAME")]
+    pub delete_text: Option<String>,
+
+    #[arg(long = "text", value_name = "NAME")]
+    pub text: Option<String>,
+
+    #[arg(long = "chunk", value_name = "WORDS")]
+    pub chunk: Option<usize>,
+
+    #[arg(long = "quote-length", value_name = "LENGTH")]
+    pub quote_length: Option<String>,
+
+    #[arg(long = "favorite")]
+    pub favorite: bool,
+
+    #[arg(long = "no-save")]
+    pub no_save: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Quote {
     pub author: String,
     pub text: String,
@@ -17,6 +17,8 @@ pub mod ui {
     #[cfg(feature = "gui")]
     pub mod gui {
         pub mod config;
+        pub mod heatmap;
+        pub mod leaderboard;
         pub mod main;
         pub mod popup;
         pub mod practice;
@@ -45,6 +47,23 @@ pub mod word_number_selection;
 pub mod settings;
 pub mod batch_size_selection;
 pub mod top_words_selection;
+pub mod typing_session;
+pub mod keystroke_log;
+pub mod replay;
+pub mod ghost;
+pub mod pacer;
+pub mod key_stats;
+pub mod adaptive;
+pub mod keyboard_layout;
+pub mod finger_stats;
+pub mod word_source;
+pub mod word_sampling;
+pub mod code_snippets;
+pub mod text_library;
+pub mod text_import;
+pub mod normalization;
+pub mod compose;
+pub mod quotes;
 
 #[cfg(feature = "cli")]
 use crate::ui::cli::modes;
@@ -63,21 +82,44 @@ use crate::ui::gui::main as gui;
     after_long_help = "Run examples:
 typeman --cli -c ./text.txt
 typeman --cli -q
+typeman --cli -q --quote-length=short --lang=french
+typeman --cli --favorite
+typeman --cli -t=30 --no-save
 typeman --cli -t=30 -n=500
 typeman --cli -w=50 -n=500
 typeman --cli -w=50 -n=500 -p -d
 typeman --cli --wiki
+typeman --cli --adaptive
+typeman --cli -t=30 --pace=60
+typeman --cli -l=1 --layout=colemak
+typeman --cli -t=30 --sampling=frequency --no-repeat=3
+typeman --cli --import ./book.txt
+typeman --cli --texts
+typeman --cli --text=book --chunk=100
+typeman --cli --delete-text=book
+typeman --replay ~/.config/typeman/keystrokes/<log>.json
+typeman --ghost ~/.config/typeman/keystrokes/<log>.json
 typeman --gui
 typeman",
     long_about = "\n
 Run 'typeman --cli -c <path/to/your/file>' to test your typing on a specified text
-Run 'typeman --cli -q' to test your typing on a random quote
+Run 'typeman --cli -q' to test your typing on a random quote, then 'typeman --cli --favorite' to keep it among your favorites
 Run 'typeman --cli -w=50 (-n=500 -p -d)' to test your typing on n most common English words, specify the -w for number of words (default is 50)
 Run 'typeman --cli (-t=30 -n=500 -p -d)' to test your typing on random words for t seconds; -t sets the time limit (default is 30 seconds)
+Run 'typeman --cli --adaptive (-w=50)' to practice the keys you miss most or type slowest
+Run 'typeman --cli --import <FILE>' to add a text to the library, then 'typeman --cli --text <NAME>' to type through it chunk by chunk
 Run 'typeman (--tui)' to start the terminal-based interface
+Run 'typeman --replay <FILE>' to play back a recorded test in the terminal-based interface
+Run 'typeman (--gui) --ghost <FILE>' to race time and word tests against a recorded test
 
 Optional:
   - Use -p to include punctuation, -d to include digits
+  - Use --pace to race a caret moving at a fixed wpm in cli tests
+  - Use --layout to practice levels for a keyboard layout other than the one in settings
+  - Use --sampling=frequency to draw common words more often and --no-repeat to space out repeated words
+  - Use --chunk to set how many words of a library text each test covers
+  - Use --quote-length to pick short, medium, long or thicc quotes, or only your favorites
+  - Use --no-save to keep a test out of the leaderboard, history, practice progress and text library
   - Use -n to specify the number of words to type (default is 50, max is 500)
   - Use -t to set a time limit for the test (default is 30 seconds, use 0 for no limit)
   - Use -n to specify the number of top words to use (default is 500, max is 1000)
@@ -122,31 +164,76 @@ pub struct Cli {
     #[arg(long = "cli", conflicts_with_all = &["tui", "gui"])]
     cli: bool,
 
+    #[arg(long = "adaptive", conflicts_with_all = &["custom_file", "random_quote", "time_limit", "level", "wiki", "gui", "tui"], help = "Practice the keys and letter pairs you miss most or type slowest")]
+    adaptive: bool,
+
     #[arg(long = "wiki", conflicts_with_all = &["custom_file", "random_quote", "time_limit", "top_words", "word_number", "level", "tui"])]
     wiki: bool,
 
     #[arg(
         long = "lang",
         value_name = "LANGUAGE",
-        help = "Language for word lists (english, indonesian)"
+        help = "Language for word lists, quotes, wiki summaries and practice lessons: english, indonesian, italian, german, french, spanish, portuguese, polish, russian, code or a pack from ~/.config/typeman/languages"
     )]
     language: Option<String>,
+
+    #[arg(long = "replay", value_name = "FILE", value_hint = ValueHint::FilePath, conflicts_with_all = &["custom_file", "random_quote", "time_limit", "top_words", "word_number", "level", "gui", "cli", "wiki"])]
+    replay: Option<PathBuf>,
+
+    #[arg(long = "ghost", value_name = "FILE", value_hint = ValueHint::FilePath, conflicts_with_all = &["cli", "replay"])]
+    ghost: Option<PathBuf>,
+
+    #[arg(long = "pace", value_name = "WPM", conflicts_with_all = &["gui", "tui", "level"], help = "Target wpm of the pacer caret, 0 turns it off (default from settings)")]
+    pace: Option<u32>,
+
+    #[arg(long = "layout", value_name = "LAYOUT", conflicts_with_all = &["gui", "tui"], help = "Keyboard layout for practice levels: qwerty, dvorak, colemak, workman, azerty, qwertz, ЙЦУКЕН or a custom one (default from settings)")]
+    layout: Option<String>,
+
+    #[arg(long = "sampling", value_name = "MODE", conflicts_with_all = &["gui", "tui"], help = "How words are drawn: uniform, or frequency to favour common words (default from settings)")]
+    sampling: Option<String>,
+
+    #[arg(long = "no-repeat", value_name = "N", conflicts_with_all = &["gui", "tui"], help = "Keep a word from coming back within N words, 1 only forbids immediate repeats (default from settings)")]
+    no_repeat: Option<usize>,
+
+    #[arg(long = "import", value_name = "FILE", value_hint = ValueHint::FilePath, conflicts_with_all = &["custom_file", "random_quote", "level", "wiki", "gui", "tui"], help = "Add a text to the library in ~/.config/typeman/texts: plain text, Markdown, HTML or EPUB")]
+    import: Option<PathBuf>,
+
+    #[arg(long = "texts", conflicts_with_all = &["custom_file", "random_quote", "level", "wiki", "gui", "tui"], help = "List the library texts with their progress and results")]
+    texts: bool,
+
+    #[arg(long = "delete-text", value_name = "NAME", conflicts_with_all = &["custom_file", "random_quote", "level", "wiki", "gui", "tui"], help = "Remove a text from the library")]
+    delete_text: Option<String>,
+
+    #[arg(long = "text", value_name = "NAME", conflicts_with_all = &["custom_file", "random_quote", "time_limit", "word_number", "level", "wiki", "gui", "tui"], help = "Type the next chunk of a library text")]
+    text: Option<String>,
+
+    #[arg(long = "chunk", value_name = "WORDS", conflicts_with_all = &["gui", "tui"], help = "Words per chunk of library texts (default from settings)")]
+    chunk: Option<usize>,
+
+    #[arg(long = "quote-length", value_name = "LENGTH", conflicts_with_all = &["gui", "tui"], help = "Quotes to pick from: all, short, medium, long, thicc or favorites (default from settings)")]
+    quote_length: Option<String>,
+
+    #[arg(long = "favorite", conflicts_with_all = &["custom_file", "random_quote", "level", "wiki", "gui", "tui"], help = "Add the last typed quote to the favorites, or remove it")]
+    favorite: bool,
+
+    #[arg(long = "no-save", conflicts_with_all = &["gui", "tui"], help = "Don't record the result in the leaderboard history, keystroke logs, practice progress or text library")]
+    no_save: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Quote {
     author: String,
     text: String,
 }
 
 #[cfg(feature = "gui")]
-pub fn gui_main() {
-    macroquad::Window::new("TypeMan", async { gui::gui_main_async().await });
+pub fn gui_main(ghost: Option<ghost::Ghost>) {
+    macroquad::Window::new("TypeMan", async { gui::gui_main_async(ghost).await });
 }
 
 #[cfg(feature = "tui")]
-pub fn tui_main() {
-    if let Err(e) = tui_mod::main() {
+pub fn tui_main(ghost: Option<ghost::Ghost>) {
+    if let Err(e) = tui_mod::main(ghost) {
         eprintln!("TUI error: {}", e);
         std::process::exit(1);
     }
@@ -165,14 +252,36 @@ fn main() {
         std::process::exit(1);
     }
 
+    if args.replay.is_some() && !cfg!(feature = "tui") {
+        eprintln!("Replay is not available in this build.");
+        std::process::exit(1);
+    }
+
     if args.cli && !cfg!(feature = "cli") {
         eprintln!("CLI mode is not available in this build.");
         std::process::exit(1);
     }
 
+    #[cfg(feature = "tui")]
+    if let Some(path) = args.replay.as_ref() {
+        if let Err(e) = tui_mod::replay(path) {
+            eprintln!("Replay error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    #[cfg(any(feature = "gui", feature = "tui"))]
+    let ghost = args.ghost.as_ref().map(|path| {
+        ghost::Ghost::from_file(path).unwrap_or_else(|e| {
+            eprintln!("Failed to load ghost: {:?}", e);
+            std::process::exit(1);
+        })
+    });
+
     #[cfg(feature = "gui")]
     if args.gui {
-        gui_main();
+        gui_main(ghost);
         return;
     }
 
@@ -184,23 +293,35 @@ fn main() {
 
     #[cfg(feature = "tui")]
     {
-        ui::tui::r#mod::main().unwrap();
+        ui::tui::r#mod::main(ghost).unwrap();
         return;
     }
 }
 
 #[cfg(feature = "cli")]
 fn run_cli(args: &Cli) {
-    if let Some(path) = args.custom_file.as_ref() {
-        modes::custom_text(path)
+    if let Some(path) = args.import.as_ref() {
+        modes::import_text(path);
+    } else if args.texts {
+        modes::list_texts();
+    } else if let Some(name) = args.delete_text.as_ref() {
+        modes::delete_text(name);
+    } else if args.favorite {
+        modes::toggle_favorite();
+    } else if let Some(name) = args.text.as_ref() {
+        modes::library_text(name, args);
+    } else if let Some(path) = args.custom_file.as_ref() {
+        modes::custom_text(path, args)
     } else if args.random_quote {
-        modes::quotes();
+        modes::quotes(args);
     } else if args.level.is_some() {
         modes::practice(args);
+    } else if args.adaptive {
+        modes::adaptive(args);
     } else if args.word_number.is_some() && args.time_limit.is_none() {
         modes::word_mode(args);
     } else if args.wiki {
-        modes::wiki_mode();
+        modes::wiki_mode(args);
     } else {
         modes::time_mode(args);
     }
@@ -0,0 +1,179 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// How typed chars are matched against chars of the reference that are not on a keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum Strictness {
+    /// The reference is typed as it is, curly quotes and accents included
+    Exact,
+    /// The reference is folded to ASCII, e.g. "café – “ok”" becomes "cafe - \"ok\""
+    AsciiFold,
+    /// Either the original char or its ASCII fold counts as correct.
+    /// Folds longer than one char, like "..." for "…" or "ss" for "ß", are typed key by key.
+    #[default]
+    Either,
+}
+
+impl Strictness {
+    pub fn all() -> &'static [Strictness] {
+        &[Strictness::Exact, Strictness::AsciiFold, Strictness::Either]
+    }
+
+    pub fn count() -> usize {
+        Self::all().len()
+    }
+
+    pub fn next(&self) -> Strictness {
+        let all = Self::all();
+        let i = all.iter().position(|strictness| strictness == self).unwrap_or(0);
+        all[(i + 1) % all.len()]
+    }
+}
+
+impl Display for Strictness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strictness::Exact => write!(f, "Exact"),
+            Strictness::AsciiFold => write!(f, "ASCII Fold"),
+            Strictness::Either => write!(f, "Either"),
+        }
+    }
+}
+
+/// Keyboard equivalent of typographic quotes, dashes, ellipses, bullets and spaces
+pub fn typable_punctuation(c: char) -> Option<&'static str> {
+    match c {
+        '‘' | '’' | '‚' | '‛' | '′' => Some("'"),
+        '“' | '”' | '„' | '‟' | '″' | '«' | '»' => Some("\""),
+        '‹' => Some("<"),
+        '›' => Some(">"),
+        '‐' | '‑' | '‒' | '–' | '−' => Some("-"),
+        '—' | '―' => Some("--"),
+        '…' => Some("..."),
+        '•' => Some("-"),
+        '\u{a0}' | '\u{2002}'..='\u{200a}' | '\u{202f}' | '\u{205f}' | '\u{3000}' => Some(" "),
+        // Soft hyphens, zero width characters and byte order marks can't be typed
+        '\u{ad}' | '\u{200b}'..='\u{200d}' | '\u{2060}' | '\u{feff}' => Some(""),
+        _ => None,
+    }
+}
+
+/// ASCII equivalent of a char, None when it is already ASCII or has none
+pub fn ascii_fold(c: char) -> Option<String> {
+    if c.is_ascii() {
+        return None;
+    }
+    if let Some(plain) = typable_punctuation(c) {
+        return Some(plain.to_string());
+    }
+    let special = match c {
+        'ß' => "ss",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ø' => "o",
+        'Ø' => "O",
+        'ł' => "l",
+        'Ł' => "L",
+        'đ' => "d",
+        'Đ' => "D",
+        'ð' => "d",
+        'Ð' => "D",
+        'þ' => "th",
+        'Þ' => "Th",
+        'ı' => "i",
+        _ => "",
+    };
+    if !special.is_empty() {
+        return Some(special.to_string());
+    }
+    // Accented letters decompose into an ASCII letter and combining marks
+    let base: String = c.nfd().filter(|mark| !is_combining_mark(*mark)).collect();
+    (base.is_ascii() && !base.is_empty()).then_some(base)
+}
+
+/// Letter without its accents, e.g. 'e' for 'é' and 'l' for 'ł'
+pub fn base_letter(c: char) -> char {
+    let fold = ascii_fold(c).unwrap_or_default();
+    let mut chars = fold.chars();
+    match (chars.next(), chars.next()) {
+        (Some(base), None) if base.is_alphabetic() => base,
+        _ => c,
+    }
+}
+
+/// `typed` is the right letter with wrong or missing accents, e.g. 'e' or 'è' for 'é'
+pub fn is_accent_mistake(expected: char, typed: char) -> bool {
+    expected != typed && expected.is_alphabetic() && base_letter(expected) == base_letter(typed)
+}
+
+/// Reference text ready to be typed with the given strictness. Every mode composes
+/// accents into single chars and replaces odd spaces and invisible chars.
+pub fn normalize(text: &str, strictness: Strictness) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for c in text.replace("\r\n", "\n").nfc() {
+        let space = typable_punctuation(c).filter(|plain| plain.trim().is_empty()).map(str::to_string);
+        let fold = match strictness {
+            Strictness::Exact | Strictness::Either => space,
+            Strictness::AsciiFold => ascii_fold(c),
+        };
+        match fold {
+            Some(fold) => normalized.push_str(&fold),
+            None => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// Whether `typed` counts as `expected` with the given strictness
+pub fn chars_match(expected: char, typed: char, strictness: Strictness) -> bool {
+    if expected == typed {
+        return true;
+    }
+    strictness == Strictness::Either
+        && ascii_fold(expected).is_some_and(|fold| fold.chars().eq(std::iter::once(typed)))
+}
+
+/// Keys that type `expected` one after another, for chars like 'ß' or '…' whose
+/// ASCII fold is longer than one char. Only `Strictness::Either` accepts them.
+pub fn fold_keys(expected: char, strictness: Strictness) -> Option<Vec<char>> {
+    if strictness != Strictness::Either {
+        return None;
+    }
+    ascii_fold(expected)
+        .map(|fold| fold.chars().collect::<Vec<char>>())
+        .filter(|keys| keys.len() > 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_strictness() {
+        let text = "Café\u{a0}“ok” – e\u{301}t\u{e9}\u{200b}… Straße";
+        assert_eq!(normalize(text, Strictness::Exact), "Café “ok” – été… Straße");
+        assert_eq!(normalize(text, Strictness::AsciiFold), "Cafe \"ok\" - ete... Strasse");
+        assert_eq!(normalize(text, Strictness::Either), "Café “ok” – été… Straße");
+    }
+
+    #[test]
+    fn test_chars_match() {
+        assert!(chars_match('é', 'e', Strictness::Either));
+        assert!(chars_match('é', 'é', Strictness::Either));
+        assert!(chars_match('’', '\'', Strictness::Either));
+        assert!(!chars_match('é', 'e', Strictness::Exact));
+        assert!(!chars_match('é', 'a', Strictness::Either));
+        assert!(is_accent_mistake('é', 'e'));
+        assert!(is_accent_mistake('ł', 'l'));
+        assert!(!is_accent_mistake('e', 'E'));
+        assert!(!is_accent_mistake('é', 'a'));
+        assert_eq!(fold_keys('ß', Strictness::Either), Some(vec!['s', 's']));
+        assert_eq!(fold_keys('ß', Strictness::Exact), None);
+        assert_eq!(fold_keys('é', Strictness::Either), None);
+    }
+}
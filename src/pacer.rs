@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use crate::ghost;
+use crate::typing_session::{TypingSession, CHARS_PER_WORD};
+
+/// Target speeds offered in the settings, 0 turns the pacer off
+pub const PRESETS: [u32; 6] = [0, 40, 60, 80, 100, 120];
+
+/// Caret moving through the reference at exactly the target wpm
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pacer {
+    pub wpm: u32,
+    /// Seconds spent at or ahead of the pacer
+    pub ahead_secs: f64,
+    /// Seconds spent behind the pacer
+    pub behind_secs: f64,
+    last_sample: Duration,
+}
+
+/// Next target in `PRESETS`, wrapping back to off
+pub fn next_preset(wpm: u32) -> u32 {
+    PRESETS
+        .iter()
+        .copied()
+        .find(|preset| *preset > wpm)
+        .unwrap_or(PRESETS[0])
+}
+
+impl Pacer {
+    /// Pacer for the target wpm, None when the target is 0
+    pub fn new(wpm: u32) -> Option<Self> {
+        (wpm > 0).then_some(Self {
+            wpm,
+            ahead_secs: 0.0,
+            behind_secs: 0.0,
+            last_sample: Duration::ZERO,
+        })
+    }
+
+    pub fn chars_per_second(&self) -> f64 {
+        self.wpm as f64 * CHARS_PER_WORD / 60.0
+    }
+
+    /// Chars the pacer has typed after `elapsed`
+    pub fn chars_at(&self, elapsed: Duration) -> usize {
+        (elapsed.as_secs_f64() * self.chars_per_second()) as usize
+    }
+
+    /// Pacer caret in the current batch of `session`, None before the start or outside the batch
+    pub fn cursor(&self, session: &TypingSession) -> Option<usize> {
+        if !session.is_started() {
+            return None;
+        }
+        let pos = self.chars_at(session.elapsed()).checked_sub(ghost::batch_offset(session))?;
+        (pos < session.ref_chars.len()).then_some(pos)
+    }
+
+    /// Adds the time since the last sample to the ahead or behind total
+    pub fn record(&mut self, elapsed: Duration, progress: usize) {
+        let step = elapsed.saturating_sub(self.last_sample).as_secs_f64();
+        self.last_sample = self.last_sample.max(elapsed);
+        if progress >= self.chars_at(elapsed) {
+            self.ahead_secs += step;
+        } else {
+            self.behind_secs += step;
+        }
+    }
+
+    /// Samples a running session, call it every frame
+    pub fn update(&mut self, session: &TypingSession) {
+        if session.is_started() {
+            self.record(session.elapsed(), ghost::progress(session));
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} wpm pace: {:.1}s ahead, {:.1}s behind",
+            self.wpm, self.ahead_secs, self.behind_secs
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pacer_speed() {
+        let pacer = Pacer::new(60).unwrap();
+        assert_eq!(pacer.chars_at(Duration::from_secs(2)), 12);
+        assert!(Pacer::new(0).is_none());
+    }
+
+    #[test]
+    fn test_pacer_records_ahead_and_behind() {
+        let mut pacer = Pacer::new(60).unwrap();
+        pacer.record(Duration::from_secs(1), 6);
+        pacer.record(Duration::from_secs(3), 14);
+        pacer.record(Duration::from_secs(4), 25);
+        assert_eq!(pacer.ahead_secs, 2.0);
+        assert_eq!(pacer.behind_secs, 2.0);
+    }
+
+    #[test]
+    fn test_next_preset_wraps() {
+        assert_eq!(next_preset(0), 40);
+        assert_eq!(next_preset(70), 80);
+        assert_eq!(next_preset(120), 0);
+    }
+}
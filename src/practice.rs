@@ -1,12 +1,21 @@
 use rand::prelude::IndexedRandom;
 
-use {
-    std::io::Write,
-    std::path::Path,
-    std::fs,
-};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::AppConfig;
+use crate::keyboard_layout::KeyboardLayout;
+use crate::language::Language;
+use crate::leaderboard::{get_config_dir, write_atomic, LeaderboardError};
 
 pub const WPM_MIN: f64 = 35.0;
+/// Accuracy in percent a level has to be typed with to pass
+pub const ACCURACY_MIN: f64 = 90.0;
+
+/// Folder older versions wrote results to, relative to where typeman was started
+const LEGACY_RESULTS_DIR: &str = "practice_results";
 
 pub const TYPING_LEVELS: [(&str, &[char]); 32] = [
     ("new: f & j", &['f', 'j']),
@@ -53,6 +62,49 @@ pub const TYPING_LEVELS: [(&str, &[char]); 32] = [
 ];
 
 
+/// `TYPING_LEVELS` moved to `layout`, each key replaced by the one in the same
+/// position, so a Colemak typist starts with "new: t & n", followed by the lessons
+/// of the language
+pub fn levels(layout: &KeyboardLayout, language: Language) -> Vec<(String, Vec<char>)> {
+    let mut levels: Vec<(String, Vec<char>)> = TYPING_LEVELS
+        .iter()
+        .map(|(name, chars)| {
+            let moved: Vec<char> = chars.iter().map(|c| layout.from_qwerty(*c)).collect();
+            (level_name(name, chars, layout), moved)
+        })
+        .collect();
+    for lesson in language.lessons() {
+        levels.push((lesson.name, lesson.keys.chars().collect()));
+    }
+    levels
+}
+
+/// Keys of a level, the last level when the language has fewer lessons than `level` needs
+pub fn level_keys(layout: &KeyboardLayout, language: Language, level: usize) -> Vec<char> {
+    let mut levels = levels(layout, language);
+    let level = level.min(levels.len() - 1);
+    levels.swap_remove(level).1
+}
+
+/// Moves the single keys named in a level name, leaving words such as "shifted" alone
+fn level_name(name: &str, level_chars: &[char], layout: &KeyboardLayout) -> String {
+    let Some(keys) = name.strip_prefix("new: ") else {
+        return name.to_string();
+    };
+    let chars: Vec<char> = keys.chars().collect();
+    let moved: String = chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let before = i.checked_sub(1).and_then(|j| chars.get(j));
+            let after = chars.get(i + 1);
+            let in_word = [before, after].iter().any(|n| n.is_some_and(|n| n.is_alphanumeric()));
+            if in_word || !level_chars.contains(c) { *c } else { layout.from_qwerty(*c) }
+        })
+        .collect();
+    format!("new: {}", moved)
+}
+
 pub fn create_words(chars: &[char], word_number: usize) -> String {
     let mut reference = String::new();
     for i in 0..word_number {
@@ -68,71 +120,314 @@ pub fn create_words(chars: &[char], word_number: usize) -> String {
     reference
 }
 
-pub fn save_results(time: f64, accuracy: f64, wpm: f64, level:usize) {
-    let results_dir = "practice_results";
-    fs::create_dir_all(results_dir).ok();
+/// Result of one practice test
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PracticeResult {
+    pub wpm: f64,
+    pub accuracy: f64,
+    pub time: f64,
+    /// Missing for results imported from `practice_results` folders
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+}
+
+impl PracticeResult {
+    pub fn passed(&self) -> bool {
+        passes(self.wpm, self.accuracy)
+    }
+}
 
-    let filename = format!("{}/level_{:?}.txt", results_dir, level);
-    let file_path = Path::new(&filename);
+/// A level is passed at `WPM_MIN` with at least `ACCURACY_MIN` accuracy
+pub fn passes(wpm: f64, accuracy: f64) -> bool {
+    wpm >= WPM_MIN && accuracy >= ACCURACY_MIN
+}
 
-    let stats = format!(
-        "Time: {:.2}s\nAccuracy: {:.1}%\nWPM: {:.1}\n---\n",
-        time, accuracy, wpm
-    );
-    
-    let mut file = fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(file_path)
-        .unwrap();
-    file.write_all(stats.as_bytes()).unwrap();
+/// Where the results of a level are kept. The standard levels are kept per keyboard
+/// layout since their keys move with it, the lessons of a language per language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelKey {
+    pub group: String,
+    /// Counted from 1 within the group
+    pub level: usize,
 }
 
-pub fn get_prev_best_wpm(level: usize) -> f64 {
-    let results_path = format!("practice_results/level_{}.txt", level);
-    let contents = match fs::read_to_string(&results_path) {
-        Ok(c) if !c.trim().is_empty() => c,
-        _ => return 0.0,
-    };
-    let mut best_wpm = 0.0;
-    for line in contents.lines() {
-        if line.starts_with("WPM:") {
-            if let Some(wpm_str) = line.strip_prefix("WPM:").map(str::trim) {
-                if let Ok(wpm) = wpm_str.parse::<f64>() {
-                    if wpm > best_wpm {
-                        best_wpm = wpm;
+impl LevelKey {
+    /// Key of the level at `index` of `levels(layout, language)`
+    pub fn new(layout: &KeyboardLayout, language: Language, index: usize) -> Self {
+        if index < TYPING_LEVELS.len() {
+            Self { group: format!("layout:{}", layout.name), level: index + 1 }
+        } else {
+            Self { group: format!("language:{}", language), level: index - TYPING_LEVELS.len() + 1 }
+        }
+    }
+}
+
+/// Results of every practice level, stored in `~/.config/typeman/practice.json`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PracticeProgress {
+    /// Results by `LevelKey` group, then by level
+    #[serde(default)]
+    pub results: BTreeMap<String, BTreeMap<usize, Vec<PracticeResult>>>,
+    /// `practice_results` folders of older versions already imported
+    #[serde(default)]
+    pub imported: Vec<PathBuf>,
+    /// The stored file could not be parsed nor moved aside, so it must not be overwritten
+    #[serde(skip)]
+    unreadable: bool,
+}
+
+impl PracticeProgress {
+    fn path() -> Result<PathBuf, LeaderboardError> {
+        let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
+            std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+        ))?;
+        Ok(config_dir.join("practice.json"))
+    }
+
+    /// Loads the progress, importing the `practice_results` folder of the current
+    /// directory the first time typeman runs there
+    pub fn load() -> Self {
+        let Ok(path) = Self::path() else {
+            return Self::default();
+        };
+        let mut progress = Self::load_from(&path);
+        // Older versions didn't keep the layout, results are taken as typed on the current one
+        let layout = KeyboardLayout::find(&AppConfig::load().keyboard_layout);
+        if progress.import_legacy(Path::new(LEGACY_RESULTS_DIR), &layout) {
+            let _ = progress.save();
+        }
+        progress
+    }
+
+    /// Progress stored at `path`. A file that can't be parsed is moved to a free
+    /// `.bak` name so saving never overwrites it.
+    fn load_from(path: &Path) -> Self {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        match serde_json::from_str(&content) {
+            Ok(progress) => progress,
+            Err(e) => {
+                let backup = (1..)
+                    .map(|i| path.with_extension(if i == 1 { "json.bak".to_string() } else { format!("json.bak{}", i) }))
+                    .find(|backup| !backup.exists())
+                    .unwrap();
+                match fs::rename(path, &backup) {
+                    Ok(()) => eprintln!(
+                        "Practice progress can't be read ({}), kept it as {} and started over",
+                        e,
+                        backup.display()
+                    ),
+                    Err(rename_error) => {
+                        eprintln!("Practice progress can't be read ({}) or moved aside ({}), it won't be saved", e, rename_error);
+                        return Self { unreadable: true, ..Self::default() };
                     }
                 }
+                Self::default()
             }
         }
     }
-    best_wpm
-}
 
-pub fn check_if_completed(results_path: &str) -> bool {
-    if let Ok(contents) = std::fs::read_to_string(results_path) {
-        for line in contents.lines() {
-            if line.starts_with("WPM:") {
-                if let Some(wpm_str) = line.strip_prefix("WPM:").map(str::trim) {
-                    if let Ok(wpm) = wpm_str.parse::<f32>() {
-                        if wpm >= 35.0 {
-                            return true;
-                        }
-                    }
+    pub fn save(&self) -> Result<(), LeaderboardError> {
+        self.save_to(&Self::path()?)
+    }
+
+    /// Writes to a temporary file first, then moves it over the old progress
+    fn save_to(&self, path: &Path) -> Result<(), LeaderboardError> {
+        if self.unreadable {
+            return Err(LeaderboardError::IoError(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Practice progress file can't be read, not overwriting it",
+            )));
+        }
+        write_atomic(path, &serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn record(&mut self, key: &LevelKey, result: PracticeResult) {
+        self.results.entry(key.group.clone()).or_default().entry(key.level).or_default().push(result);
+    }
+
+    fn level_results(&self, key: &LevelKey) -> &[PracticeResult] {
+        self.results
+            .get(&key.group)
+            .and_then(|levels| levels.get(&key.level))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    pub fn best_wpm(&self, key: &LevelKey) -> f64 {
+        self.level_results(key).iter().map(|result| result.wpm).fold(0.0, f64::max)
+    }
+
+    pub fn is_completed(&self, key: &LevelKey) -> bool {
+        self.level_results(key).iter().any(PracticeResult::passed)
+    }
+
+    /// Adds the `level_N.txt` files of a folder once, as standard levels of `layout`.
+    /// True when anything changed.
+    fn import_legacy(&mut self, dir: &Path, layout: &KeyboardLayout) -> bool {
+        let Ok(dir) = dir.canonicalize() else {
+            return false;
+        };
+        if !dir.is_dir() || self.imported.contains(&dir) {
+            return false;
+        }
+        let Ok(files) = fs::read_dir(&dir) else {
+            return false;
+        };
+        for file in files.flatten() {
+            let name = file.file_name().to_string_lossy().to_string();
+            let Some(level) = name
+                .strip_prefix("level_")
+                .and_then(|rest| rest.strip_suffix(".txt"))
+                .and_then(|number| number.parse::<usize>().ok())
+                .filter(|level| (1..=TYPING_LEVELS.len()).contains(level))
+            else {
+                continue;
+            };
+            let key = LevelKey::new(layout, Language::default(), level - 1);
+            if let Ok(content) = fs::read_to_string(file.path()) {
+                for result in parse_legacy_results(&content) {
+                    self.record(&key, result);
                 }
             }
         }
+        self.imported.push(dir);
+        true
     }
-    false
 }
 
-pub fn get_first_not_done() -> usize {
-    for i in 0..TYPING_LEVELS.len() {
-        let results_path = format!("practice_results/level_{}.txt", i + 1);
-        let done = check_if_completed(results_path.as_str());
-        if !done {
-            return i;
+/// Results of a `level_N.txt` file, blocks of "Time: 12.34s", "Accuracy: 98.0%"
+/// and "WPM: 40.0" lines ended by "---"
+fn parse_legacy_results(content: &str) -> Vec<PracticeResult> {
+    let value = |line: &str, key: &str, unit: &str| {
+        line.strip_prefix(key)
+            .map(|rest| rest.trim().trim_end_matches(unit))
+            .and_then(|number| number.parse::<f64>().ok())
+    };
+    let mut results = Vec::new();
+    let (mut time, mut accuracy) = (0.0, 0.0);
+    for line in content.lines() {
+        if let Some(t) = value(line, "Time:", "s") {
+            time = t;
+        } else if let Some(a) = value(line, "Accuracy:", "%") {
+            accuracy = a;
+        } else if let Some(wpm) = value(line, "WPM:", "") {
+            results.push(PracticeResult { wpm, accuracy, time, timestamp: None });
         }
     }
-    1
+    results
+}
+
+pub fn save_results(time: f64, accuracy: f64, wpm: f64, key: &LevelKey) {
+    let mut progress = PracticeProgress::load();
+    progress.record(key, PracticeResult {
+        wpm,
+        accuracy,
+        time,
+        timestamp: Some(chrono::Local::now().to_rfc3339()),
+    });
+    if let Err(e) = progress.save() {
+        eprintln!("Failed to save practice progress: {:?}", e);
+    }
+}
+
+pub fn get_prev_best_wpm(key: &LevelKey) -> f64 {
+    PracticeProgress::load().best_wpm(key)
+}
+
+/// Index of the first level of `levels(layout, language)` that isn't passed yet
+pub fn get_first_not_done(layout: &KeyboardLayout, language: Language) -> usize {
+    let progress = PracticeProgress::load();
+    (0..levels(layout, language).len())
+        .find(|i| !progress.is_completed(&LevelKey::new(layout, language, *i)))
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_follow_layout() {
+        let colemak = KeyboardLayout::builtin().into_iter().find(|l| l.name == "Colemak").unwrap();
+        let colemak_levels = levels(&colemak, Language::English);
+        assert_eq!(colemak_levels.len(), TYPING_LEVELS.len());
+        assert_eq!(colemak_levels[0], ("new: t & n".to_string(), vec!['t', 'n']));
+        assert_eq!(colemak_levels[5].0, "new: a & o");
+        assert_eq!(colemak_levels[18].0, "new: v, b, k");
+        assert_eq!(colemak_levels[24].0, "new: numbers row");
+        assert_eq!(colemak_levels[27].0, "new: shifted: !@#$");
+        assert_eq!(levels(&KeyboardLayout::default(), Language::English)[21].0, "new: x & ,");
+    }
+
+    #[test]
+    fn test_language_lessons_follow_standard_levels() {
+        let german = levels(&KeyboardLayout::default(), Language::German);
+        assert!(german.len() > TYPING_LEVELS.len());
+        assert_eq!(german[TYPING_LEVELS.len()], ("new: ä, ö & ü".to_string(), vec!['ä', 'ö', 'ü']));
+        let last = TYPING_LEVELS[TYPING_LEVELS.len() - 1].1.to_vec();
+        assert_eq!(level_keys(&KeyboardLayout::default(), Language::English, 1000), last);
+    }
+
+    #[test]
+    fn test_import_legacy_results() {
+        let dir = std::env::temp_dir().join(format!("typeman-practice-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("level_1.txt"), "Time: 20.50s\nAccuracy: 97.0%\nWPM: 41.2\n---\nTime: 25.00s\nAccuracy: 99.0%\nWPM: 30.0\n---\n").unwrap();
+        fs::write(dir.join("level_2.txt"), "Time: 20.00s\nAccuracy: 60.0%\nWPM: 50.0\n---\n").unwrap();
+        fs::write(dir.join("notes.txt"), "WPM: 100.0").unwrap();
+
+        let qwerty = KeyboardLayout::default();
+        let first = LevelKey::new(&qwerty, Language::English, 0);
+        let mut progress = PracticeProgress::default();
+        assert!(progress.import_legacy(&dir, &qwerty));
+        assert_eq!(progress.level_results(&first).len(), 2);
+        assert_eq!(progress.best_wpm(&first), 41.2);
+        assert_eq!(progress.level_results(&first)[0].time, 20.5);
+        assert!(progress.is_completed(&first));
+        // Fast enough, but not accurate enough
+        assert!(!progress.is_completed(&LevelKey::new(&qwerty, Language::English, 1)));
+        assert_eq!(progress.results[&first.group].len(), 2);
+
+        // The same folder is only imported once
+        assert!(!progress.import_legacy(&dir, &qwerty));
+        assert_eq!(progress.level_results(&first).len(), 2);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_unreadable_progress_is_kept() {
+        let dir = std::env::temp_dir().join(format!("typeman-progress-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("practice.json");
+        fs::write(&path, "{\"levels\": {\"1\": [{\"wpm\": 4").unwrap();
+
+        let mut progress = PracticeProgress::load_from(&path);
+        assert_eq!(progress, PracticeProgress::default());
+        assert_eq!(fs::read_to_string(path.with_extension("json.bak")).unwrap(), "{\"levels\": {\"1\": [{\"wpm\": 4");
+
+        progress.record(&LevelKey::new(&KeyboardLayout::default(), Language::English, 0), PracticeResult { wpm: 40.0, accuracy: 95.0, time: 20.0, timestamp: None });
+        progress.save_to(&path).unwrap();
+        assert_eq!(PracticeProgress::load_from(&path), progress);
+        assert!(!path.with_extension("json.tmp").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_progress_is_kept_per_layout_and_language() {
+        let qwerty = KeyboardLayout::default();
+        let colemak = KeyboardLayout::find("Colemak");
+        let passed = PracticeResult { wpm: 50.0, accuracy: 98.0, time: 20.0, timestamp: None };
+        let mut progress = PracticeProgress::default();
+        progress.record(&LevelKey::new(&qwerty, Language::German, 0), passed.clone());
+        assert!(progress.is_completed(&LevelKey::new(&qwerty, Language::English, 0)));
+        assert!(!progress.is_completed(&LevelKey::new(&colemak, Language::German, 0)));
+
+        // Language lessons are shared between layouts, but not between languages
+        let lesson = TYPING_LEVELS.len();
+        progress.record(&LevelKey::new(&qwerty, Language::German, lesson), passed);
+        assert!(progress.is_completed(&LevelKey::new(&colemak, Language::German, lesson)));
+        assert!(!progress.is_completed(&LevelKey::new(&qwerty, Language::French, lesson)));
+        assert_eq!(LevelKey::new(&colemak, Language::German, lesson).level, 1);
+    }
 }
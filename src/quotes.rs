@@ -0,0 +1,306 @@
+use rand::prelude::IndexedRandom;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::language::Language;
+use crate::leaderboard::{get_config_dir, write_atomic, LeaderboardError};
+use crate::Quote;
+
+/// Number of quotes kept out of the next picks
+pub const RECENT_QUOTES: usize = 50;
+
+/// Quotes quote mode picks from, by length in chars like other typing sites
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum QuoteLength {
+    #[default]
+    All,
+    /// Up to 100 chars
+    Short,
+    /// 101 to 300 chars
+    Medium,
+    /// 301 to 600 chars
+    Long,
+    /// Over 600 chars
+    Thicc,
+    /// Only favorite quotes, of any length
+    Favorites,
+}
+
+impl QuoteLength {
+    pub fn all() -> &'static [QuoteLength] {
+        &[
+            QuoteLength::All,
+            QuoteLength::Short,
+            QuoteLength::Medium,
+            QuoteLength::Long,
+            QuoteLength::Thicc,
+            QuoteLength::Favorites,
+        ]
+    }
+
+    pub fn count() -> usize {
+        Self::all().len()
+    }
+
+    pub fn next(&self) -> QuoteLength {
+        let all = Self::all();
+        let i = all.iter().position(|length| length == self).unwrap_or(0);
+        all[(i + 1) % all.len()]
+    }
+
+    #[cfg(feature = "cli")]
+    pub fn from_str(s: &str) -> Option<QuoteLength> {
+        Self::all().iter().copied().find(|length| length.to_string().eq_ignore_ascii_case(s))
+    }
+
+    /// A quote of `chars` chars is in this bucket
+    pub fn contains(&self, chars: usize) -> bool {
+        match self {
+            QuoteLength::All | QuoteLength::Favorites => true,
+            QuoteLength::Short => chars <= 100,
+            QuoteLength::Medium => (101..=300).contains(&chars),
+            QuoteLength::Long => (301..=600).contains(&chars),
+            QuoteLength::Thicc => chars > 600,
+        }
+    }
+}
+
+impl Display for QuoteLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuoteLength::All => write!(f, "All"),
+            QuoteLength::Short => write!(f, "Short"),
+            QuoteLength::Medium => write!(f, "Medium"),
+            QuoteLength::Long => write!(f, "Long"),
+            QuoteLength::Thicc => write!(f, "Thicc"),
+            QuoteLength::Favorites => write!(f, "Favorites"),
+        }
+    }
+}
+
+/// Why quote mode has nothing to type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoQuote {
+    /// The language has no quote of this length
+    Length(Language, QuoteLength),
+    /// No quote was added to the favorites yet
+    NoFavorites,
+}
+
+impl Display for NoQuote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoQuote::Length(language, length) => write!(
+                f,
+                "No {} quotes in {}, pick another quote length",
+                length.to_string().to_lowercase(),
+                language
+            ),
+            NoQuote::NoFavorites => write!(f, "No favorite quotes yet, add one after typing a quote"),
+        }
+    }
+}
+
+/// Quote as it is remembered in the history
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedQuote {
+    pub text: String,
+    pub author: String,
+    pub language: Language,
+}
+
+impl SavedQuote {
+    fn new(quote: &Quote, language: Language) -> Self {
+        Self {
+            text: quote.text.clone(),
+            author: quote.author.clone(),
+            language,
+        }
+    }
+
+    /// Reference typed in quote mode, e.g. "\"Carpe diem.\" - Horace"
+    pub fn reference(&self) -> String {
+        format!("\"{}\" - {}", self.text, self.author)
+    }
+}
+
+/// Recently typed and favorite quotes, stored in `~/.config/typeman/quotes.json`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct QuoteHistory {
+    /// Most recent last, the last one is the quote being typed
+    #[serde(default)]
+    pub recent: Vec<SavedQuote>,
+    #[serde(default)]
+    pub favorites: Vec<SavedQuote>,
+}
+
+impl QuoteHistory {
+    fn path() -> Result<PathBuf, LeaderboardError> {
+        let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
+            std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+        ))?;
+        Ok(config_dir.join("quotes.json"))
+    }
+
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| Ok(fs::read_to_string(path)?))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), LeaderboardError> {
+        write_atomic(&Self::path()?, &serde_json::to_string_pretty(self)?)
+    }
+
+    /// Random quote of the language and length that was not typed lately, from every
+    /// quote of the language and length when none is left. English quotes are used for
+    /// languages without quotes and favorites of any language when the language has none.
+    pub fn pick(&mut self, language: Language, length: QuoteLength) -> Result<SavedQuote, NoQuote> {
+        let pool = self.candidates(language, length)?;
+        let fresh: Vec<&SavedQuote> = pool.iter().filter(|quote| !self.recent.contains(quote)).collect();
+        let last = self.recent.last();
+        let not_last: Vec<&SavedQuote> = pool.iter().filter(|quote| Some(*quote) != last).collect();
+        let quote = if !fresh.is_empty() {
+            fresh.choose(&mut rand::rng()).copied()
+        } else if !not_last.is_empty() {
+            not_last.choose(&mut rand::rng()).copied()
+        } else {
+            pool.first()
+        }
+        .ok_or(NoQuote::Length(language, length))?
+        .clone();
+
+        self.recent.retain(|recent| *recent != quote);
+        self.recent.push(quote.clone());
+        let overflow = self.recent.len().saturating_sub(RECENT_QUOTES);
+        self.recent.drain(..overflow);
+        Ok(quote)
+    }
+
+    fn candidates(&self, language: Language, length: QuoteLength) -> Result<Vec<SavedQuote>, NoQuote> {
+        if length == QuoteLength::Favorites {
+            if self.favorites.is_empty() {
+                return Err(NoQuote::NoFavorites);
+            }
+            let favorites: Vec<SavedQuote> = self.favorites.iter().filter(|quote| quote.language == language).cloned().collect();
+            return Ok(if favorites.is_empty() { self.favorites.clone() } else { favorites });
+        }
+        let (quotes, quote_language) = match language.source().quotes() {
+            quotes if quotes.is_empty() => (Language::English.source().quotes(), Language::English),
+            quotes => (quotes, language),
+        };
+        let matching: Vec<SavedQuote> = quotes
+            .iter()
+            .map(|quote| SavedQuote::new(quote, quote_language))
+            .filter(|quote| length.contains(quote.reference().chars().count()))
+            .collect();
+        if matching.is_empty() {
+            return Err(NoQuote::Length(language, length));
+        }
+        Ok(matching)
+    }
+
+    /// Quote picked last, the one being typed or just finished
+    pub fn last(&self) -> Option<&SavedQuote> {
+        self.recent.last()
+    }
+
+    pub fn is_favorite(&self, quote: &SavedQuote) -> bool {
+        self.favorites.contains(quote)
+    }
+
+    /// Adds the last quote to the favorites or removes it, true when it is a favorite now
+    pub fn toggle_last_favorite(&mut self) -> bool {
+        let Some(quote) = self.recent.last().cloned() else {
+            return false;
+        };
+        if self.is_favorite(&quote) {
+            self.favorites.retain(|favorite| *favorite != quote);
+            false
+        } else {
+            self.favorites.push(quote);
+            true
+        }
+    }
+}
+
+/// Reference for quote mode, see `QuoteHistory::pick`. The pick is remembered so the
+/// next quotes differ and the quote can be made a favorite after the test.
+pub fn next_quote(language: Language, length: QuoteLength) -> Result<String, NoQuote> {
+    let mut history = QuoteHistory::load();
+    let quote = history.pick(language, length)?;
+    let _ = history.save();
+    Ok(quote.reference())
+}
+
+/// The last quote is a favorite
+pub fn last_is_favorite() -> bool {
+    let history = QuoteHistory::load();
+    history.last().is_some_and(|quote| history.is_favorite(quote))
+}
+
+/// Toggles the favorite state of the last quote, Some(true) when it became a favorite
+pub fn toggle_last_favorite() -> Option<bool> {
+    let mut history = QuoteHistory::load();
+    history.last()?;
+    let favorite = history.toggle_last_favorite();
+    history.save().ok()?;
+    Some(favorite)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_buckets() {
+        assert!(QuoteLength::Short.contains(100));
+        assert!(QuoteLength::Medium.contains(101));
+        assert!(QuoteLength::Long.contains(600));
+        assert!(QuoteLength::Thicc.contains(601));
+        assert!(!QuoteLength::Short.contains(101));
+        assert_eq!(QuoteLength::Favorites.next(), QuoteLength::All);
+    }
+
+    #[test]
+    fn test_pick_avoids_recent_and_matches_language() {
+        let mut history = QuoteHistory::default();
+        let mut seen = Vec::new();
+        let total = Language::German.source().quotes().len();
+        for _ in 0..total {
+            let quote = history.pick(Language::German, QuoteLength::All).unwrap();
+            assert_eq!(quote.language, Language::German);
+            assert!(!seen.contains(&quote), "{} came back early", quote.text);
+            seen.push(quote);
+        }
+        // Every quote was typed, the next one still isn't the last one
+        let next = history.pick(Language::German, QuoteLength::All).unwrap();
+        assert_ne!(Some(&next), seen.last());
+        assert!(history
+            .pick(Language::English, QuoteLength::Short)
+            .is_ok_and(|quote| quote.reference().chars().count() <= 100));
+    }
+
+    #[test]
+    fn test_no_quote_of_the_length() {
+        let mut history = QuoteHistory::default();
+        assert_eq!(history.pick(Language::Polish, QuoteLength::Long), Err(NoQuote::Length(Language::Polish, QuoteLength::Long)));
+        assert_eq!(history.pick(Language::Polish, QuoteLength::Favorites), Err(NoQuote::NoFavorites));
+        assert!(history.recent.is_empty());
+    }
+
+    #[test]
+    fn test_favorites() {
+        let mut history = QuoteHistory::default();
+        let quote = history.pick(Language::French, QuoteLength::All).unwrap();
+        assert!(history.toggle_last_favorite());
+        history.pick(Language::French, QuoteLength::Short).unwrap();
+        assert_eq!(history.pick(Language::French, QuoteLength::Favorites), Ok(quote.clone()));
+        assert!(!history.toggle_last_favorite());
+        assert!(!history.is_favorite(&quote));
+    }
+}
@@ -0,0 +1,204 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::keystroke_log::{self, KeyEvent, KeystrokeLog, LoggedKey};
+use crate::leaderboard::{LeaderboardError, TestType};
+use crate::typing_session::{SessionMode, TypingSession};
+
+pub const SPEEDS: [f64; 4] = [0.5, 1.0, 2.0, 4.0];
+
+/// Plays a recorded test back into a fresh `TypingSession`, following the recorded delays
+pub struct Replay {
+    pub log: KeystrokeLog,
+    pub session: TypingSession,
+    pub speed_index: usize,
+    pub paused: bool,
+    next_event: usize,
+    next_batch: usize,
+    clock_ms: f64,
+    next_event_at_ms: f64,
+    last_update: Option<Instant>,
+}
+
+impl Replay {
+    pub fn new(log: KeystrokeLog) -> Self {
+        let mut replay = Self {
+            session: TypingSession::new(String::new(), SessionMode::Text),
+            log,
+            speed_index: 1,
+            paused: false,
+            next_event: 0,
+            next_batch: 0,
+            clock_ms: 0.0,
+            next_event_at_ms: 0.0,
+            last_update: None,
+        };
+        replay.restart();
+        replay
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LeaderboardError> {
+        Ok(Self::new(keystroke_log::load_log(path)?))
+    }
+
+    /// Replay of the log saved together with a leaderboard entry, if there is one
+    pub fn for_timestamp(timestamp: &str) -> Option<Self> {
+        let path = keystroke_log::log_path(timestamp).ok()?;
+        if !path.exists() {
+            return None;
+        }
+        Self::load(&path).ok()
+    }
+
+    /// Rewinds to the first keystroke
+    pub fn restart(&mut self) {
+        // Batches are fed by the replay, so the session must never end on its own
+        let mode = match self.log.test_type {
+            TestType::Practice(_) => SessionMode::Practice,
+            _ => SessionMode::Words(usize::MAX),
+        };
+        let first_batch = self.log.batches.first().cloned().unwrap_or_default();
+        self.session = TypingSession::new(first_batch, mode);
+        self.session.auto_indent = self.log.auto_indent;
+        self.session.set_strictness(self.log.strictness);
+        self.session.half_accent_errors = self.log.half_accent_errors;
+        self.next_event = 0;
+        self.next_batch = 1;
+        self.clock_ms = 0.0;
+        self.next_event_at_ms = self.log.events.first().map_or(0.0, |e| e.delay_ms as f64);
+        self.last_update = None;
+    }
+
+    pub fn speed(&self) -> f64 {
+        SPEEDS[self.speed_index]
+    }
+
+    pub fn faster(&mut self) {
+        self.speed_index = usize::min(self.speed_index + 1, SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed_index = self.speed_index.saturating_sub(1);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.last_update = None;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_event >= self.log.events.len()
+    }
+
+    /// Position in the recorded test, independent of playback speed
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.clock_ms / 1000.0)
+    }
+
+    /// Moves the playback forward by the wall-clock time since the last call
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_update {
+            if !self.paused {
+                self.advance(now.duration_since(last).as_secs_f64() * 1000.0 * self.speed());
+            }
+        }
+        self.last_update = Some(now);
+    }
+
+    /// Moves the recorded clock forward by `ms` and applies every key that falls within it
+    pub fn advance(&mut self, ms: f64) {
+        if self.is_done() {
+            return;
+        }
+        self.clock_ms += ms;
+        while !self.is_done() && self.next_event_at_ms <= self.clock_ms {
+            let event = self.log.events[self.next_event];
+            self.apply(event);
+            self.next_event += 1;
+            if let Some(next) = self.log.events.get(self.next_event) {
+                self.next_event_at_ms += next.delay_ms as f64;
+            }
+        }
+        if self.is_done() {
+            self.clock_ms = self.clock_ms.min(self.next_event_at_ms);
+        }
+    }
+
+    fn apply(&mut self, event: KeyEvent) {
+        match event.key {
+            LoggedKey::Char(c) => {
+                self.session.type_char(c);
+            }
+            LoggedKey::Backspace => {
+                self.session.backspace();
+            }
+        }
+        if self.session.needs_batch() {
+            if let Some(batch) = self.log.batches.get(self.next_batch) {
+                self.session.next_batch(batch.clone());
+                self.next_batch += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::Language;
+
+    fn recorded(reference: &str, mode: SessionMode, typed: &str) -> KeystrokeLog {
+        let mut session = TypingSession::new(reference.to_string(), mode);
+        for c in typed.chars() {
+            if c == '<' {
+                session.backspace();
+            } else {
+                session.type_char(c);
+            }
+        }
+        KeystrokeLog::from_session(&session, TestType::Quote, "quote", Language::English)
+    }
+
+    #[test]
+    fn test_replay_rebuilds_session() {
+        let log = recorded("the cat", SessionMode::Text, "thx<e cat");
+        let mut replay = Replay::new(log);
+        assert_eq!(replay.session.pos, 0);
+        replay.advance(f64::MAX);
+        assert!(replay.is_done());
+        assert_eq!(replay.session.is_correct, vec![2, 2, 1, 2, 2, 2, 2]);
+        assert_eq!(replay.session.words_done, 2);
+    }
+
+    #[test]
+    fn test_replay_follows_delays() {
+        let mut log = recorded("abc", SessionMode::Text, "abc");
+        for (event, delay) in log.events.iter_mut().zip([0, 100, 100]) {
+            event.delay_ms = delay;
+        }
+        let mut replay = Replay::new(log);
+        replay.advance(0.0);
+        assert_eq!(replay.session.pos, 1);
+        replay.advance(150.0);
+        assert_eq!(replay.session.pos, 2);
+        replay.advance(50.0);
+        assert_eq!(replay.session.pos, 3);
+        assert!(replay.is_done());
+    }
+
+    #[test]
+    fn test_replay_feeds_batches() {
+        let mut session = TypingSession::new("ab".to_string(), SessionMode::Words(2));
+        session.type_char('a');
+        session.type_char('b');
+        session.next_batch("cd".to_string());
+        session.type_char('c');
+        session.type_char('d');
+        let log = KeystrokeLog::from_session(&session, TestType::Word(2), "word", Language::English);
+        let mut replay = Replay::new(log);
+        replay.advance(f64::MAX);
+        assert_eq!(replay.session.reference, "cd");
+        assert_eq!(replay.session.words_done, 2);
+    }
+}
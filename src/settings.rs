@@ -7,6 +7,18 @@ pub enum Settings {
     Language,
     BatchSize,
     TopWords,
+    PaceGhost,
+    Pacer,
+    Layout,
+    Emulation,
+    Sampling,
+    NoRepeat,
+    CodeLanguage,
+    SkipIndent,
+    ChunkSize,
+    Strictness,
+    AccentErrors,
+    QuoteLength,
 }
 
 impl Settings {
@@ -16,6 +28,18 @@ impl Settings {
             Settings::Language,
             Settings::BatchSize,
             Settings::TopWords,
+            Settings::PaceGhost,
+            Settings::Pacer,
+            Settings::Layout,
+            Settings::Emulation,
+            Settings::Sampling,
+            Settings::NoRepeat,
+            Settings::CodeLanguage,
+            Settings::SkipIndent,
+            Settings::ChunkSize,
+            Settings::Strictness,
+            Settings::AccentErrors,
+            Settings::QuoteLength,
         ]
     }
 
@@ -31,6 +55,18 @@ impl Display for Settings {
             Settings::Language => write!(f, "Language"),
             Settings::BatchSize => write!(f, "Batch Size"),
             Settings::TopWords => write!(f, "Top Words"),
+            Settings::PaceGhost => write!(f, "Pace Ghost"),
+            Settings::Pacer => write!(f, "Pacer"),
+            Settings::Layout => write!(f, "Keyboard Layout"),
+            Settings::Emulation => write!(f, "Layout Emulation"),
+            Settings::Sampling => write!(f, "Word Sampling"),
+            Settings::NoRepeat => write!(f, "No Repeats"),
+            Settings::CodeLanguage => write!(f, "Code Language"),
+            Settings::SkipIndent => write!(f, "Skip Indentation"),
+            Settings::ChunkSize => write!(f, "Text Chunk Size"),
+            Settings::Strictness => write!(f, "Character Matching"),
+            Settings::AccentErrors => write!(f, "Half Accent Errors"),
+            Settings::QuoteLength => write!(f, "Quote Length"),
         }
     }
 }
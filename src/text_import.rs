@@ -0,0 +1,455 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::leaderboard::LeaderboardError;
+use crate::normalization;
+
+/// Formats texts can be imported from, picked by file extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextFormat {
+    Plain,
+    Markdown,
+    Html,
+    Epub,
+}
+
+impl TextFormat {
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "md" | "markdown" => TextFormat::Markdown,
+            "html" | "htm" | "xhtml" => TextFormat::Html,
+            "epub" => TextFormat::Epub,
+            _ => TextFormat::Plain,
+        }
+    }
+}
+
+/// Reads a file as clean typable text, whatever its format
+pub fn read_text(path: &Path) -> Result<String, LeaderboardError> {
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")) {
+        return Err(invalid_data("PDF files can't be read directly, extract the text first (e.g. pdftotext book.pdf)"));
+    }
+    let text = match TextFormat::from_path(path) {
+        TextFormat::Epub => epub_to_text(path)?,
+        TextFormat::Markdown => markdown_to_text(&fs::read_to_string(path)?),
+        TextFormat::Html => html_to_text(&fs::read_to_string(path)?),
+        TextFormat::Plain => {
+            let content = fs::read_to_string(path)?;
+            // Page breaks are left in by pdftotext, such texts are hard wrapped
+            if content.contains('\u{c}') { unwrap_pdf_text(&content) } else { content }
+        }
+    };
+    Ok(clean_typography(&text))
+}
+
+/// Replaces typographic characters that are not on a keyboard with their plain equivalents
+pub fn clean_typography(text: &str) -> String {
+    let mut cleaned = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' => cleaned.push_str("    "),
+            '\r' => {}
+            _ => match normalization::typable_punctuation(c) {
+                Some(plain) => cleaned.push_str(plain),
+                None => cleaned.push(c),
+            },
+        }
+    }
+    tidy_lines(&cleaned)
+}
+
+/// Trims line ends, drops leading blank lines and keeps at most one blank line between paragraphs
+fn tidy_lines(text: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+/// Joins the hard wrapped lines of text extracted from a PDF back into paragraphs,
+/// mending words hyphenated at line ends
+pub fn unwrap_pdf_text(text: &str) -> String {
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for line in text.replace('\u{c}', "\n\n").lines().map(str::trim) {
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if current.ends_with('-') && !current.ends_with(" -") && line.starts_with(char::is_lowercase) {
+            current.pop();
+        } else if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+    paragraphs.join("\n\n")
+}
+
+/// Plain text of a Markdown document: code blocks are kept as they are,
+/// paragraphs are joined into one line and the syntax around text is dropped
+pub fn markdown_to_text(markdown: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut paragraph = String::new();
+    let mut in_code = false;
+
+    fn flush(out: &mut Vec<String>, paragraph: &mut String) {
+        if !paragraph.is_empty() {
+            out.push(strip_inline_markdown(paragraph));
+            paragraph.clear();
+        }
+    }
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            flush(&mut out, &mut paragraph);
+            if in_code {
+                out.push(String::new());
+            }
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            out.push(line.to_string());
+            continue;
+        }
+        if trimmed.is_empty() {
+            flush(&mut out, &mut paragraph);
+            out.push(String::new());
+            continue;
+        }
+        if is_horizontal_rule(trimmed) || is_table_separator(trimmed) {
+            flush(&mut out, &mut paragraph);
+            continue;
+        }
+
+        let content = trimmed.trim_start_matches('>').trim_start();
+        if content.starts_with('#') {
+            flush(&mut out, &mut paragraph);
+            out.push(strip_inline_markdown(content.trim_start_matches('#').trim()));
+            out.push(String::new());
+        } else if content.starts_with('|') {
+            flush(&mut out, &mut paragraph);
+            let cells: Vec<&str> = content.trim_matches('|').split('|').map(str::trim).collect();
+            out.push(strip_inline_markdown(&cells.join(" ")));
+        } else if let Some(item) = list_item(content) {
+            flush(&mut out, &mut paragraph);
+            paragraph.push_str(item);
+        } else {
+            if !paragraph.is_empty() {
+                paragraph.push(' ');
+            }
+            paragraph.push_str(content);
+        }
+    }
+    flush(&mut out, &mut paragraph);
+    tidy_lines(&out.join("\n"))
+}
+
+fn is_horizontal_rule(line: &str) -> bool {
+    let chars: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3
+        && ['-', '*', '_'].iter().any(|&rule| chars.chars().all(|c| c == rule))
+}
+
+fn is_table_separator(line: &str) -> bool {
+    line.starts_with('|') && line.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+/// Text of a list item without its bullet, numbered items keep their number
+fn list_item(line: &str) -> Option<&str> {
+    ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|bullet| line.strip_prefix(bullet))
+        .or_else(|| {
+            let digits = line.chars().take_while(char::is_ascii_digit).count();
+            (digits > 0 && line[digits..].starts_with(". ")).then_some(line)
+        })
+}
+
+/// Drops emphasis, inline code marks, link targets and image sources
+fn strip_inline_markdown(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '!' if chars.get(i + 1) == Some(&'[') => {
+                i += 1;
+                continue;
+            }
+            '[' => {
+                // [text](target) keeps only the text
+                if let Some(close) = chars[i..].iter().position(|&c| c == ']').map(|p| p + i) {
+                    if chars.get(close + 1) == Some(&'(') {
+                        if let Some(end) = chars[close..].iter().position(|&c| c == ')').map(|p| p + close) {
+                            out.extend(&chars[i + 1..close]);
+                            i = end + 1;
+                            continue;
+                        }
+                    }
+                }
+                out.push(c);
+            }
+            '`' => {}
+            '*' | '_' | '~' => {
+                // Emphasis marks sit at word edges, snake_case and 2*3 stay as they are
+                let before = i.checked_sub(1).map(|j| chars[j]);
+                let run = chars[i..].iter().take_while(|&&m| m == c).count();
+                let after = chars.get(i + run);
+                let inside_word = before.is_some_and(char::is_alphanumeric) && after.is_some_and(|a| a.is_alphanumeric());
+                if inside_word || (c == '~' && run == 1) {
+                    out.extend(&chars[i..i + run]);
+                }
+                i += run;
+                continue;
+            }
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Plain text of an HTML document: tags, scripts and styles are dropped,
+/// block elements start new lines and entities are decoded
+pub fn html_to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut pre: usize = 0;
+
+    while let Some(start) = rest.find('<') {
+        push_html_text(&mut out, &rest[..start], pre > 0);
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        match name.as_str() {
+            "script" | "style" | "head" | "title" if !closing => {
+                let close = format!("</{}", name);
+                rest = rest.to_ascii_lowercase().find(&close).map_or(rest, |at| &rest[at..]);
+            }
+            "pre" => {
+                pre = if closing { pre.saturating_sub(1) } else { pre + 1 };
+                out.push('\n');
+            }
+            "br" => out.push('\n'),
+            "p" | "div" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote" | "section" | "article"
+            | "ul" | "ol" | "table" | "hr" => out.push_str("\n\n"),
+            "li" | "tr" | "dt" | "dd" if !closing => out.push('\n'),
+            "td" | "th" if !closing && !out.ends_with(char::is_whitespace) => out.push(' '),
+            _ => {}
+        }
+    }
+    push_html_text(&mut out, rest, pre > 0);
+    tidy_lines(&out)
+}
+
+/// Appends text between tags, collapsing whitespace outside of `<pre>`
+fn push_html_text(out: &mut String, text: &str, preformatted: bool) {
+    let text = decode_entities(text);
+    if preformatted {
+        out.push_str(&text);
+        return;
+    }
+    for c in text.chars() {
+        if c.is_whitespace() && c != '\u{a0}' {
+            if !out.ends_with(|last: char| last.is_whitespace()) && !out.is_empty() {
+                out.push(' ');
+            }
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+/// Decodes named and numeric character references
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..].find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end + 1];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                "ndash" => Some('–'),
+                "mdash" => Some('—'),
+                "hellip" => Some('…'),
+                "lsquo" => Some('‘'),
+                "rsquo" => Some('’'),
+                "ldquo" => Some('“'),
+                "rdquo" => Some('”'),
+                "laquo" => Some('«'),
+                "raquo" => Some('»'),
+                "shy" => Some('\u{ad}'),
+                "copy" => Some('©'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end + 2))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Chapters of an EPUB book in reading order, as plain text
+pub fn epub_to_text(path: &Path) -> Result<String, LeaderboardError> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?).map_err(|e| invalid_data(&e.to_string()))?;
+
+    let container = read_zip_entry(&mut archive, "META-INF/container.xml")?;
+    let opf_path = find_tags(&container, "rootfile")
+        .iter()
+        .find_map(|tag| attribute(tag, "full-path"))
+        .ok_or_else(|| invalid_data("no package file in the EPUB container"))?;
+    let opf = read_zip_entry(&mut archive, &opf_path)?;
+    let base = opf_path.rfind('/').map_or("", |at| &opf_path[..at + 1]);
+
+    let manifest: Vec<(String, String)> = find_tags(&opf, "item")
+        .iter()
+        .filter_map(|tag| Some((attribute(tag, "id")?, attribute(tag, "href")?)))
+        .collect();
+
+    let mut chapters = Vec::new();
+    for idref in find_tags(&opf, "itemref").iter().filter_map(|tag| attribute(tag, "idref")) {
+        let Some((_, href)) = manifest.iter().find(|(id, _)| *id == idref) else {
+            continue;
+        };
+        let href = decode_entities(href.split('#').next().unwrap_or(href));
+        let chapter = html_to_text(&read_zip_entry(&mut archive, &format!("{}{}", base, href))?);
+        if !chapter.is_empty() {
+            chapters.push(chapter);
+        }
+    }
+    Ok(chapters.join("\n\n"))
+}
+
+fn read_zip_entry(archive: &mut zip::ZipArchive<fs::File>, name: &str) -> Result<String, LeaderboardError> {
+    let mut entry = archive.by_name(name).map_err(|e| invalid_data(&format!("{}: {}", name, e)))?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Opening tags with the given name, without the angle brackets
+fn find_tags<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}", name);
+    xml.match_indices(&open)
+        .filter_map(|(start, _)| {
+            let tag = &xml[start + 1..];
+            let after = tag[name.len()..].chars().next()?;
+            (after.is_whitespace() || after == '/' || after == '>').then(|| &tag[..tag.find('>').unwrap_or(tag.len())])
+        })
+        .collect()
+}
+
+/// Value of an attribute in a tag, in single or double quotes
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!("{}=", name);
+    let mut search = tag;
+    while let Some(at) = search.find(&pattern) {
+        let preceded_by_space = at > 0 && search[..at].ends_with(char::is_whitespace);
+        let value = &search[at + pattern.len()..];
+        if preceded_by_space {
+            let quote = value.chars().next().filter(|q| *q == '"' || *q == '\'')?;
+            let end = value[1..].find(quote)?;
+            return Some(value[1..end + 1].to_string());
+        }
+        search = value;
+    }
+    None
+}
+
+fn invalid_data(message: &str) -> LeaderboardError {
+    LeaderboardError::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_typography() {
+        let text = "\u{feff}“Wait…” she said\u{a0}— it’s 9–5.  \n\n\n\nNext";
+        assert_eq!(clean_typography(text), "\"Wait...\" she said -- it's 9-5.\n\nNext");
+    }
+
+    #[test]
+    fn test_markdown_to_text() {
+        let markdown = "# Title\n\nSome **bold** and _it_ text\nwith a [link](http://x.y) and `code`.\n\n- one\n- two\n\n```\nfn main() {}\n```\n";
+        assert_eq!(
+            markdown_to_text(markdown),
+            "Title\n\nSome bold and it text with a link and code.\n\none\ntwo\n\nfn main() {}"
+        );
+        assert_eq!(strip_inline_markdown("snake_case_name"), "snake_case_name");
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        let html = "<html><head><title>T</title><style>p {}</style></head><body>\n<h1>Chapter&nbsp;1</h1>\n<p>It was   a <em>dark</em>\nnight &amp; &#8220;cold&#x201D;.</p><p>Next<br/>line</p></body></html>";
+        assert_eq!(
+            clean_typography(&html_to_text(html)),
+            "Chapter 1\n\nIt was a dark night & \"cold\".\n\nNext\nline"
+        );
+    }
+
+    #[test]
+    fn test_unwrap_pdf_text() {
+        let text = "The quick brown fox jum-\nped over\nthe dog.\n\u{c}Second page\nstarts here.";
+        assert_eq!(unwrap_pdf_text(text), "The quick brown fox jumped over the dog.\n\nSecond page starts here.");
+    }
+}
@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::leaderboard::{get_config_dir, write_atomic, LeaderboardError};
+use crate::text_import;
+
+/// Chunk sizes offered by the settings, in words
+pub const CHUNK_SIZE_PRESETS: [usize; 5] = [25, 50, 100, 200, 500];
+pub const DEFAULT_CHUNK_SIZE: usize = 50;
+
+/// Next size in `CHUNK_SIZE_PRESETS`, wrapping back to the smallest
+pub fn next_chunk_size(size: usize) -> usize {
+    CHUNK_SIZE_PRESETS
+        .iter()
+        .copied()
+        .find(|preset| *preset > size)
+        .unwrap_or(CHUNK_SIZE_PRESETS[0])
+}
+
+/// Results of the finished chunks of a text
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TextStats {
+    pub tests: usize,
+    pub words: usize,
+    pub best_wpm: f64,
+    pub avg_wpm: f64,
+    pub avg_accuracy: f64,
+}
+
+impl TextStats {
+    pub fn add(&mut self, wpm: f64, accuracy: f64, words: usize) {
+        let n = self.tests as f64;
+        self.avg_wpm = (self.avg_wpm * n + wpm) / (n + 1.0);
+        self.avg_accuracy = (self.avg_accuracy * n + accuracy) / (n + 1.0);
+        self.best_wpm = self.best_wpm.max(wpm);
+        self.words += words;
+        self.tests += 1;
+    }
+}
+
+/// Part of a text typed in one test
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// Byte offset right after the chunk, where the next one starts
+    pub end: usize,
+}
+
+/// Up to `words` words of `content` from the byte offset `position`, line breaks included.
+/// Starts over at the beginning when nothing is left to type.
+pub fn chunk_at(content: &str, position: usize, words: usize) -> Chunk {
+    let rest = content.get(position..).unwrap_or("");
+    let (start, rest) = if rest.trim().is_empty() { (0, content) } else { (position, rest) };
+    let skipped = rest.len() - rest.trim_start().len();
+
+    let mut count = 0;
+    let mut end = rest.len();
+    let mut in_word = false;
+    for (i, c) in rest.char_indices().skip_while(|(_, c)| c.is_whitespace()) {
+        if c.is_whitespace() {
+            if in_word && count == words.max(1) {
+                end = i;
+                break;
+            }
+            in_word = false;
+        } else if !in_word {
+            in_word = true;
+            count += 1;
+        }
+    }
+    Chunk {
+        text: rest[skipped..end].trim_end().to_string(),
+        end: start + end,
+    }
+}
+
+/// Text stored in the library directory, with the position reached in it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LibraryText {
+    pub name: String,
+    /// File name in the texts directory
+    pub file: String,
+    /// Path the text was imported from
+    #[serde(default)]
+    pub source: String,
+    /// Byte offset of the next chunk
+    pub position: usize,
+    pub length: usize,
+    #[serde(default)]
+    pub stats: TextStats,
+}
+
+impl LibraryText {
+    pub fn content(&self) -> Result<String, LeaderboardError> {
+        Ok(fs::read_to_string(get_texts_dir()?.join(&self.file))?)
+    }
+
+    /// Share of the text typed so far, in percent
+    pub fn progress(&self) -> f64 {
+        if self.length == 0 {
+            return 0.0;
+        }
+        self.position as f64 / self.length as f64 * 100.0
+    }
+
+    /// One line for lists, e.g. "Moby Dick  12%  4 tests  best 61 wpm"
+    pub fn summary(&self) -> String {
+        if self.stats.tests == 0 {
+            return format!("{}  {:.0}%", self.name, self.progress());
+        }
+        format!(
+            "{}  {:.0}%  {} tests  best {:.0} wpm",
+            self.name,
+            self.progress(),
+            self.stats.tests,
+            self.stats.best_wpm,
+        )
+    }
+
+    /// Saves the result of a finished chunk and moves on to the next one
+    pub fn record(&mut self, chunk_end: usize, wpm: f64, accuracy: f64, words: usize) {
+        self.stats.add(wpm, accuracy, words);
+        self.position = if chunk_end >= self.length { 0 } else { chunk_end };
+    }
+}
+
+/// Texts imported into `~/.config/typeman/texts/`, indexed by `library.json`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TextLibrary {
+    pub texts: Vec<LibraryText>,
+}
+
+impl TextLibrary {
+    fn index_path() -> Result<PathBuf, LeaderboardError> {
+        Ok(get_texts_dir()?.join("library.json"))
+    }
+
+    pub fn load() -> Self {
+        Self::index_path()
+            .and_then(|path| Ok(fs::read_to_string(path)?))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), LeaderboardError> {
+        write_atomic(&Self::index_path()?, &serde_json::to_string_pretty(self)?)
+    }
+
+    /// Text with the given name, ignoring case
+    pub fn get(&self, name: &str) -> Option<&LibraryText> {
+        self.texts.iter().find(|text| text.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut LibraryText> {
+        self.texts.iter_mut().find(|text| text.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.texts.iter().map(|text| text.name.as_str()).collect()
+    }
+
+    /// One line per text for the mode bar popups, or how to add one when the library is empty
+    pub fn summaries(&self) -> Vec<String> {
+        if self.texts.is_empty() {
+            return vec!["No texts yet, add one with typeman --cli --import FILE".to_string()];
+        }
+        self.texts.iter().map(LibraryText::summary).collect()
+    }
+
+    /// Stores `content` under `name`, or under "name (2)" and so on when it is taken.
+    /// Returns the name the text got.
+    pub fn add(&mut self, name: &str, content: &str, source: &str) -> Result<String, LeaderboardError> {
+        let content = content.replace("\r\n", "\n").trim().to_string();
+        if content.is_empty() {
+            return Err(LeaderboardError::IoError(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "the text is empty",
+            )));
+        }
+        let name = (1..)
+            .map(|i| if i == 1 { name.to_string() } else { format!("{} ({})", name, i) })
+            .find(|candidate| self.get(candidate).is_none())
+            .unwrap_or_else(|| name.to_string());
+        let file = format!("{}.txt", chrono::Local::now().format("%Y%m%d%H%M%S%3f"));
+        fs::write(get_texts_dir()?.join(&file), &content)?;
+
+        self.texts.push(LibraryText {
+            name: name.clone(),
+            file,
+            source: source.to_string(),
+            position: 0,
+            length: content.len(),
+            stats: TextStats::default(),
+        });
+        self.save()?;
+        Ok(name)
+    }
+
+    /// Imports a file, named after the file. EPUB, Markdown and HTML are turned into plain text.
+    pub fn import(&mut self, path: &Path) -> Result<String, LeaderboardError> {
+        let content = text_import::read_text(path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "text".to_string());
+        self.add(&name, &content, &source_of(path))
+    }
+
+    /// Name of the text imported from `path`, importing it the first time
+    pub fn find_or_import(&mut self, path: &Path) -> Result<String, LeaderboardError> {
+        let source = source_of(path);
+        match self.texts.iter().find(|text| text.source == source) {
+            Some(text) => Ok(text.name.clone()),
+            None => self.import(path),
+        }
+    }
+
+    /// Deletes a text and its file, false when there is no text with that name
+    pub fn remove(&mut self, name: &str) -> Result<bool, LeaderboardError> {
+        let Some(index) = self.texts.iter().position(|text| text.name.eq_ignore_ascii_case(name)) else {
+            return Ok(false);
+        };
+        let text = self.texts.remove(index);
+        let _ = fs::remove_file(get_texts_dir()?.join(&text.file));
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Next chunk of `words` words of the named text
+    pub fn next_chunk(&self, name: &str, words: usize) -> Option<Chunk> {
+        let text = self.get(name)?;
+        let content = text.content().ok()?;
+        Some(chunk_at(&content, text.position, words))
+    }
+
+    /// Saves the result of a finished chunk of the named text
+    pub fn record(&mut self, name: &str, chunk_end: usize, wpm: f64, accuracy: f64, words: usize) -> Result<(), LeaderboardError> {
+        if let Some(text) = self.get_mut(name) {
+            text.record(chunk_end, wpm, accuracy, words);
+            self.save()?;
+        }
+        Ok(())
+    }
+}
+
+fn source_of(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+pub fn get_texts_dir() -> Result<PathBuf, LeaderboardError> {
+    let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
+        std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+    ))?;
+    let texts_dir = config_dir.join("texts");
+    fs::create_dir_all(&texts_dir)?;
+    Ok(texts_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_keep_line_breaks_and_wrap() {
+        let content = "One two three.\n\nFour five six seven.";
+        let first = chunk_at(content, 0, 4);
+        assert_eq!(first.text, "One two three.\n\nFour");
+        let second = chunk_at(content, first.end, 4);
+        assert_eq!(second.text, "five six seven.");
+        assert_eq!(second.end, content.len());
+        // Nothing left, back to the start
+        assert_eq!(chunk_at(content, second.end, 2).text, "One two");
+    }
+
+    #[test]
+    fn test_record_moves_position_and_stats() {
+        let mut text = LibraryText {
+            name: "book".to_string(),
+            file: "book.txt".to_string(),
+            source: String::new(),
+            position: 0,
+            length: 100,
+            stats: TextStats::default(),
+        };
+        text.record(40, 50.0, 90.0, 8);
+        text.record(100, 70.0, 100.0, 10);
+        assert_eq!(text.position, 0);
+        assert_eq!(text.stats.tests, 2);
+        assert_eq!(text.stats.words, 18);
+        assert_eq!(text.stats.best_wpm, 70.0);
+        assert_eq!(text.stats.avg_wpm, 60.0);
+        assert_eq!(text.stats.avg_accuracy, 95.0);
+    }
+}
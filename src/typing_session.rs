@@ -0,0 +1,557 @@
+use std::time::{Duration, Instant};
+
+use crate::keystroke_log::{KeyEvent, LoggedKey};
+use crate::normalization::{self, Strictness};
+use crate::utils;
+
+/// Average chars per word, a space included, used to turn a chars per minute speed into WPM and back
+pub const CHARS_PER_WORD: f64 = 6.0;
+
+/// Decides when a test is over and whether the reference is refilled in batches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionMode {
+    /// Ends after the given number of seconds, new batches are loaded as needed
+    Time(f32),
+    /// Ends once the given number of words is typed, new batches are loaded as needed
+    Words(usize),
+    /// Ends at the end of a single reference (quote, wiki, custom text)
+    Text,
+    /// Like `Text`, but a mistake blocks the cursor until the right key is pressed
+    Practice,
+}
+
+/// Result of a single keystroke
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keystroke {
+    /// Right key on the first try
+    Correct,
+    /// Right key after an earlier mistake on this position
+    Corrected,
+    Incorrect,
+    /// Key was not scored (test over, nothing left to type, leading space)
+    Ignored,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionStats {
+    pub wpm: f64,
+    pub raw_wpm: f64,
+    pub accuracy: f64,
+    pub correct_words: usize,
+    pub all_words: usize,
+    pub elapsed: f64,
+    /// 100% when every second was typed at the same speed, lower the more it varied
+    pub consistency: f64,
+    pub errors: usize,
+}
+
+/// Typing test state driven by every frontend, so the same keystrokes give
+/// the same results in CLI, TUI and GUI.
+///
+/// `is_correct` holds one value per reference char:
+/// `0` untyped, `2` correct, `1` corrected, `-1` incorrect.
+#[derive(Debug, Clone)]
+pub struct TypingSession {
+    pub mode: SessionMode,
+    pub reference: String,
+    pub ref_chars: Vec<char>,
+    pub is_correct: Vec<i32>,
+    pub error_positions: Vec<bool>,
+    pub pos: usize,
+    pub pressed: Vec<char>,
+    pub words_done: usize,
+    pub keystrokes: usize,
+    pub correct_keystrokes: usize,
+    pub error_count: usize,
+    pub start_time: Option<Instant>,
+    pub end_time: Option<Instant>,
+    pub speed_per_second: Vec<f64>,
+    pub errors_per_second: Vec<f64>,
+    /// Every scored key and backspace, in order
+    pub log: Vec<KeyEvent>,
+    /// Reference texts typed so far, the current one last
+    pub batches: Vec<String>,
+    /// Indentation after a newline is filled in like an editor would
+    pub auto_indent: bool,
+    /// How chars that are not on a keyboard are matched, see `set_strictness`
+    pub strictness: Strictness,
+    /// Mistakes only in the accent of a letter count as half an error in accuracy
+    pub half_accent_errors: bool,
+    /// Mistakes only in the accent of a letter, e.g. 'e' for 'é'
+    pub accent_errors: usize,
+    /// Keys of a multi-char fold typed so far at `pos`, see `normalization::fold_keys`
+    fold_typed: usize,
+    last_key_time: Option<Instant>,
+    archived_correct_words: usize,
+    archived_all_words: usize,
+    sampled_keystrokes: usize,
+    errors_this_second: f64,
+}
+
+impl TypingSession {
+    pub fn new(reference: String, mode: SessionMode) -> Self {
+        let reference = normalization::normalize(&reference, Strictness::Exact);
+        let ref_chars: Vec<char> = reference.chars().collect();
+        Self {
+            batches: vec![reference.clone()],
+            mode,
+            is_correct: vec![0; ref_chars.len()],
+            error_positions: vec![false; ref_chars.len()],
+            reference,
+            ref_chars,
+            pos: 0,
+            pressed: Vec::new(),
+            words_done: 0,
+            keystrokes: 0,
+            correct_keystrokes: 0,
+            error_count: 0,
+            start_time: None,
+            end_time: None,
+            speed_per_second: Vec::new(),
+            errors_per_second: Vec::new(),
+            log: Vec::new(),
+            auto_indent: false,
+            strictness: Strictness::Exact,
+            half_accent_errors: false,
+            accent_errors: 0,
+            fold_typed: 0,
+            last_key_time: None,
+            archived_correct_words: 0,
+            archived_all_words: 0,
+            sampled_keystrokes: 0,
+            errors_this_second: 0.0,
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.start_time.is_some()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.end_time.is_some()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) => end.duration_since(start),
+            (Some(start), None) => start.elapsed(),
+            _ => Duration::from_secs(0),
+        }
+    }
+
+    /// The reference has line breaks, which are typed with Enter
+    pub fn is_multiline(&self) -> bool {
+        self.ref_chars.contains(&'\n')
+    }
+
+    /// Sets how chars that are not on a keyboard are matched, and folds the reference
+    /// for it while nothing is typed yet
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.strictness = strictness;
+        if self.is_started() {
+            return;
+        }
+        let reference = normalization::normalize(&self.reference, strictness);
+        if reference != self.reference {
+            self.batches.pop();
+            self.set_reference(reference);
+        }
+    }
+
+    fn is_strict(&self) -> bool {
+        self.mode == SessionMode::Practice
+    }
+
+    fn record(&mut self, key: LoggedKey, expected: Option<char>, state: i32, at: Instant) {
+        let delay_ms = self
+            .last_key_time
+            .map_or(0, |last| at.saturating_duration_since(last).as_millis() as u64);
+        self.last_key_time = Some(at);
+        self.log.push(KeyEvent { key, expected, state, delay_ms });
+    }
+
+    fn completes_word_at(&self, pos: usize) -> bool {
+        let is_space = |i: usize| self.ref_chars.get(i).is_some_and(|c| c.is_whitespace());
+        pos > 0 && !is_space(pos - 1) && (pos == self.ref_chars.len() || is_space(pos))
+    }
+
+    /// Spaces or tabs at `pos` with only indentation between them and the start of the line
+    fn is_indent(&self, pos: usize) -> bool {
+        let line_start = self.ref_chars[..pos]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1);
+        matches!(self.ref_chars.get(pos), Some(' ' | '\t'))
+            && self.ref_chars[line_start..pos].iter().all(|c| *c == ' ' || *c == '\t')
+    }
+
+    /// Moves the cursor over the indentation of the current line without scoring it
+    fn skip_indent(&mut self) {
+        while self.auto_indent && self.is_indent(self.pos) {
+            self.is_correct[self.pos] = 2;
+            self.pressed.push(self.ref_chars[self.pos]);
+            self.pos += 1;
+        }
+    }
+
+    /// Scores one typed char against the reference and moves the cursor.
+    pub fn type_char(&mut self, c: char) -> Keystroke {
+        self.type_char_at(c, Instant::now())
+    }
+
+    /// `type_char` for a key pressed at `at`, for keys typed after a delay like a held letter
+    pub fn type_char_at(&mut self, c: char, at: Instant) -> Keystroke {
+        if self.is_finished() || self.pos >= self.ref_chars.len() {
+            return Keystroke::Ignored;
+        }
+        if self.pos == 0 && self.is_correct[0] == 0 && c == ' ' {
+            return Keystroke::Ignored;
+        }
+        self.skip_indent();
+        if self.pos >= self.ref_chars.len() {
+            return Keystroke::Ignored;
+        }
+        if self.start_time.is_none() {
+            self.start_time = Some(at);
+        }
+
+        self.keystrokes += 1;
+        let expected = self.ref_chars[self.pos];
+        let fold_keys = normalization::fold_keys(expected, self.strictness).unwrap_or_default();
+        let fold_step = fold_keys.get(self.fold_typed) == Some(&c);
+        if fold_step && self.fold_typed + 1 < fold_keys.len() {
+            // The cursor stays until the last key of the fold
+            self.fold_typed += 1;
+            self.correct_keystrokes += 1;
+            self.record(LoggedKey::Char(c), Some(expected), 2, at);
+            return Keystroke::Correct;
+        }
+        self.fold_typed = 0;
+        let outcome = if fold_step || normalization::chars_match(expected, c, self.strictness) {
+            self.correct_keystrokes += 1;
+            if self.error_positions[self.pos] {
+                self.is_correct[self.pos] = 1;
+                Keystroke::Corrected
+            } else {
+                self.is_correct[self.pos] = 2;
+                Keystroke::Correct
+            }
+        } else {
+            let accent_only = normalization::is_accent_mistake(self.ref_chars[self.pos], c);
+            if accent_only {
+                self.accent_errors += 1;
+            }
+            self.is_correct[self.pos] = -1;
+            self.error_positions[self.pos] = true;
+            self.error_count += 1;
+            self.errors_this_second += if accent_only && self.half_accent_errors { 0.5 } else { 1.0 };
+            Keystroke::Incorrect
+        };
+        self.record(LoggedKey::Char(c), Some(self.ref_chars[self.pos]), self.is_correct[self.pos], at);
+
+        if outcome == Keystroke::Incorrect && self.is_strict() {
+            return outcome;
+        }
+
+        self.pressed.push(c);
+        self.pos += 1;
+        if self.completes_word_at(self.pos) {
+            self.words_done += 1;
+        }
+        if c == '\n' {
+            self.skip_indent();
+        }
+        self.tick();
+        outcome
+    }
+
+    /// Steps back one char. Mistakes are remembered, so retyping the position
+    /// scores it as corrected. Skipped indentation is stepped over with the newline before it.
+    pub fn backspace(&mut self) -> bool {
+        if self.is_finished() || self.pos == 0 {
+            return false;
+        }
+        self.fold_typed = 0;
+        while self.auto_indent && self.pos > 0 && self.is_indent(self.pos - 1) {
+            self.pos -= 1;
+            self.is_correct[self.pos] = 0;
+            self.pressed.pop();
+        }
+        if self.pos == 0 {
+            return true;
+        }
+        if self.completes_word_at(self.pos) {
+            self.words_done = self.words_done.saturating_sub(1);
+        }
+        self.pos -= 1;
+        self.is_correct[self.pos] = 0;
+        self.pressed.pop();
+        self.record(LoggedKey::Backspace, Some(self.ref_chars[self.pos]), 0, Instant::now());
+        true
+    }
+
+    /// True when the current batch is typed out and the mode expects another one.
+    pub fn needs_batch(&self) -> bool {
+        !self.is_finished()
+            && self.pos >= self.ref_chars.len()
+            && matches!(self.mode, SessionMode::Time(_) | SessionMode::Words(_))
+    }
+
+    /// Replaces the reference with the next batch, keeping the words typed so far.
+    pub fn next_batch(&mut self, reference: String) {
+        let (_, correct_words, all_words) = utils::count_correct_words(&self.reference, &self.is_correct);
+        self.archived_correct_words += correct_words;
+        self.archived_all_words += all_words;
+        self.set_reference(normalization::normalize(&reference, self.strictness));
+    }
+
+    /// Drops untyped chars past `len`, used when a frontend only shows part of the reference.
+    pub fn truncate_reference(&mut self, len: usize) {
+        if len < self.pos || len >= self.ref_chars.len() {
+            return;
+        }
+        let reference: String = self.ref_chars[..len].iter().collect();
+        self.ref_chars.truncate(len);
+        self.is_correct.truncate(len);
+        self.error_positions.truncate(len);
+        if let Some(last) = self.batches.last_mut() {
+            *last = reference.clone();
+        }
+        self.reference = reference;
+    }
+
+    fn set_reference(&mut self, reference: String) {
+        self.ref_chars = reference.chars().collect();
+        self.is_correct = vec![0; self.ref_chars.len()];
+        self.error_positions = vec![false; self.ref_chars.len()];
+        self.batches.push(reference.clone());
+        self.reference = reference;
+        self.pos = 0;
+        self.fold_typed = 0;
+    }
+
+    /// Records per-second samples and checks the end condition of the mode.
+    /// Returns true once the test is over.
+    pub fn tick(&mut self) -> bool {
+        if self.is_finished() {
+            return true;
+        }
+        let Some(start) = self.start_time else {
+            return false;
+        };
+        let now = Instant::now();
+        while start + Duration::from_secs(self.speed_per_second.len() as u64 + 1) <= now {
+            self.push_sample();
+        }
+
+        let end_time = match self.mode {
+            SessionMode::Time(secs) => {
+                let limit = start + Duration::from_secs_f32(secs);
+                (now >= limit).then_some(limit)
+            }
+            SessionMode::Words(target) => (self.words_done >= target).then_some(now),
+            SessionMode::Text | SessionMode::Practice => {
+                (self.pos >= self.ref_chars.len()).then_some(now)
+            }
+        };
+        if let Some(end_time) = end_time {
+            self.push_sample();
+            self.end_time = Some(end_time);
+        }
+        self.is_finished()
+    }
+
+    fn push_sample(&mut self) {
+        let chars_in_this_second = self.keystrokes.saturating_sub(self.sampled_keystrokes);
+        self.speed_per_second.push(chars_in_this_second as f64 * 60.0);
+        self.errors_per_second.push(self.errors_this_second);
+        self.sampled_keystrokes = self.keystrokes;
+        self.errors_this_second = 0.0;
+    }
+
+    pub fn stats(&self) -> SessionStats {
+        let (_, correct_words, all_words) = utils::count_correct_words(&self.reference, &self.is_correct);
+        let correct_words = correct_words + self.archived_correct_words;
+        let all_words = all_words + self.archived_all_words;
+        let elapsed = self.elapsed().as_secs_f64();
+        let minutes = elapsed / 60.0;
+        let (wpm, raw_wpm) = if minutes > 0.0 {
+            (correct_words as f64 / minutes, all_words as f64 / minutes)
+        } else {
+            (0.0, 0.0)
+        };
+        let half_errors = if self.half_accent_errors { self.accent_errors as f64 * 0.5 } else { 0.0 };
+        let accuracy = if self.keystrokes > 0 {
+            (self.correct_keystrokes as f64 + half_errors) / self.keystrokes as f64 * 100.0
+        } else {
+            0.0
+        };
+        SessionStats {
+            wpm,
+            raw_wpm,
+            accuracy,
+            correct_words,
+            all_words,
+            elapsed,
+            consistency: self.consistency(wpm),
+            errors: self.error_count,
+        }
+    }
+
+    fn consistency(&self, wpm: f64) -> f64 {
+        if wpm <= 0.0 || self.speed_per_second.is_empty() {
+            return 0.0;
+        }
+        let samples: Vec<f64> = self.speed_per_second.iter().map(|cpm| cpm / CHARS_PER_WORD).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        (100.0 - (variance.sqrt() / wpm * 100.0).round()).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_str(session: &mut TypingSession, s: &str) {
+        for c in s.chars() {
+            session.type_char(c);
+        }
+    }
+
+    #[test]
+    fn test_scoring_and_corrections() {
+        let mut session = TypingSession::new("ab cd".to_string(), SessionMode::Text);
+        assert_eq!(session.type_char('a'), Keystroke::Correct);
+        assert_eq!(session.type_char('x'), Keystroke::Incorrect);
+        assert!(session.backspace());
+        assert_eq!(session.type_char('b'), Keystroke::Corrected);
+        assert_eq!(session.is_correct[..2], [2, 1]);
+        assert_eq!(session.words_done, 1);
+        type_str(&mut session, " cd");
+        assert!(session.is_finished());
+        assert_eq!(session.words_done, 2);
+        assert_eq!(session.keystrokes, 6);
+        assert_eq!(session.correct_keystrokes, 5);
+    }
+
+    #[test]
+    fn test_practice_blocks_on_mistake() {
+        let mut session = TypingSession::new("fj".to_string(), SessionMode::Practice);
+        assert_eq!(session.type_char('k'), Keystroke::Incorrect);
+        assert_eq!(session.pos, 0);
+        assert_eq!(session.type_char('f'), Keystroke::Corrected);
+        assert_eq!(session.pos, 1);
+    }
+
+    #[test]
+    fn test_backspace_undoes_word_completion() {
+        let mut session = TypingSession::new("ab cd".to_string(), SessionMode::Text);
+        type_str(&mut session, "ab");
+        assert_eq!(session.words_done, 1);
+        session.backspace();
+        assert_eq!(session.words_done, 0);
+        assert_eq!(session.is_correct[1], 0);
+    }
+
+    #[test]
+    fn test_leading_space_is_ignored() {
+        let mut session = TypingSession::new("ab".to_string(), SessionMode::Text);
+        assert_eq!(session.type_char(' '), Keystroke::Ignored);
+        assert!(!session.is_started());
+    }
+
+    #[test]
+    fn test_word_mode_batches() {
+        let mut session = TypingSession::new("ab cd".to_string(), SessionMode::Words(3));
+        type_str(&mut session, "ab cd");
+        assert!(session.needs_batch());
+        session.next_batch("ef gh".to_string());
+        type_str(&mut session, "ef");
+        assert!(session.is_finished());
+        assert_eq!(session.stats().correct_words, 3);
+    }
+
+    #[test]
+    fn test_auto_indent_after_newline() {
+        let mut session = TypingSession::new("if x {\n    y;\n}".to_string(), SessionMode::Text);
+        session.auto_indent = true;
+        type_str(&mut session, "if x {\n");
+        assert_eq!(session.pos, 11);
+        assert_eq!(session.keystrokes, 7);
+        // One backspace steps back over the indentation and the newline
+        session.backspace();
+        assert_eq!(session.pos, 6);
+        type_str(&mut session, "\ny;\n}");
+        assert!(session.is_finished());
+        assert_eq!(session.stats().correct_words, 5);
+    }
+
+    #[test]
+    fn test_enter_at_line_break() {
+        let mut session = TypingSession::new("one\n  two".to_string(), SessionMode::Text);
+        assert!(session.is_multiline());
+        assert_eq!(session.type_char('x'), Keystroke::Incorrect);
+        session.backspace();
+        type_str(&mut session, "one");
+        assert_eq!(session.type_char(' '), Keystroke::Incorrect);
+        session.backspace();
+        assert_eq!(session.type_char('\n'), Keystroke::Corrected);
+        // Without auto indent the indentation has to be typed
+        assert_eq!(session.pos, 4);
+        type_str(&mut session, "  two");
+        assert!(session.is_finished());
+        assert_eq!(session.stats().correct_words, 2);
+    }
+
+    #[test]
+    fn test_scripted_keystrokes_stats() {
+        let mut session = TypingSession::new("the cat sat".to_string(), SessionMode::Text);
+        // A mistake fixed with backspace, then one left in "cat"
+        type_str(&mut session, "thx");
+        session.backspace();
+        type_str(&mut session, "e cax sat");
+        assert!(session.is_finished());
+        assert_eq!(session.is_correct, vec![2, 2, 1, 2, 2, 2, -1, 2, 2, 2, 2]);
+
+        // Half a minute, so WPM is twice the word count
+        let start = session.start_time.unwrap();
+        session.end_time = Some(start + Duration::from_secs(30));
+        let stats = session.stats();
+        assert_eq!(stats.correct_words, 2, "the corrected \"the\" counts, \"cax\" doesn't");
+        assert_eq!(stats.all_words, 3);
+        assert_eq!(stats.wpm, 4.0);
+        assert_eq!(stats.raw_wpm, 6.0);
+        assert_eq!(stats.errors, 2);
+        // 10 of 12 keys were right, the backspace isn't a keystroke
+        assert_eq!(session.keystrokes, 12);
+        assert_eq!(stats.accuracy, 10.0 / 12.0 * 100.0);
+        assert_eq!(stats.elapsed, 30.0);
+    }
+
+    #[test]
+    fn test_half_accent_errors() {
+        let mut session = TypingSession::new("café".to_string(), SessionMode::Text);
+        session.half_accent_errors = true;
+        type_str(&mut session, "cafe");
+        assert_eq!(session.accent_errors, 1);
+        assert_eq!(session.is_correct[3], -1);
+        assert_eq!(session.stats().accuracy, 87.5);
+    }
+
+    #[test]
+    fn test_either_accepts_both_forms_of_long_folds() {
+        for typed in ["Straße…", "Strasse...", "Strasse…"] {
+            let mut session = TypingSession::new("Straße…".to_string(), SessionMode::Text);
+            session.set_strictness(Strictness::Either);
+            assert_eq!(session.reference, "Straße…");
+            type_str(&mut session, typed);
+            assert_eq!(session.is_correct, vec![2; 7], "{typed}");
+        }
+
+        let mut session = TypingSession::new("ß".to_string(), SessionMode::Text);
+        session.set_strictness(Strictness::Either);
+        type_str(&mut session, "sx");
+        assert_eq!(session.is_correct, vec![-1]);
+    }
+}
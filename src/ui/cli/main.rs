@@ -6,11 +6,18 @@ use crossterm::{
 use std::io::{stdout, Write};
 use std::time::Instant;
 use crossterm::event::{self, Event, KeyCode, KeyEvent};
-use std::collections::VecDeque;
+use crate::practice::{self, LevelKey};
 use crate::utils;
-use crate::practice;
+use crate::pacer::Pacer;
+use crate::config::AppConfig;
+use crate::compose::Composer;
+use crate::keyboard_layout::KeyboardLayout;
+use crate::typing_session::{SessionMode, TypingSession};
 
 
+/// Screen row of the first reference line
+const REF_ROW: u16 = 2;
+
 struct RawModeGuard;
 
 impl RawModeGuard {
@@ -35,117 +42,165 @@ fn display_results(elapsed: f64, accuracy: f64, wpm: f64, raw: f64) {
     );
 }
 
-fn initial_display(reference: &str, timer_pos: (u16, u16)) {
+/// Line breaks of the reference have to be typed, so they are shown
+fn display_char(c: char) -> char {
+    if c == '\n' { '↵' } else { c }
+}
+
+/// Column and row of every reference char, wrapped like the TUI and GUI wrap it,
+/// followed by the position after the last char
+fn char_positions(lines: &[String]) -> Vec<(u16, u16)> {
+    let mut positions: Vec<(u16, u16)> = lines
+        .iter()
+        .enumerate()
+        .flat_map(|(row, line)| (0..line.chars().count()).map(move |col| (col as u16, row as u16 + REF_ROW)))
+        .collect();
+    let end = positions.last().map_or((0, REF_ROW), |&(col, row)| (col + 1, row));
+    positions.push(end);
+    positions
+}
+
+fn cursor_at(positions: &[(u16, u16)], pos: usize) -> cursor::MoveTo {
+    let (col, row) = positions.get(pos).or(positions.last()).copied().unwrap_or((0, REF_ROW));
+    cursor::MoveTo(col, row)
+}
+
+fn initial_display(lines: &[String], timer_pos: (u16, u16)) {
     let mut stdout = stdout();
 
+    queue!(stdout, Clear(ClearType::All), SetAttribute(Attribute::Dim)).unwrap();
+    for (row, line) in lines.iter().enumerate() {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, row as u16 + REF_ROW),
+            Print(line.chars().map(display_char).collect::<String>()),
+        ).unwrap();
+    }
     queue!(
         stdout,
-        Clear(ClearType::All),
-        cursor::MoveTo(0, 2),
-        SetAttribute(Attribute::Dim),
-        Print(reference),
         SetAttribute(Attribute::Reset),
         cursor::MoveTo(timer_pos.0, timer_pos.1),
         Print("Time: 00:00"),
-        cursor::MoveTo(0, 2)
+        cursor::MoveTo(0, REF_ROW)
     ).unwrap();
     stdout.flush().unwrap();
 }
 
-pub fn type_loop(reference: &str, time_limit: Option<u64>, start_time: &mut Option<Instant>, practice: Option<usize>, is_correct: &mut VecDeque<i32>, mode: &str) -> i32 {
-    let ref_chars: Vec<char> = reference.chars().collect();
+/// Runs a whole test, loading new batches until the session ends.
+/// Returns 1 when the user interrupted the test.
+pub fn run_test(session: &mut TypingSession, practice: Option<LevelKey>, mut pacer: Option<Pacer>, mut next_batch: impl FnMut() -> String) -> i32 {
+    let app_config = AppConfig::load();
+    session.auto_indent = app_config.skip_indent;
+    session.set_strictness(app_config.strictness);
+    session.half_accent_errors = app_config.half_accent_errors;
+    loop {
+        if type_loop(session, practice.is_some(), &mut pacer) != 0 {
+            return 1;
+        }
+        if session.needs_batch() {
+            session.next_batch(next_batch());
+        } else {
+            break;
+        }
+    }
+    if !session.is_started() {
+        return 0;
+    }
+    let stats = session.stats();
+    let mut stdout = stdout();
+
+    if let Some(level) = practice {
+        let term_width = crossterm::terminal::size().map(|(w, _)| w as usize).unwrap_or(80);
+        let lines = utils::wrap_reference(&session.reference, term_width).len();
+
+        let prev_best_wpm = practice::get_prev_best_wpm(&level);
+
+        queue!(
+            stdout,
+            cursor::MoveTo(0, (lines as u16) + 1)
+        ).unwrap();
+
+        if practice::passes(stats.wpm, stats.accuracy) {
+            println!("\nLevel passed!\n")
+        } else {
+            println!("\nAchieve {} WPM and {}% accuracy to pass this level.\n", practice::WPM_MIN, practice::ACCURACY_MIN);
+        }
+
+        if prev_best_wpm < stats.wpm {
+            println!("\nNew highscore for this level!");
+        }
+    }
+    show_final_results(session, pacer.as_ref());
+
+    0
+}
+
+/// Types through the current batch of the session. Returns 1 when the user
+/// interrupted the test, 0 when the batch is done or the test is over.
+pub fn type_loop(session: &mut TypingSession, practice_mode: bool, pacer: &mut Option<Pacer>) -> i32 {
     let mut stdout = stdout();
     let _raw_guard = RawModeGuard::new();
 
     let (width, _height) = crossterm::terminal::size().unwrap();
     let timer_pos = (width.saturating_sub(15), 0);
 
-    initial_display(reference, timer_pos);
+    let lines = utils::wrap_reference(&session.reference, width as usize);
+    let positions = char_positions(&lines);
+    initial_display(&lines, timer_pos);
 
-    let mut user_input = String::new();
-    let mut position = 0;
-    let mut error_positions = vec![false; ref_chars.len()];
     let mut last_update = Instant::now();
+    let mut pacer_column = None;
+    let emulated = AppConfig::load().emulated_layout();
+    let mut composer = Composer::default();
 
-    let all_words = reference.split_whitespace().count();
-    let mut words_done = 0;
+    let all_words = match session.mode {
+        SessionMode::Words(target) => target,
+        _ => session.reference.split_whitespace().count(),
+    };
 
     loop {
-        if mode == "time" {
-            update_timer(&mut stdout, timer_pos,*start_time, &mut last_update, width, position, time_limit);
+        if let SessionMode::Time(time_limit) = session.mode {
+            update_timer(&mut stdout, timer_pos, session, &mut last_update, &positions, time_limit);
         } else {
-            update_word_count(&mut stdout, timer_pos, words_done, width, position, all_words);
+            update_word_count(&mut stdout, timer_pos, session.words_done, &positions, session.pos, all_words);
+        }
+        if let Some(pacer) = pacer.as_mut() {
+            pacer.update(session);
+            update_pacer_marker(&mut stdout, pacer, session, &positions, &mut pacer_column);
         }
 
-        let byte_opt = poll_input();
-        if byte_opt.is_none() {
-            let limit = time_limit.unwrap_or(30);
-            if start_time.is_some() && start_time.unwrap().elapsed().as_secs() >= limit {
+        // Dead keys and decomposed accents are typed as one char
+        let keys = match poll_input(emulated.as_ref()) {
+            Some(ch) if handle_control_keys(ch, &mut stdout) => return 1,
+            Some('\u{8}' | '\u{7f}') if composer.cancel() => Vec::new(),
+            Some(ch) => composer.feed(ch, session.ref_chars.get(session.pos).copied(), session.strictness),
+            None => composer.flush_stale().into_iter().collect(),
+        };
+        if keys.is_empty() {
+            if session.tick() {
                 break;
             }
             std::thread::sleep(std::time::Duration::from_millis(10));
             continue;
         }
 
-        let byte = byte_opt.unwrap();
-
-        if handle_control_keys(byte, &mut stdout) {
-            return 1;
+        for (ch, pressed) in keys {
+            handle_typing(
+                ch,
+                pressed,
+                session,
+                &mut stdout,
+                &positions,
+                practice_mode,
+            );
         }
 
-        handle_typing(
-            byte,
-            &mut user_input,
-            &ref_chars,
-            &mut position,
-            &mut error_positions,
-            &mut stdout,
-            is_correct,
-            practice.is_some(),
-            &mut words_done,
-            start_time
-        );
-
         stdout.flush().unwrap();
 
-        if position >= ref_chars.len() {
+        if session.is_finished() || session.needs_batch() {
             break;
         }
     }
-    if let (Some(practice_level), Some(start_time_val)) = (practice, start_time.as_ref()) {
-        let elapsed = start_time_val.elapsed().as_secs_f64();
-        let error_count = error_positions.iter().filter(|&&e| e).count();
-        let accuracy = 100.0 - (error_count as f64 / reference.len() as f64 * 100.0);
-        let wpm = (user_input.len() as f64 / 5.0) / (elapsed / 60.0);
-
-        let term_width = crossterm::terminal::size().map(|(w, _)| w as usize).unwrap_or(80);
-        let lines = reference.len().div_ceil(term_width);
-
-        practice::save_results(
-            elapsed,
-            accuracy,
-            wpm,
-            practice_level,
-        );
-
-        queue!(
-            stdout,
-            cursor::MoveTo(0, (lines as u16) + 1)
-        ).unwrap();
-
-        if wpm >= practice::WPM_MIN {
-            println!("\nLevel passed!\n")
-        } else {
-            println!("\nAchive WPM of 35 to pass this level.\n");
-        }
-
-        let prev_best_wpm = practice::get_prev_best_wpm(practice_level);
-
-        if prev_best_wpm < wpm {
-            println!("\nNew highscore for this level!");
-        }
-    }
-    show_final_results(reference, start_time.expect("No start time"), is_correct);
 
     0
 }
@@ -153,23 +208,13 @@ pub fn type_loop(reference: &str, time_limit: Option<u64>, start_time: &mut Opti
 fn update_timer(
     stdout: &mut std::io::Stdout,
     timer_pos: (u16, u16),
-    start_time: Option<Instant>,
+    session: &TypingSession,
     last_update: &mut Instant,
-    width: u16,
-    position: usize,
-    time_limit: Option<u64>,
+    positions: &[(u16, u16)],
+    time_limit: f32,
 ) {
-    if last_update.elapsed().as_millis() > 100 || start_time.is_none(){
-        let elapsed_secs = if let Some(start_time) = start_time {
-            start_time.elapsed().as_secs()
-        } else {
-            0
-        };
-        let remaining = if let Some(limit) = time_limit {
-            limit.saturating_sub(elapsed_secs)
-        } else {
-            elapsed_secs
-        };
+    if last_update.elapsed().as_millis() > 100 || !session.is_started() {
+        let remaining = (time_limit as u64).saturating_sub(session.elapsed().as_secs());
 
         let display_mins = remaining / 60;
         let display_secs = remaining % 60;
@@ -179,7 +224,7 @@ fn update_timer(
             cursor::MoveTo(timer_pos.0, timer_pos.1),
             Clear(ClearType::UntilNewLine),
             Print(format!("Time: {:02}:{:02}", display_mins, display_secs)),
-            cursor::MoveTo(position as u16 % width, position as u16 / width + 2)
+            cursor_at(positions, session.pos)
         )
         .unwrap();
 
@@ -192,7 +237,7 @@ fn update_word_count(
     stdout: &mut std::io::Stdout,
     pos: (u16, u16),
     words_done: usize,
-    width: u16,
+    positions: &[(u16, u16)],
     position: usize,
     all_words: usize,
 ) {
@@ -201,14 +246,44 @@ fn update_word_count(
         cursor::MoveTo(pos.0, pos.1),
         Clear(ClearType::UntilNewLine),
         Print(format!("{}\\{}", words_done, all_words)),
-        cursor::MoveTo(position as u16 % width, position as u16 / width + 2)
+        cursor_at(positions, position)
     )
     .unwrap();
 
     stdout.flush().unwrap();
 }
 
-fn poll_input() -> Option<u8> {
+/// Marks the column of the pacer caret on the line above the reference
+fn update_pacer_marker(
+    stdout: &mut std::io::Stdout,
+    pacer: &Pacer,
+    session: &TypingSession,
+    positions: &[(u16, u16)],
+    pacer_column: &mut Option<u16>,
+) {
+    let column = pacer.cursor(session).and_then(|pos| positions.get(pos)).map(|&(col, _)| col);
+    if column == *pacer_column {
+        return;
+    }
+    *pacer_column = column;
+
+    queue!(stdout, cursor::MoveTo(0, 1), Clear(ClearType::CurrentLine)).unwrap();
+    if let Some(column) = column {
+        queue!(
+            stdout,
+            cursor::MoveTo(column, 1),
+            SetForegroundColor(Color::Cyan),
+            Print('▾'),
+            SetForegroundColor(Color::Reset)
+        )
+        .unwrap();
+    }
+    queue!(stdout, cursor_at(positions, session.pos)).unwrap();
+    stdout.flush().unwrap();
+}
+
+/// Next key press, with typed chars moved to `emulated` when a layout is emulated
+fn poll_input(emulated: Option<&KeyboardLayout>) -> Option<char> {
     if event::poll(std::time::Duration::from_millis(10)).unwrap() {
         if let Event::Key(KeyEvent { code, modifiers, kind: _kind, .. }) = event::read().unwrap() {
             #[cfg(windows)]
@@ -216,12 +291,12 @@ fn poll_input() -> Option<u8> {
                 return None;
             }
             match (code, modifiers) {
-                (KeyCode::Char('c'), event::KeyModifiers::CONTROL) => Some(0x03), // Ctrl+C
-                (KeyCode::Char('d'), event::KeyModifiers::CONTROL) => Some(0x04), // Ctrl+D
-                (KeyCode::Char(c), _) => Some(c as u8),
-                (KeyCode::Backspace, _) => Some(8),
-                (KeyCode::Esc, _) => Some(0x1B),
-                (KeyCode::Enter, _) => Some(b'\n'),
+                (KeyCode::Char('c'), event::KeyModifiers::CONTROL) => Some('\u{3}'), // Ctrl+C
+                (KeyCode::Char('d'), event::KeyModifiers::CONTROL) => Some('\u{4}'), // Ctrl+D
+                (KeyCode::Char(c), _) => Some(emulated.map_or(c, |layout| layout.from_qwerty(c))),
+                (KeyCode::Backspace, _) => Some('\u{8}'),
+                (KeyCode::Esc, _) => Some('\u{1b}'),
+                (KeyCode::Enter, _) => Some('\n'),
                 _ => None,
             }
         } else {
@@ -232,9 +307,9 @@ fn poll_input() -> Option<u8> {
     }
 }
 
-fn handle_control_keys(byte: u8, stdout: &mut std::io::Stdout) -> bool {
-    match byte {
-        0x03 | 0x04 => {
+fn handle_control_keys(ch: char, stdout: &mut std::io::Stdout) -> bool {
+    match ch {
+        '\u{3}' | '\u{4}' => {
             queue!(
                 stdout,
                 Clear(ClearType::All),
@@ -243,130 +318,69 @@ fn handle_control_keys(byte: u8, stdout: &mut std::io::Stdout) -> bool {
             .unwrap();
             true
         }
-        0x1B => true, // ESC
+        '\u{1b}' => true, // ESC
         _ => false,
     }
 }
 
-#[allow(clippy::too_many_arguments)]
 fn handle_typing(
-    byte: u8,
-    user_input: &mut String,
-    ref_chars: &[char],
-    position: &mut usize,
-    error_positions: &mut [bool],
+    c: char,
+    pressed: Instant,
+    session: &mut TypingSession,
     stdout: &mut std::io::Stdout,
-    is_correct: &mut VecDeque<i32>,
+    positions: &[(u16, u16)],
     practice_mode: bool,
-    words_done: &mut usize,
-    start_time: &mut Option<Instant>
 ) {
-    match byte {
+    let start = session.pos;
+    match c {
         // backspace
-        8 | 127 if *position > 0 => {
-            is_correct[*position] = 0;
-            *position -= 1;
-            user_input.pop();
-            if ref_chars.len() > *position + 1 && ref_chars[*position + 1] == ' ' {
-                *words_done -= 1;
+        '\u{8}' | '\u{7f}' => {
+            if session.backspace() {
+                draw_typed(session, stdout, positions, session.pos..start, practice_mode);
             }
-
-            queue!(
-                stdout,
-                cursor::MoveLeft(1),
-                SetAttribute(Attribute::Dim),
-                Print(ref_chars[*position]),
-                SetAttribute(Attribute::Reset),
-                cursor::MoveLeft(1),
-                SetForegroundColor(Color::Reset),
-            )
-            .unwrap();
         }
-        _ if *position < ref_chars.len() => {
-            let c = byte as char;
-            let ref_char = ref_chars[*position];
-            
-            if !(ref_chars.len() <= *position + 1 || ref_chars[*position + 1] != ' ' || practice_mode && c != ref_char) {
-                *words_done += 1;
-            }
-            if c == ref_char {
-                if start_time.is_none() {
-                    *start_time = Some(Instant::now());
-                }
-                if error_positions[*position] {
-                    is_correct[*position] = 1;
-                    // Corrected an error: yellow
-                    let char_display = if practice_mode && c == ' ' {
-                        '_'
-                    } else {
-                        c
-                    };
-                    queue!(
-                        stdout,
-                        SetForegroundColor(Color::Yellow),
-                        Print(char_display),
-                        SetForegroundColor(Color::Reset)
-                    )
-                    .unwrap();
-                } else {
-                    // Correct on first try: green
-                    is_correct[*position] = 2;
-                    queue!(
-                        stdout,
-                        SetForegroundColor(Color::White),
-                        Print(c),
-                        SetForegroundColor(Color::Reset)
-                    )
-                    .unwrap();
-                }
-                user_input.push(c);
-                *position += 1;
-            } else {
-                if start_time.is_none() {
-                    *start_time = Some(Instant::now());
-                }
-                is_correct[*position] = -1;
-                error_positions[*position] = true;
-                if practice_mode {
-                    return;
-                }
-                if ref_char == ' ' {
-                    queue!(
-                        stdout,
-                        SetForegroundColor(Color::Red),
-                        Print('_'),
-                        SetForegroundColor(Color::Reset)
-                    )
-                .unwrap();
-                } else {
-                    queue!(
-                        stdout,
-                        SetForegroundColor(Color::Red),
-                        Print(ref_char),
-                        SetForegroundColor(Color::Reset)
-                    )
-                    .unwrap();
-                }
-                user_input.push(c);
-                *position += 1;
-            }
+        // Enter only types the line breaks of the reference
+        '\n' if !session.is_multiline() => {}
+        _ => {
+            session.type_char_at(c, pressed);
+            // Indentation skipped after a newline is drawn with the key
+            draw_typed(session, stdout, positions, start..session.pos, practice_mode);
         }
-        _ => {}
     }
+    queue!(stdout, cursor_at(positions, session.pos)).unwrap();
 }
 
-fn show_final_results(
-    reference: &str,
-    start_time: Instant,
-    is_correct: &VecDeque<i32>,
+/// Redraws reference chars colored by how they were typed, dim when they are not typed yet
+fn draw_typed(
+    session: &TypingSession,
+    stdout: &mut std::io::Stdout,
+    positions: &[(u16, u16)],
+    range: std::ops::Range<usize>,
+    practice_mode: bool,
 ) {
-    let (_corrected_words, correct_words, all_words) = utils::count_correct_words(reference, is_correct);
-    let elapsed = start_time.elapsed().as_secs_f64();
-    let wpm = correct_words as f64 / (elapsed / 60.0);
-    let raw = all_words as f64 / (elapsed / 60.0);
+    queue!(stdout, cursor_at(positions, range.start)).unwrap();
+    for i in range {
+        let ref_char = session.ref_chars[i];
+        let (color, shown) = match session.is_correct[i] {
+            2 => (Color::White, display_char(ref_char)),
+            1 if practice_mode && ref_char == ' ' => (Color::Yellow, '_'),
+            1 => (Color::Yellow, display_char(ref_char)),
+            -1 if ref_char == ' ' => (Color::Red, '_'),
+            -1 => (Color::Red, display_char(ref_char)),
+            _ => {
+                queue!(stdout, SetAttribute(Attribute::Dim), Print(display_char(ref_char)), SetAttribute(Attribute::Reset)).unwrap();
+                continue;
+            }
+        };
+        queue!(stdout, SetForegroundColor(color), Print(shown), SetForegroundColor(Color::Reset)).unwrap();
+    }
+}
+
+fn show_final_results(session: &TypingSession, pacer: Option<&Pacer>) {
+    let stats = session.stats();
 
     let term_width = crossterm::terminal::size().map(|(w, _)| w as usize).unwrap_or(80);
-    let lines = reference.len().div_ceil(term_width);
+    let lines = utils::wrap_reference(&session.reference, term_width).len();
 
     let mut stdout = stdout();
     queue!(
@@ -375,19 +389,15 @@ fn show_final_results(
     )
     .unwrap();
     stdout.flush().unwrap();
-    
-    let correct_count = is_correct.iter().filter(|&&v| v == 1 || v == 2).count();
-    let all_pressed_count = is_correct.iter().filter(|&&v| v != 0).count();
-    let accuracy = if correct_count > 0 {
-        (correct_count as f64 / all_pressed_count as f64) * 100.0
-    } else {
-        0.0
-    };
-    display_results(elapsed, accuracy, wpm, raw);
-    
+
+    display_results(stats.elapsed, stats.accuracy, stats.wpm, stats.raw_wpm);
+    if let Some(pacer) = pacer {
+        println!("{}", pacer.summary());
+    }
+
     queue!(
         stdout,
         cursor::MoveToNextLine(1)
     ).unwrap();
     stdout.flush().unwrap();
-}
\ No newline at end of file
+}
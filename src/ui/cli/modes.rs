@@ -1,29 +1,86 @@
 use std::path::PathBuf;
-use std::fs;
-use std::time::Instant;
-use std::collections::VecDeque;
 
 use crate::ui::cli;
 use crate::Cli;
 use crate::utils;
 use crate::practice;
 use crate::language::Language;
+use crate::config::AppConfig;
+use crate::pacer::Pacer;
+use crate::keyboard_layout::KeyboardLayout;
+use crate::word_sampling::{SamplingMode, WordSampler};
+use crate::adaptive;
+use crate::key_stats::KeyStats;
+use crate::keystroke_log;
+use crate::leaderboard::{self, LeaderboardEntry, TestConfig, TestType};
+use crate::text_library::TextLibrary;
+use crate::quotes::{self, QuoteHistory, QuoteLength};
+use crate::typing_session::{SessionMode, TypingSession};
 
 fn get_language_from_args(args: &Cli) -> Language {
-    args.language
-        .as_ref()
-        .and_then(|lang_str| Language::from_str(lang_str))
-        .unwrap_or_default()
+    let Some(lang_str) = args.language.as_ref() else {
+        return Language::default();
+    };
+    Language::from_str(lang_str).unwrap_or_else(|| {
+        let names: Vec<String> = Language::all().iter().map(|l| l.to_string().to_lowercase()).collect();
+        eprintln!("Unknown language '{}', using English. Available: {}", lang_str, names.join(", "));
+        Language::default()
+    })
+}
+
+/// Layout from `--layout`, falling back to the layout in the settings
+fn get_layout_from_args(args: &Cli) -> KeyboardLayout {
+    KeyboardLayout::find(&args.layout.clone().unwrap_or_else(|| AppConfig::load().keyboard_layout))
+}
+
+/// Sampler from `--sampling` and `--no-repeat`, falling back to the settings
+fn get_sampler_from_args(args: &Cli, language: Language) -> WordSampler {
+    let app_config = AppConfig::load();
+    let mode = match args.sampling.as_ref() {
+        Some(mode_str) => SamplingMode::from_str(mode_str).unwrap_or_else(|| {
+            eprintln!("Unknown sampling mode '{}', using uniform. Available: uniform, frequency", mode_str);
+            SamplingMode::Uniform
+        }),
+        None => app_config.sampling_mode,
+    };
+    WordSampler::new(mode, args.no_repeat.unwrap_or(app_config.no_repeat_within), language)
+}
+
+/// Quote length from `--quote-length`, falling back to the settings
+fn get_quote_length_from_args(args: &Cli) -> QuoteLength {
+    let Some(length_str) = args.quote_length.as_ref() else {
+        return AppConfig::load().quote_length;
+    };
+    QuoteLength::from_str(length_str).unwrap_or_else(|| {
+        eprintln!("Unknown quote length '{}', using all. Available: all, short, medium, long, thicc, favorites", length_str);
+        QuoteLength::All
+    })
 }
 
-pub fn wiki_mode() {
+/// Pacer from `--pace`, falling back to the target wpm in the settings
+fn get_pacer_from_args(args: &Cli) -> Option<Pacer> {
+    Pacer::new(args.pace.unwrap_or_else(|| AppConfig::load().pacer_wpm))
+}
+
+/// Records a finished test in the leaderboard history and keystroke logs, unless `--no-save`
+fn save_session(args: &Cli, session: &TypingSession, test_type: TestType, test_mode: &str, language: Language, config: TestConfig) {
+    if args.no_save || !session.is_finished() {
+        return;
+    }
+    let entry = LeaderboardEntry::from_session(session, test_type, test_mode, language, config);
+    if let Err(e) = leaderboard::save_entry(&entry) {
+        eprintln!("Failed to save result: {:?}", e);
+    }
+    keystroke_log::save_session_at(session, entry.test_type, test_mode, language, &entry.timestamp);
+}
+
+pub fn wiki_mode(args: &Cli) {
     println!("Starting Wikipedia mode test");
     
-    let reference = utils::get_wiki_summary();
-    let mut start_time: Option<Instant> = None;
-    let mut is_correct: VecDeque<i32> = VecDeque::from(vec![0; reference.len()]);
-
-    cli::main::type_loop(&reference, None, &mut start_time, None, &mut is_correct, "wiki");
+    let language = get_language_from_args(args);
+    let mut session = TypingSession::new(utils::get_wiki_summary(language), SessionMode::Text);
+    cli::main::run_test(&mut session, None, get_pacer_from_args(args), String::new);
+    save_session(args, &session, TestType::Wiki, "wiki", language, TestConfig::default());
 }
 
 pub fn word_mode(args: &Cli) {
@@ -49,12 +106,12 @@ pub fn word_mode(args: &Cli) {
 
     let language = get_language_from_args(args);
     let word_list = utils::read_first_n_words(top_words, language);
+    let sampler = get_sampler_from_args(args, language);
 
-    let reference = utils::get_reference(punctuation, digits, &word_list, word_number);
-    let mut start_time: Option<Instant> = None;
-    let mut is_correct: VecDeque<i32> = VecDeque::from(vec![0; reference.len()]);
-
-    cli::main::type_loop(&reference, None, &mut start_time, None, &mut is_correct, "word");
+    let reference = utils::get_reference(punctuation, digits, &word_list, word_number, &sampler);
+    let mut session = TypingSession::new(reference, SessionMode::Words(word_number));
+    cli::main::run_test(&mut session, None, get_pacer_from_args(args), || utils::get_reference(punctuation, digits, &word_list, word_number, &sampler));
+    save_session(args, &session, TestType::Word(word_number), "word", language, TestConfig { punctuation, digits, top_words, batch_size: word_number });
 }
 
 pub fn time_mode(args: &Cli) {
@@ -75,92 +132,168 @@ pub fn time_mode(args: &Cli) {
 
     let language = get_language_from_args(args);
     let word_list = utils::read_first_n_words(top_words, language);
+    let sampler = get_sampler_from_args(args, language);
 
     let batch_size = 20;
-    let mut start_time: Option<Instant> = None;
 
     let punctuation = args.punctuation;
     let digits = args.digits;
 
-    'outer: while start_time.is_none() || start_time.unwrap().elapsed().as_secs() < time_limit {
-        let reference = utils::get_reference(punctuation, digits, &word_list, batch_size) + " ";
-        let mut is_correct: VecDeque<i32> = VecDeque::from(vec![0; reference.len()]);
-        
-        if let Some(start_time_val) = start_time {
-            let elapsed = start_time_val.elapsed().as_secs();
-            let remaining_time = if time_limit > elapsed {
-                Some(time_limit - elapsed)
-            } else {
-                Some(0)
-            };
-    
-            if remaining_time <= Some(0) {
-                break;
-            }
-        }
+    let reference = utils::get_reference(punctuation, digits, &word_list, batch_size, &sampler);
+    let mut session = TypingSession::new(reference, SessionMode::Time(time_limit as f32));
+    let res = cli::main::run_test(&mut session, None, get_pacer_from_args(args), || utils::get_reference(punctuation, digits, &word_list, batch_size, &sampler));
+    if res != 0 {
+        println!("Test interrupted by user.");
+    }
+    save_session(args, &session, TestType::Time(time_limit as u32), "time", language, TestConfig { punctuation, digits, top_words, batch_size });
+}
 
-        let res = cli::main::type_loop(&reference, Some(time_limit), &mut start_time, None, &mut is_correct, "time");
-        if res != 0 {
-            println!("Test interrupted by user.");
-            break;
-        }
+pub fn custom_text(path: &PathBuf, args: &Cli) {
+    println!("Starting custom text test with file: {:?}", path);
+    utils::validate_custom_file(path).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    // Long files are typed through in chunks, so the file goes into the library first
+    let mut library = TextLibrary::load();
+    match library.find_or_import(path) {
+        Ok(name) => library_text(&name, args),
+        Err(e) => eprintln!("Error reading file: {:?}", e),
+    }
+}
 
-        if start_time.unwrap().elapsed().as_secs() >= time_limit {
-            break 'outer;
+/// Types the next chunk of a library text and moves its position on when the chunk is finished
+pub fn library_text(name: &str, args: &Cli) {
+    let mut library = TextLibrary::load();
+    let Some(text) = library.get(name) else {
+        eprintln!("No text named '{}' in the library. Available: {}", name, library.names().join(", "));
+        return;
+    };
+    let name = text.name.clone();
+    println!("Starting '{}' at {:.0}%", name, text.progress());
+
+    let chunk_size = args.chunk.unwrap_or_else(|| AppConfig::load().chunk_size);
+    let Some(chunk) = library.next_chunk(&name, chunk_size) else {
+        eprintln!("Error reading text");
+        return;
+    };
+    let mut session = TypingSession::new(chunk.text, SessionMode::Text);
+    cli::main::run_test(&mut session, None, get_pacer_from_args(args), String::new);
+    if !args.no_save && session.is_finished() {
+        let stats = session.stats();
+        if let Err(e) = library.record(&name, chunk.end, stats.wpm, stats.accuracy, session.words_done) {
+            eprintln!("Failed to save progress: {:?}", e);
         }
     }
+    save_session(args, &session, TestType::Custom, "library", get_language_from_args(args), TestConfig::default());
 }
 
-pub fn custom_text(path: &PathBuf) {
-    println!("Starting custom text test with file: {:?}", path);
+pub fn import_text(path: &PathBuf) {
     utils::validate_custom_file(path).unwrap_or_else(|err| {
         eprintln!("{}", err);
         std::process::exit(1);
     });
-    let reference = match fs::read_to_string(path) {
-        Ok(content) => content.replace('\n', " "),
-        Err(_) => {
-            eprintln!("Error reading file");
+    let mut library = TextLibrary::load();
+    match library.import(path) {
+        Ok(name) => println!("Imported '{}', type it with typeman --cli --text \"{}\"", name, name),
+        Err(e) => eprintln!("Error importing file: {:?}", e),
+    }
+}
+
+pub fn list_texts() {
+    for summary in TextLibrary::load().summaries() {
+        println!("{}", summary);
+    }
+}
+
+pub fn delete_text(name: &str) {
+    match TextLibrary::load().remove(name) {
+        Ok(true) => println!("Deleted '{}'", name),
+        Ok(false) => eprintln!("No text named '{}' in the library", name),
+        Err(e) => eprintln!("Error deleting text: {:?}", e),
+    }
+}
+
+pub fn quotes(args: &Cli) {
+    println!("Starting random quote test");
+    let language = get_language_from_args(args);
+    let reference = match quotes::next_quote(language, get_quote_length_from_args(args)) {
+        Ok(reference) => reference,
+        Err(e) => {
+            eprintln!("{}", e);
             return;
         }
     };
-    let mut is_correct: VecDeque<i32> = VecDeque::from(vec![0; reference.len()]);
-    let mut start_time: Option<Instant> = None;
-    cli::main::type_loop(reference.as_str(), None, &mut start_time, None, &mut is_correct, "custom");
+    let mut session = TypingSession::new(reference, SessionMode::Text);
+    cli::main::run_test(&mut session, None, get_pacer_from_args(args), String::new);
+    save_session(args, &session, TestType::Quote, "quote", language, TestConfig::default());
+    if session.is_finished() {
+        println!("Run 'typeman --cli --favorite' to add this quote to your favorites");
+    }
 }
 
-pub fn quotes() {
-    println!("Starting random quote test");
-    let reference = utils::get_random_quote();
-    let mut start_time: Option<Instant> = None;
-    let mut is_correct: VecDeque<i32> = VecDeque::from(vec![0; reference.len()]);
+pub fn toggle_favorite() {
+    let last = QuoteHistory::load().last().map(|quote| quote.reference());
+    match (quotes::toggle_last_favorite(), last) {
+        (Some(true), Some(quote)) => println!("Added to favorites: {}", quote),
+        (Some(false), Some(quote)) => println!("Removed from favorites: {}", quote),
+        _ => eprintln!("No quote typed yet, start one with typeman --cli -q"),
+    }
+}
+
+pub fn adaptive(args: &Cli) {
+    println!("Starting adaptive practice on your weakest keys");
+
+    let word_number = match args.word_number {
+        Some(Some(n)) => n,
+        _ => adaptive::WORD_NUMBER,
+    };
+    if !(1..=1000).contains(&word_number) {
+        eprintln!("Word number must be between 1 and 1000.");
+        return;
+    }
 
-    cli::main::type_loop(&reference, None, &mut start_time, None, &mut is_correct, "quote");
+    let language = get_language_from_args(args);
+    let word_list = utils::read_first_n_words(args.top_words.unwrap_or(1000), language);
+    let stats = KeyStats::load_all().unwrap_or_default();
+
+    let reference = adaptive::create_words(&stats, &word_list, word_number);
+    let mut session = TypingSession::new(reference, SessionMode::Text);
+    cli::main::run_test(&mut session, None, get_pacer_from_args(args), String::new);
+    save_session(args, &session, TestType::Adaptive, "adaptive", language, TestConfig::default());
 }
 
 pub fn practice(args: &Cli) {
     let level = args.level.unwrap();
-    if level.is_none() || level.unwrap() < 1 || level.unwrap() > practice::TYPING_LEVELS.len() {
-        eprintln!("Please choose a level between 1 and {}.", practice::TYPING_LEVELS.len());
-        for i in 0..practice::TYPING_LEVELS.len() {
-            let results_path = format!("practice_results/level_{}.txt", i + 1);
-            if practice::check_if_completed(results_path.as_str()) {
-                println!("✔ Level {}: {}", i + 1, practice::TYPING_LEVELS[i].0);
+    let language = get_language_from_args(args);
+    let layout = get_layout_from_args(args);
+    let levels = practice::levels(&layout, language);
+    if level.is_none() || level.unwrap() < 1 || level.unwrap() > levels.len() {
+        eprintln!("Please choose a level between 1 and {}.", levels.len());
+        let progress = practice::PracticeProgress::load();
+        for i in 0..levels.len() {
+            if progress.is_completed(&practice::LevelKey::new(&layout, language, i)) {
+                println!("✔ Level {}: {}", i + 1, levels[i].0);
             } else {
-                println!("  Level {}: {}", i + 1, practice::TYPING_LEVELS[i].0);
+                println!("  Level {}: {}", i + 1, levels[i].0);
             }
         }
         return;
     }
     
     let curr_level= level.unwrap() - 1;
-    let chars = practice::TYPING_LEVELS[curr_level].1;
+    let chars = &levels[curr_level].1;
     
     let reference = practice::create_words(chars, args.word_number.unwrap_or(Some(50)).unwrap_or(50));
-    let mut is_correct: VecDeque<i32> = VecDeque::from(vec![0; reference.len()]);
-    let mut start_time: Option<Instant> = None;
-    let res = cli::main::type_loop(&reference, None, &mut start_time, Some(curr_level), &mut is_correct, "practice");
+    let mut session = TypingSession::new(reference, SessionMode::Practice);
+    let key = practice::LevelKey::new(&layout, language, curr_level);
+    let res = cli::main::run_test(&mut session, Some(key.clone()), None, String::new);
     if res == 1 {
         println!("Exiting practice mode.");
     }
+    if !args.no_save && session.is_finished() {
+        let stats = session.stats();
+        practice::save_results(stats.elapsed, stats.accuracy, stats.wpm, &key);
+    }
+    save_session(args, &session, TestType::Practice(curr_level + 1), "practice", language, TestConfig::default());
 }
\ No newline at end of file
@@ -1,6 +1,4 @@
-use core::time;
 use macroquad::prelude::*;
-use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
 use crate::color_scheme::ColorScheme;
@@ -9,8 +7,20 @@ use crate::ui::gui::main;
 use crate::ui::gui::popup::{PopupContent, PopupStates};
 use crate::{practice, utils};
 use crate::config::AppConfig;
-
-fn save_config(punctuation: bool, numbers: bool, time_mode: bool, word_mode: bool, quote: bool, test_time: f32, batch_size: usize, practice_mode: bool, wiki_mode: bool, language: Language, color_scheme: ColorScheme, word_number: usize, top_words: usize, selected_practice_level: Option<usize>) {
+use crate::leaderboard::TestType;
+use crate::pacer;
+use crate::adaptive;
+use crate::code_snippets::{self, CodeLanguage};
+use crate::text_library::{self, TextLibrary};
+use crate::normalization::Strictness;
+use crate::quotes::{self, QuoteLength};
+use crate::compose::Composer;
+use crate::keyboard_layout::KeyboardLayout;
+use crate::word_sampling::{self, SamplingMode, WordSampler};
+use crate::key_stats::KeyStats;
+use crate::typing_session::{SessionMode, TypingSession};
+
+fn save_config(punctuation: bool, numbers: bool, time_mode: bool, word_mode: bool, quote: bool, test_time: f32, batch_size: usize, practice_mode: bool, wiki_mode: bool, language: Language, color_scheme: ColorScheme, word_number: usize, top_words: usize, selected_practice_level: Option<usize>, pace_ghost: bool, pacer_wpm: u32, adaptive_mode: bool, keyboard_layout: &str, emulate_layout: bool, sampling_mode: SamplingMode, no_repeat_within: usize, code_mode: bool, code_language: Option<CodeLanguage>, skip_indent: bool, library_mode: bool, library_text: &Option<String>, chunk_size: usize, strictness: Strictness, half_accent_errors: bool, quote_length: QuoteLength) {
     let app_config = AppConfig {
         punctuation: punctuation,
         numbers: numbers,
@@ -26,6 +36,22 @@ fn save_config(punctuation: bool, numbers: bool, time_mode: bool, word_mode: boo
         color_scheme: color_scheme,
         word_number: word_number,
         top_words: top_words,
+        pace_ghost: pace_ghost,
+        pacer_wpm: pacer_wpm,
+        adaptive_mode: adaptive_mode,
+        keyboard_layout: keyboard_layout.to_string(),
+        emulate_layout: emulate_layout,
+        sampling_mode: sampling_mode,
+        no_repeat_within: no_repeat_within,
+        code_mode: code_mode,
+        code_language: code_language,
+        skip_indent: skip_indent,
+        library_mode: library_mode,
+        library_text: library_text.clone(),
+        chunk_size: chunk_size,
+        strictness: strictness,
+        half_accent_errors: half_accent_errors,
+        quote_length: quote_length,
     };
 
     let _ = app_config.save();
@@ -97,82 +123,119 @@ fn draw_toggle_button(
     (clicked, hovered, btn_width)
 }
 
-pub fn update_game_state(
-    reference: &str,
-    pressed_vec: &mut Vec<char>,
-    is_correct: &mut VecDeque<i32>,
-    pos1: &mut usize,
-    timer: &mut Duration,
-    start_time: &mut Instant,
-    game_started: &mut bool,
-    game_over: &mut bool,
+pub fn session_mode(
+    time_mode: bool,
+    word_mode: bool,
+    practice_mode: bool,
     test_time: f32,
+    word_number: usize,
+) -> SessionMode {
+    if time_mode {
+        SessionMode::Time(test_time)
+    } else if word_mode {
+        SessionMode::Words(word_number)
+    } else if practice_mode {
+        SessionMode::Practice
+    } else {
+        SessionMode::Text
+    }
+}
+
+pub fn test_type(
     time_mode: bool,
-    words_done: &mut usize,
-    errors_this_second: &mut f64,
-    practice_mode: &mut bool,
-    practice_menu: bool,
-    wiki_mode: bool,
+    word_mode: bool,
     quote: bool,
+    wiki_mode: bool,
+    adaptive_mode: bool,
+    code_mode: bool,
+    library_mode: bool,
+    test_time: f32,
     word_number: usize,
-) {
-    if !*game_started
-        && main::handle_input(
-            reference,
-            pressed_vec,
-            is_correct,
-            pos1,
-            words_done,
-            errors_this_second,
-            &mut false,
-            &mut vec![false; reference.chars().count()],
-            *practice_mode,
-            practice_menu,
-            *game_over,
-        )
-    {
-        *game_started = true;
-        *start_time = Instant::now();
+    selected_practice_level: Option<usize>,
+) -> TestType {
+    if time_mode {
+        TestType::Time(test_time as u32)
+    } else if word_mode {
+        TestType::Word(word_number)
+    } else if quote {
+        TestType::Quote
+    } else if wiki_mode {
+        TestType::Wiki
+    } else if adaptive_mode {
+        TestType::Adaptive
+    } else if code_mode {
+        TestType::Code
+    } else if library_mode {
+        TestType::Custom
+    } else {
+        TestType::Practice(selected_practice_level.unwrap_or(0) + 1)
     }
+}
+
+/// Practice text for the weakest keys of all saved tests
+pub fn adaptive_reference(language: Language) -> String {
+    let stats = KeyStats::load_all().unwrap_or_default();
+    adaptive::create_words(&stats, &utils::read_first_n_words(1000, language), adaptive::WORD_NUMBER)
+}
 
-    if *game_started && !*game_over {
-        *timer = start_time.elapsed();
-        if (timer.as_secs_f32() >= test_time && time_mode) || (*pos1 >= reference.chars().count() && (wiki_mode || quote)) || (*words_done >= word_number && !wiki_mode && !quote)  {
-            *game_over = true;
+/// Next quote of the language and length, `notice` is set to why there is none
+pub fn quote_reference(language: Language, length: QuoteLength, notice: &mut Option<String>) -> String {
+    match quotes::next_quote(language, length) {
+        Ok(reference) => {
+            *notice = None;
+            reference
+        }
+        Err(e) => {
+            *notice = Some(e.to_string());
+            String::new()
         }
     }
 }
 
-pub fn reset_game_state(
-    pressed_vec: &mut Vec<char>,
-    is_correct: &mut VecDeque<i32>,
-    pos1: &mut usize,
-    timer: &mut Duration,
-    start_time: &mut Instant,
-    game_started: &mut bool,
-    game_over: &mut bool,
-    speed_per_second: &mut Vec<f64>,
-    last_recorded_time: &mut Instant,
-    words_done: &mut usize,
-    errors_per_second: &mut Vec<f64>,
-    saved_results: &mut bool,
-    error_positions: &mut Vec<bool>,
+/// Random code snippet, `snippet_language` is set to its language for highlighting
+pub fn code_reference(code_language: Option<CodeLanguage>, snippet_language: &mut Option<CodeLanguage>) -> String {
+    let snippet = code_snippets::random_snippet(code_language);
+    *snippet_language = Some(snippet.language);
+    snippet.text
+}
+
+/// Next chunk of the library text in library mode, `chunk_end` is set to where it ends.
+/// Leaves library mode when the text is no longer in the library.
+pub fn library_reference(library_mode: &mut bool, library: &TextLibrary, library_text: &Option<String>, chunk_size: usize, chunk_end: &mut usize) -> Option<String> {
+    if !*library_mode {
+        return None;
+    }
+    let Some(chunk) = library_text.as_deref().and_then(|name| library.next_chunk(name, chunk_size)) else {
+        *library_mode = false;
+        return None;
+    };
+    *chunk_end = chunk.end;
+    Some(chunk.text)
+}
+
+pub fn update_game_state(
+    session: &mut TypingSession,
+    config_opened: &mut bool,
+    practice_menu: bool,
+    emulated: Option<&KeyboardLayout>,
+    composer: &mut Composer,
 ) {
-    *is_correct = VecDeque::from(vec![0; is_correct.len()]);
-    pressed_vec.clear();
-    *error_positions = vec![false; is_correct.len()];
-    *pos1 = 0;
-    *timer = Duration::new(0, 0);
-    *start_time = Instant::now();
-    *game_started = false;
-    *game_over = false;
-    *speed_per_second = vec![];
-    *errors_per_second = vec![];
-    *last_recorded_time = Instant::now();
-    *words_done = 0;
+    main::handle_input(session, config_opened, practice_menu, emulated, composer);
+    session.tick();
+}
+
+/// Starts a new test on `reference`, keeping the current mode.
+pub fn new_test(session: &mut TypingSession, reference: String, saved_results: &mut bool) {
+    *session = TypingSession::new(reference, session.mode);
     *saved_results = false;
 }
 
+/// Restarts the test on the same reference.
+pub fn reset_game_state(session: &mut TypingSession, saved_results: &mut bool) {
+    let reference = session.reference.clone();
+    new_test(session, reference, saved_results);
+}
+
 pub fn handle_settings_buttons(
     font: &Option<Font>,
     emoji_font: &Font,
@@ -182,21 +245,10 @@ pub fn handle_settings_buttons(
     quote: &mut bool,
     time_mode: &mut bool,
     word_mode: &mut bool,
-    pressed_vec: &mut Vec<char>,
-    is_correct: &mut VecDeque<i32>,
-    pos1: &mut usize,
-    timer: &mut time::Duration,
-    start_time: &mut Instant,
-    game_started: &mut bool,
-    game_over: &mut bool,
-    reference: &mut String,
+    session: &mut TypingSession,
     test_time: &mut f32,
     batch_size: &mut usize,
     start_x: f32,
-    speed_per_second: &mut Vec<f64>,
-    last_recorded_time: &mut Instant,
-    words_done: &mut usize,
-    errors_per_second: &mut Vec<f64>,
     font_size: u16,
     config_opened: &mut bool,
     selected_config: &mut String,
@@ -204,14 +256,33 @@ pub fn handle_settings_buttons(
     selected_practice_level: &mut Option<usize>,
     practice_mode: &mut bool,
     saved_results: &mut bool,
-    error_positions: &mut Vec<bool>,
     language: &mut Language,
     color_scheme: &mut crate::color_scheme::ColorScheme,
     wiki_mode: &mut bool,
+    adaptive_mode: &mut bool,
     menu_buttons_times: &mut std::collections::HashMap<String, Instant>,
     popup_states: &mut PopupStates,
     top_words: &mut usize,
     word_number: &mut usize,
+    pace_ghost: &mut bool,
+    pacer_wpm: &mut u32,
+    layout: &mut KeyboardLayout,
+    emulate_layout: &mut bool,
+    sampling_mode: &mut SamplingMode,
+    no_repeat_within: &mut usize,
+    code_mode: &mut bool,
+    code_language: &mut Option<CodeLanguage>,
+    snippet_language: &mut Option<CodeLanguage>,
+    skip_indent: &mut bool,
+    library_mode: &mut bool,
+    library: &mut TextLibrary,
+    library_text: &mut Option<String>,
+    library_chunk_end: &mut usize,
+    chunk_size: &mut usize,
+    strictness: &mut Strictness,
+    half_accent_errors: &mut bool,
+    quote_length: &mut QuoteLength,
+    quote_notice: &mut Option<String>,
 ) -> bool {
 
     let btn_y = screen_height() / 5.0;
@@ -238,7 +309,7 @@ pub fn handle_settings_buttons(
             "|",
             "|",
             divider,
-            !*quote && !*practice_mode && !*wiki_mode,
+            !*quote && !*practice_mode && !*wiki_mode && !*adaptive_mode && !*code_mode && !*library_mode,
         ),
         (
             "punctuation",
@@ -248,7 +319,7 @@ pub fn handle_settings_buttons(
                 "! punct"
             },
             *punctuation,
-            !*quote && !*practice_mode && !*wiki_mode,
+            !*quote && !*practice_mode && !*wiki_mode && !*adaptive_mode && !*code_mode && !*library_mode,
         ),
         (
             "numbers",
@@ -258,13 +329,16 @@ pub fn handle_settings_buttons(
                 "# num"
             },
             *numbers,
-            !*quote && !*practice_mode && !*wiki_mode,
+            !*quote && !*practice_mode && !*wiki_mode && !*adaptive_mode && !*code_mode && !*library_mode,
         ),
         ("|", "|", divider, true),
         ("time", "+ time", *time_mode, true),
         ("words", "+ words", *word_mode, true),
         ("quote", "quote", *quote, true),
         ("practice", "practice", *practice_mode, true),
+        ("adaptive", "adaptive", *adaptive_mode, true),
+        ("code", "code", *code_mode, true),
+        ("texts", "+ texts", *library_mode, true),
         (
             "wikipedia",
             if screen_width() > screen_height() && screen_width() > 1500.0 {
@@ -277,7 +351,7 @@ pub fn handle_settings_buttons(
         ),
     ];
 
-    let popup_opened = popup_states.language.visible || popup_states.color_scheme.visible || popup_states.time_selection.visible || popup_states.word_number_selection.visible || popup_states.settings.visible || popup_states.batch_size_selection.visible || popup_states.top_words_selection.visible;
+    let popup_opened = popup_states.language.visible || popup_states.color_scheme.visible || popup_states.time_selection.visible || popup_states.word_number_selection.visible || popup_states.settings.visible || popup_states.batch_size_selection.visible || popup_states.top_words_selection.visible || popup_states.texts.visible;
 
     if is_key_pressed(KeyCode::Up) {
         if !popup_opened {
@@ -350,25 +424,12 @@ pub fn handle_settings_buttons(
         {
        
         if popup_states.language.visible {
-            *language = match popup_states.language.selected {
-                0 => Language::English,
-                1 => Language::Indonesian,
-                2 => Language::Italian,
-                _ => Language::English,
-            };
+            *language = Language::all().get(popup_states.language.selected).copied().unwrap_or_default();
             popup_states.language.visible = false;
             popup_states.language.hide();
             if *word_mode || *time_mode {
-                if *time_mode {
-                    *reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), *batch_size);
-                } else if *word_mode {
-                    *reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), usize::min(*batch_size, *batch_size));
-                }
-                *is_correct = VecDeque::from(vec![0; reference.chars().count()]);
-                *error_positions = vec![false; reference.chars().count()];
-                pressed_vec.clear();
-                *pos1 = 0;
-                *words_done = 0;
+                let reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), *batch_size, &WordSampler::new(*sampling_mode, *no_repeat_within, *language));
+                new_test(session, reference, saved_results);
             }
             return false;
         } else if popup_states.color_scheme.visible {
@@ -397,10 +458,12 @@ pub fn handle_settings_buttons(
                 5 => 600.0,
                 _ => 30.0,
             };
-            *reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), *batch_size);
+            session.mode = SessionMode::Time(*test_time);
+            let reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), *batch_size, &WordSampler::new(*sampling_mode, *no_repeat_within, *language));
+            new_test(session, reference, saved_results);
             popup_states.time_selection.visible = false;
             popup_states.time_selection.hide();
-            save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level);
+            save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
             return false;
         } else if popup_states.word_number_selection.visible {
             *word_number = match popup_states.word_number_selection.selected {
@@ -411,10 +474,12 @@ pub fn handle_settings_buttons(
                 4 => 500,
                 _ => 50,
             };
-            *reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), usize::min(*batch_size, 100));
+            session.mode = SessionMode::Words(*word_number);
+            let reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), usize::min(*batch_size, 100), &WordSampler::new(*sampling_mode, *no_repeat_within, *language));
+            new_test(session, reference, saved_results);
             popup_states.word_number_selection.visible = false;
             popup_states.word_number_selection.hide();
-            save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level);
+            save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
             return false;
         } else if popup_states.batch_size_selection.visible {
             *batch_size = match popup_states.batch_size_selection.selected {
@@ -425,9 +490,11 @@ pub fn handle_settings_buttons(
                 _ => 50,
             };
             if *time_mode {
-                *reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), *batch_size);
+                let reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), *batch_size, &WordSampler::new(*sampling_mode, *no_repeat_within, *language));
+                new_test(session, reference, saved_results);
             } else if *word_mode {
-                *reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), usize::min(*batch_size, 100));
+                let reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), usize::min(*batch_size, 100), &WordSampler::new(*sampling_mode, *no_repeat_within, *language));
+                new_test(session, reference, saved_results);
             }
             popup_states.batch_size_selection.visible = false;
             popup_states.batch_size_selection.hide();
@@ -441,13 +508,35 @@ pub fn handle_settings_buttons(
                 _ => 500,
             };
             if *time_mode {
-                *reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), *batch_size);
+                let reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), *batch_size, &WordSampler::new(*sampling_mode, *no_repeat_within, *language));
+                new_test(session, reference, saved_results);
             } else if *word_mode {
-                *reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), usize::min(*batch_size, 100));
+                let reference = utils::get_reference(*punctuation, *numbers, &utils::read_first_n_words(*top_words, *language), usize::min(*batch_size, 100), &WordSampler::new(*sampling_mode, *no_repeat_within, *language));
+                new_test(session, reference, saved_results);
             }
             popup_states.top_words_selection.visible = false;
             popup_states.top_words_selection.hide();
             return false;
+        } else if popup_states.texts.visible {
+            if let Some(text) = library.texts.get(popup_states.texts.selected) {
+                *library_text = Some(text.name.clone());
+                *library_mode = true;
+                *time_mode = false;
+                *word_mode = false;
+                *quote = false;
+                *practice_mode = false;
+                *wiki_mode = false;
+                *adaptive_mode = false;
+                *code_mode = false;
+                session.mode = SessionMode::Text;
+                if let Some(reference) = library_reference(library_mode, library, library_text, *chunk_size, library_chunk_end) {
+                    new_test(session, reference, saved_results);
+                }
+            }
+            popup_states.texts.visible = false;
+            popup_states.texts.hide();
+            save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+            return false;
         } else if popup_states.settings.visible {
             if popup_states.settings.selected == 0 {
                 popup_states.color_scheme.visible = true;
@@ -455,15 +544,96 @@ pub fn handle_settings_buttons(
                 popup_states.color_scheme.selected = schemes.iter().position(|&s| s == *color_scheme).unwrap_or(0);
             } else if popup_states.settings.selected == 1 {
                 popup_states.language.visible = true;
-                popup_states.language.selected = match language {
-                    Language::English => 0,
-                    Language::Indonesian => 1,
-                    Language::Italian=> 2,
-                };
+                popup_states.language.selected = Language::all().iter().position(|l| l == language).unwrap_or(0);
             } else if popup_states.settings.selected == 2 {
                 popup_states.batch_size_selection.visible = true;
             } else if popup_states.settings.selected == 3 {
                 popup_states.top_words_selection.visible = true;
+            } else if popup_states.settings.selected == 4 {
+                *pace_ghost = !*pace_ghost;
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                return false;
+            } else if popup_states.settings.selected == 5 {
+                *pacer_wpm = pacer::next_preset(*pacer_wpm);
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                return false;
+            } else if popup_states.settings.selected == 6 {
+                *layout = KeyboardLayout::find(&KeyboardLayout::next_name(&layout.name));
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                if *practice_mode {
+                    let chars = &practice::level_keys(layout, *language, selected_practice_level.unwrap_or(0));
+                    new_test(session, practice::create_words(chars, *batch_size), saved_results);
+                }
+                return false;
+            } else if popup_states.settings.selected == 7 {
+                *emulate_layout = !*emulate_layout;
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                return false;
+            } else if popup_states.settings.selected == 8 {
+                *sampling_mode = sampling_mode.next();
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                return false;
+            } else if popup_states.settings.selected == 9 {
+                *no_repeat_within = word_sampling::next_no_repeat(*no_repeat_within);
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                return false;
+            } else if popup_states.settings.selected == 10 {
+                *code_language = CodeLanguage::next(*code_language);
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                if *code_mode {
+                    new_test(session, code_reference(*code_language, snippet_language), saved_results);
+                }
+                return false;
+            } else if popup_states.settings.selected == 11 {
+                *skip_indent = !*skip_indent;
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                return false;
+            } else if popup_states.settings.selected == 12 {
+                *chunk_size = text_library::next_chunk_size(*chunk_size);
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                if let Some(reference) = library_reference(library_mode, library, library_text, *chunk_size, library_chunk_end) {
+                    new_test(session, reference, saved_results);
+                }
+                return false;
+            } else if popup_states.settings.selected == 13 {
+                *strictness = strictness.next();
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                return false;
+            } else if popup_states.settings.selected == 14 {
+                *half_accent_errors = !*half_accent_errors;
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                return false;
+            } else if popup_states.settings.selected == 15 {
+                *quote_length = quote_length.next();
+                popup_states.settings.visible = false;
+                popup_states.settings.hide();
+                save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
+                if *quote {
+                    new_test(session, quote_reference(*language, *quote_length, quote_notice), saved_results);
+                }
+                return false;
             }
         }
 
@@ -479,33 +649,45 @@ pub fn handle_settings_buttons(
                 selected_practice_level,
                 practice_mode,
                 language,
+                layout,
                 popup_states,
                 wiki_mode,
+                adaptive_mode,
+                code_mode,
+                library_mode,
             );
         }
 
-        save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level);
+        save_config(*punctuation, *numbers, *time_mode, *word_mode, *quote, *test_time, *batch_size, *practice_mode, *wiki_mode, *language, *color_scheme, *word_number, *top_words, *selected_practice_level, *pace_ghost, *pacer_wpm, *adaptive_mode, &layout.name, *emulate_layout, *sampling_mode, *no_repeat_within, *code_mode, *code_language, *skip_indent, *library_mode, library_text, *chunk_size, *strictness, *half_accent_errors, *quote_length);
 
+        let mut reference = None;
         if !popup_states.settings.visible {
-            if *quote {
-                *reference = utils::get_random_quote();
+            if *selected_config == "texts" {
+                open_texts_popup(popup_states, library, library_text);
+            } else if *quote {
+                reference = Some(quote_reference(*language, *quote_length, quote_notice));
             } else if *practice_mode {
-                *reference = practice::create_words(
-                    practice::TYPING_LEVELS[selected_practice_level.unwrap_or(0)].1,
+                reference = Some(practice::create_words(
+                    &practice::level_keys(layout, *language, selected_practice_level.unwrap_or(0)),
                     *batch_size,
-                );
+                ));
                 if let Some(time) = menu_buttons_times.get_mut("practice") {
                     *time = Instant::now();
                 }
+            } else if *adaptive_mode {
+                reference = Some(adaptive_reference(*language));
+            } else if *code_mode {
+                reference = Some(code_reference(*code_language, snippet_language));
+            } else if *library_mode {
+                reference = library_reference(library_mode, library, library_text, *chunk_size, library_chunk_end);
             } else if *wiki_mode {
-                *reference = utils::get_wiki_summary();
+                reference = Some(utils::get_wiki_summary(*language));
                 if let Some(time) = menu_buttons_times.get_mut("wiki") {
                     *time = Instant::now();
                 }
             } else if *selected_config != "language" && *selected_config != "theme" {
                 let updated_word_list = utils::read_first_n_words(500, *language);
-                *reference =
-                    utils::get_reference(*punctuation, *numbers, &updated_word_list, *batch_size);
+                reference = Some(utils::get_reference(*punctuation, *numbers, &updated_word_list, *batch_size, &WordSampler::new(*sampling_mode, *no_repeat_within, *language)));
             }
             if *selected_config == "time" {
                 if menu_buttons_times.get("time").map_or(true, |&t| t.elapsed() <= Duration::from_millis(500)) {
@@ -546,24 +728,11 @@ pub fn handle_settings_buttons(
 
         }
 
-        if selected_config != "language" && selected_config != "theme" {
-            *is_correct = VecDeque::from(vec![0; reference.len()]);
-            *error_positions = vec![false; is_correct.len()];
-            reset_game_state(
-                pressed_vec,
-                is_correct,
-                pos1,
-                timer,
-                start_time,
-                game_started,
-                game_over,
-                speed_per_second,
-                last_recorded_time,
-                words_done,
-                errors_per_second,
-                saved_results,
-                &mut vec![false; reference.chars().count()],
-            );
+        session.mode = session_mode(*time_mode, *word_mode, *practice_mode, *test_time, *word_number);
+        if let Some(reference) = reference {
+            new_test(session, reference, saved_results);
+        } else if selected_config != "language" && selected_config != "theme" {
+            reset_game_state(session, saved_results);
         }
     }
 
@@ -592,7 +761,9 @@ pub fn handle_settings_buttons(
             any_button_hovered = true;
         }
 
-        if clicked && *label != "|" && *label != "language" {
+        if clicked && *label == "texts" {
+            open_texts_popup(popup_states, library, library_text);
+        } else if clicked && *label != "|" && *label != "language" {
             update_config(
                 label,
                 punctuation,
@@ -604,55 +775,50 @@ pub fn handle_settings_buttons(
                 selected_practice_level,
                 practice_mode,
                 language,
+                layout,
                 popup_states,
                 wiki_mode,
+                adaptive_mode,
+                code_mode,
+                library_mode,
             );
+            session.mode = session_mode(*time_mode, *word_mode, *practice_mode, *test_time, *word_number);
             if *quote {
-                *reference = utils::get_random_quote();
-                *is_correct = VecDeque::from(vec![0; reference.chars().count()]);
-                *error_positions = vec![false; is_correct.len()];
                 *punctuation = false;
                 *numbers = false;
-                reset_game_state(
-                    pressed_vec,
-                    is_correct,
-                    pos1,
-                    timer,
-                    start_time,
-                    game_started,
-                    game_over,
-                    speed_per_second,
-                    last_recorded_time,
-                    words_done,
-                    errors_per_second,
-                    saved_results,
-                    error_positions,
-                );
+                new_test(session, quote_reference(*language, *quote_length, quote_notice), saved_results);
             } else if *practice_menu {
                 *practice_menu = true;
+            } else if *adaptive_mode {
+                new_test(session, adaptive_reference(*language), saved_results);
+            } else if *code_mode {
+                new_test(session, code_reference(*code_language, snippet_language), saved_results);
+            } else if let Some(reference) = library_reference(library_mode, library, library_text, *chunk_size, library_chunk_end) {
+                new_test(session, reference, saved_results);
             } else {
                 let updated_word_list = utils::read_first_n_words(500, *language);
-                *reference = utils::get_reference(*punctuation, *numbers, &updated_word_list, *batch_size);
-                *is_correct = VecDeque::from(vec![0; reference.chars().count()]);
-                *error_positions = vec![false; is_correct.len()];
-                reset_game_state(
-                    pressed_vec,
-                    is_correct,
-                    pos1,
-                    timer,
-                    start_time,
-                    game_started,
-                    game_over,
-                    speed_per_second,
-                    last_recorded_time,
-                    words_done,
-                    errors_per_second,
-                    saved_results,
-                    error_positions,
-                );
+                let reference = utils::get_reference(*punctuation, *numbers, &updated_word_list, *batch_size, &WordSampler::new(*sampling_mode, *no_repeat_within, *language));
+                new_test(session, reference, saved_results);
             }
         }
     }
+    if *emulate_layout {
+        // Not a button, only shows which layout the keys are remapped to
+        let label = format!("⌨ {}", layout.name.to_lowercase());
+        draw_toggle_button(
+            start_x + total_width,
+            btn_y,
+            btn_padding,
+            &label,
+            font,
+            emoji_font.clone(),
+            false,
+            true,
+            font_size,
+            false,
+            color_scheme,
+        );
+    }
     if popup_states.language.visible {
         popup_states.language.draw(font, color_scheme, PopupContent::Language);
     } else if popup_states.color_scheme.visible {
@@ -664,7 +830,9 @@ pub fn handle_settings_buttons(
     } else if popup_states.batch_size_selection.visible {
         popup_states.batch_size_selection.draw(font, color_scheme, PopupContent::BatchSizeSelection);
     } else if popup_states.top_words_selection.visible {
-        popup_states.top_words_selection.draw(font, color_scheme, PopupContent::TopWordsSelection);
+        popup_states.top_words_selection.draw(font, color_scheme, PopupContent::TopWordsSelection(language.word_count()));
+    } else if popup_states.texts.visible {
+        popup_states.texts.draw(font, color_scheme, PopupContent::Texts(library.summaries()));
     } else if popup_states.settings.visible {
         popup_states.settings.draw(font, color_scheme, PopupContent::Settings);
     }
@@ -672,6 +840,16 @@ pub fn handle_settings_buttons(
     any_button_hovered
 }
 
+/// Reloads the library and shows it with the current text selected
+fn open_texts_popup(popup_states: &mut PopupStates, library: &mut TextLibrary, library_text: &Option<String>) {
+    *library = TextLibrary::load();
+    popup_states.texts.visible = true;
+    popup_states.texts.selected = library_text
+        .as_ref()
+        .and_then(|name| library.names().iter().position(|n| n == name))
+        .unwrap_or(0);
+}
+
 fn update_config(
     label: &str,
     punctuation: &mut bool,
@@ -683,8 +861,12 @@ fn update_config(
     selected_practice_level: &mut Option<usize>,
     practice_mode: &mut bool,
     language: &mut Language,
+    layout: &KeyboardLayout,
     popup_states: &mut PopupStates,
     wiki_mode: &mut bool,
+    adaptive_mode: &mut bool,
+    code_mode: &mut bool,
+    library_mode: &mut bool,
 ) {
     match label {
         "punctuation" => {
@@ -695,6 +877,9 @@ fn update_config(
         }
         "time" => {
             *time_mode = true;
+            *adaptive_mode = false;
+            *code_mode = false;
+            *library_mode = false;
             *word_mode = false;
             *quote = false;
             *practice_mode = false;
@@ -702,6 +887,9 @@ fn update_config(
         }
         "words" => {
             *word_mode = true;
+            *adaptive_mode = false;
+            *code_mode = false;
+            *library_mode = false;
             *time_mode = false;
             *quote = false;
             *practice_mode = false;
@@ -709,6 +897,9 @@ fn update_config(
         }
         "quote" => {
             *quote = true;
+            *adaptive_mode = false;
+            *code_mode = false;
+            *library_mode = false;
             *punctuation = false;
             *numbers = false;
             *time_mode = false;
@@ -723,7 +914,7 @@ fn update_config(
             *time_mode = false;
             *word_mode = false;
             *practice_menu = true;
-            *selected_practice_level = Some(practice::get_first_not_done());
+            *selected_practice_level = Some(practice::get_first_not_done(layout, *language));
         }
         "wikipedia" => {
             *wiki_mode = true;
@@ -731,6 +922,33 @@ fn update_config(
             *word_mode = false;
             *practice_mode = false;
             *quote = false;
+            *adaptive_mode = false;
+            *code_mode = false;
+            *library_mode = false;
+        }
+        "adaptive" => {
+            *adaptive_mode = true;
+            *punctuation = false;
+            *numbers = false;
+            *wiki_mode = false;
+            *time_mode = false;
+            *word_mode = false;
+            *practice_mode = false;
+            *quote = false;
+            *code_mode = false;
+            *library_mode = false;
+        }
+        "code" => {
+            *code_mode = true;
+            *library_mode = false;
+            *adaptive_mode = false;
+            *punctuation = false;
+            *numbers = false;
+            *wiki_mode = false;
+            *time_mode = false;
+            *word_mode = false;
+            *practice_mode = false;
+            *quote = false;
         }
         "english" => {
             *language = Language::English;
@@ -0,0 +1,129 @@
+use macroquad::prelude::*;
+
+use crate::color_scheme::ColorScheme;
+use crate::custom_colors::MyColor;
+use crate::key_stats::{HeatmapData, HeatmapMetric, KeyStats};
+use crate::keyboard_layout::KeyboardLayout;
+use crate::typing_session::TypingSession;
+
+/// Handles the heatmap keys, returns true when the view should close
+pub fn handle_heatmap_keys(heatmap: &mut HeatmapData) -> bool {
+    if is_key_pressed(KeyCode::A) {
+        heatmap.all_tests = !heatmap.all_tests;
+    }
+    if is_key_pressed(KeyCode::M) {
+        heatmap.metric = heatmap.metric.next();
+    }
+    is_key_pressed(KeyCode::H)
+}
+
+pub fn draw_heatmap(
+    heatmap: &HeatmapData,
+    session: &TypingSession,
+    screen_width: f32,
+    screen_height: f32,
+    font: Option<&Font>,
+    color_scheme: &ColorScheme,
+    layout: &KeyboardLayout,
+) {
+    let current;
+    let (stats, scope) = if heatmap.all_tests {
+        (&heatmap.all, "all tests")
+    } else {
+        current = KeyStats::from_session(session);
+        (&current, "this test")
+    };
+    let metric = heatmap.metric;
+
+    let key_size = f32::min(screen_width / 16.0, screen_height / 9.0);
+    let gap = key_size / 10.0;
+    let keyboard_width = (layout.rows[1].chars().count() as f32 + 1.0) * (key_size + gap);
+    let start_x = (screen_width - keyboard_width) / 2.0;
+    let start_y = screen_height / 2.0 - 2.5 * (key_size + gap);
+
+    let title = format!("Key heatmap - {} - {}", scope, metric);
+    let title_size = (key_size / 2.5) as u16;
+    let title_dims = measure_text(&title, font, title_size, 1.0);
+    draw_text_ex(
+        &title,
+        (screen_width - title_dims.width) / 2.0,
+        start_y - key_size,
+        TextParams {
+            font,
+            font_size: title_size,
+            color: color_scheme.main_color(),
+            ..Default::default()
+        },
+    );
+
+    let bg: MyColor = color_scheme.bg_color();
+    let cold: MyColor = color_scheme.correct_color();
+    let hot: MyColor = color_scheme.incorrect_color();
+    let key_font = (key_size / 3.0) as u16;
+    let label_font = (key_size / 4.5) as u16;
+
+    for (i, row) in layout.rows.iter().enumerate() {
+        let y = start_y + i as f32 * (key_size + gap);
+        let mut x = start_x + i as f32 * key_size / 2.0;
+        for key in row.chars() {
+            let width = if key == ' ' { 6.0 * key_size } else { key_size };
+            let pressed = stats.heat(key, metric).is_some();
+            let shade: Color = stats.color(key, metric, bg, cold, hot).into();
+            draw_rectangle(x, y, width, key_size, shade);
+            draw_rectangle_lines(x, y, width, key_size, 1.0, color_scheme.border_color());
+
+            let text_color = if pressed { color_scheme.bg_color() } else { color_scheme.ref_color() };
+            let name = if key == ' ' { "space".to_string() } else { key.to_string() };
+            draw_text_ex(
+                &name,
+                x + gap,
+                y + key_size * 0.4,
+                TextParams {
+                    font,
+                    font_size: key_font,
+                    color: text_color,
+                    ..Default::default()
+                },
+            );
+            draw_text_ex(
+                &stats.label(key, metric),
+                x + gap,
+                y + key_size * 0.85,
+                TextParams {
+                    font,
+                    font_size: label_font,
+                    color: text_color,
+                    ..Default::default()
+                },
+            );
+            x += width + gap;
+        }
+    }
+
+    let worst: Vec<String> = stats
+        .worst_keys(metric, 5)
+        .into_iter()
+        .map(|key| format!("{} {}", key, stats.label(key, metric)))
+        .collect();
+    let summary = if stats.is_empty() {
+        "No keys pressed yet".to_string()
+    } else {
+        let title = match metric {
+            HeatmapMetric::Errors => "Most missed",
+            HeatmapMetric::Latency => "Slowest",
+        };
+        format!("{}: {}", title, worst.join(", "))
+    };
+    let summary_dims = measure_text(&summary, font, title_size, 1.0);
+    draw_text_ex(
+        &summary,
+        (screen_width - summary_dims.width) / 2.0,
+        start_y + layout.rows.len() as f32 * (key_size + gap) + key_size,
+        TextParams {
+            font,
+            font_size: title_size,
+            color: color_scheme.ref_color(),
+            ..Default::default()
+        },
+    );
+}
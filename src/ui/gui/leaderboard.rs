@@ -0,0 +1,154 @@
+use macroquad::prelude::*;
+
+use crate::color_scheme::ColorScheme;
+use crate::leaderboard::LeaderboardData;
+
+/// Handles the leaderboard keys, returns true when the view should close
+pub fn handle_leaderboard_keys(leaderboard: &mut LeaderboardData) -> bool {
+    if is_key_pressed(KeyCode::Up) {
+        leaderboard.selected = leaderboard.selected.saturating_sub(1);
+    }
+    if is_key_pressed(KeyCode::Down) && leaderboard.selected + 1 < leaderboard.entries.len() {
+        leaderboard.selected += 1;
+    }
+
+    let query = &mut leaderboard.query;
+    let before = query.clone();
+    if is_key_pressed(KeyCode::T) {
+        query.next_test_type(&leaderboard.history);
+    }
+    if is_key_pressed(KeyCode::N) {
+        query.next_language(&leaderboard.history);
+    }
+    if is_key_pressed(KeyCode::P) {
+        query.next_punctuation();
+    }
+    if is_key_pressed(KeyCode::D) {
+        query.next_digits();
+    }
+    if is_key_pressed(KeyCode::R) {
+        query.dates = query.dates.next();
+    }
+    if is_key_pressed(KeyCode::S) {
+        query.sort = query.sort.next();
+    }
+    if is_key_pressed(KeyCode::B) {
+        query.personal_bests = !query.personal_bests;
+    }
+    if is_key_pressed(KeyCode::C) {
+        query.clear_filters();
+    }
+    if *query != before {
+        leaderboard.selected = 0;
+        leaderboard.refresh();
+    }
+    is_key_pressed(KeyCode::L)
+}
+
+pub fn draw_leaderboard(
+    leaderboard: &LeaderboardData,
+    screen_width: f32,
+    screen_height: f32,
+    font: Option<&Font>,
+    color_scheme: &ColorScheme,
+) {
+    let font_size = f32::min(screen_width / 60.0, screen_height / 30.0).max(12.0);
+    let line_height = font_size * 1.5;
+    let start_x = screen_width / 12.0;
+    let mut y = screen_height / 8.0;
+
+    let text = |text: &str, x: f32, y: f32, color: Color| {
+        draw_text_ex(
+            text,
+            x,
+            y,
+            TextParams {
+                font,
+                font_size: font_size as u16,
+                color,
+                ..Default::default()
+            },
+        );
+    };
+
+    text("Local Leaderboard", start_x, y, color_scheme.main_color());
+    y += line_height;
+    text(&leaderboard.query.describe(), start_x, y, color_scheme.ref_color());
+    y += line_height * 1.5;
+
+    if leaderboard.entries.is_empty() {
+        let message = if leaderboard.history.is_empty() {
+            "No typing test results yet. Complete a test to see your scores here!"
+        } else {
+            "No results match the filters. Press C to clear them."
+        };
+        text(message, start_x, y, color_scheme.ref_color());
+    } else {
+        let columns = ["Rank", "Date", "Type", "WPM", "Acc%", "Time", "Words", "Lang"];
+        let widths = [5.0, 12.0, 9.0, 7.0, 7.0, 7.0, 7.0, 5.0];
+        let column_x: Vec<f32> = widths
+            .iter()
+            .scan(start_x, |x, width| {
+                let column = *x;
+                *x += width * font_size * 0.7;
+                Some(column)
+            })
+            .collect();
+        for (title, x) in columns.iter().zip(&column_x) {
+            text(title, *x, y, color_scheme.main_color());
+        }
+        y += line_height;
+
+        let visible_rows = ((screen_height * 0.85 - y) / line_height).max(1.0) as usize;
+        let scroll = leaderboard
+            .selected
+            .saturating_sub(visible_rows / 2)
+            .min(leaderboard.entries.len().saturating_sub(visible_rows));
+        for (i, entry) in leaderboard.entries.iter().enumerate().skip(scroll).take(visible_rows) {
+            let mut test_type = entry.test_type.to_string();
+            if entry.punctuation {
+                test_type.push('!');
+            }
+            if entry.digits {
+                test_type.push('#');
+            }
+            let cells = [
+                (i + 1).to_string(),
+                entry.timestamp.chars().take(10).collect(),
+                test_type,
+                format!("{:.1}", entry.wpm),
+                format!("{:.1}", entry.accuracy),
+                format!("{:.0}s", entry.test_duration),
+                entry.word_count.to_string(),
+                entry.language.short_name().to_string(),
+            ];
+            let color: Color = if i == leaderboard.selected {
+                draw_rectangle(
+                    start_x - font_size / 2.0,
+                    y - font_size,
+                    column_x[column_x.len() - 1] + 5.0 * font_size * 0.7 - start_x + font_size,
+                    line_height,
+                    color_scheme.dimmer_main(),
+                );
+                color_scheme.bg_color()
+            } else {
+                color_scheme.text_color()
+            };
+            for (cell, x) in cells.iter().zip(&column_x) {
+                text(cell, *x, y, color);
+            }
+            y += line_height;
+        }
+
+        if let Some(quote) = leaderboard.entries.get(leaderboard.selected).and_then(|entry| entry.quote.as_ref()) {
+            text(quote, start_x, y + line_height / 2.0, color_scheme.ref_color());
+        }
+    }
+
+    text(
+        "↑/↓ - navigate, T - type, N - language, P - punctuation, D - digits, R - dates, C - clear filters, S - sort, B - personal bests, L - close",
+        start_x,
+        screen_height * 0.93,
+        color_scheme.border_color(),
+    );
+}
@@ -2,18 +2,32 @@ use core::time;
 use macroquad::prelude::*;
 use miniquad::CursorIcon;
 use miniquad::window::set_mouse_cursor;
-use std::collections::VecDeque;
 use std::thread;
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
 
 use crate::color_scheme::ColorScheme;
+use crate::code_snippets::{self, CodeLanguage};
 use crate::config::AppConfig;
-use crate::practice::{self, TYPING_LEVELS};
+use crate::text_library::TextLibrary;
+use crate::compose::Composer;
+use crate::finger_stats::FingerStats;
+use crate::keyboard_layout::KeyboardLayout;
+use crate::word_sampling::WordSampler;
+use crate::practice;
+use crate::quotes;
 use crate::ui::gui::config::{self, reset_game_state};
 use crate::ui::gui::popup::{PopupStates, PopupState};
 use crate::ui::gui::practice as gui_practice;
 use crate::ui::gui::results;
+use crate::ghost::Ghost;
+use crate::pacer::Pacer;
+use crate::key_stats::HeatmapData;
+use crate::leaderboard::{self, LeaderboardData, LeaderboardEntry, TestConfig};
+use crate::ui::gui::heatmap;
+use crate::ui::gui::leaderboard as gui_leaderboard;
+use crate::keystroke_log;
+use crate::typing_session::TypingSession;
 use crate::utils;
 
 
@@ -30,7 +44,7 @@ const DEJAVU: &[u8] = include_bytes!(concat!(
     "/assets/fonts/DejaVuSansCondensed.ttf"
 ));
 
-pub async fn gui_main_async() {
+pub async fn gui_main_async(chosen_ghost: Option<Ghost>) {
     let mut app_config = AppConfig::load();
 
     let mut punctuation = app_config.punctuation;
@@ -41,8 +55,30 @@ pub async fn gui_main_async() {
     let mut language = app_config.language;
     let mut practice_mode = app_config.practice_mode;
     let mut wiki_mode = app_config.wiki_mode;
+    let mut adaptive_mode = app_config.adaptive_mode;
+    let mut pace_ghost = app_config.pace_ghost || chosen_ghost.is_some();
+    let mut pacer_wpm = app_config.pacer_wpm;
+    let mut layout = KeyboardLayout::find(&app_config.keyboard_layout);
+    let mut emulate_layout = app_config.emulate_layout;
+    let mut sampling_mode = app_config.sampling_mode;
+    let mut no_repeat_within = app_config.no_repeat_within;
+    let mut code_mode = app_config.code_mode;
+    let mut code_language = app_config.code_language;
+    let mut snippet_language: Option<CodeLanguage> = None;
+    let mut skip_indent = app_config.skip_indent;
+    let mut library_mode = app_config.library_mode;
+    let mut library = TextLibrary::load();
+    let mut library_text = app_config.library_text.clone();
+    let mut library_chunk_end = 0;
+    let mut chunk_size = app_config.chunk_size;
+    let mut strictness = app_config.strictness;
+    let mut half_accent_errors = app_config.half_accent_errors;
+    let mut quote_length = app_config.quote_length;
+    let mut quote_favorite = false;
+    let mut composer = Composer::default();
+    let library_chunk = config::library_reference(&mut library_mode, &library, &library_text, chunk_size, &mut library_chunk_end);
 
-    if !time_mode && !word_mode && !quote && !practice_mode && !wiki_mode {
+    if !time_mode && !word_mode && !quote && !practice_mode && !wiki_mode && !adaptive_mode && !code_mode && !library_mode {
         time_mode = true;
     }
 
@@ -56,40 +92,43 @@ pub async fn gui_main_async() {
     let mut word_number = app_config.word_number;
 
     let updated_word_list = utils::read_first_n_words(500, language);
+    // Kept for the whole run so no repeats carry over from one batch to the next
+    let mut word_sampler = WordSampler::new(sampling_mode, no_repeat_within, language);
     let mut selected_practice_level: Option<usize> = Some(app_config.selected_level);
 
-    let mut reference = if practice_mode {
+    // Why quote mode has nothing to type, shown in place of the reference
+    let mut quote_notice = None;
+    let reference = if practice_mode {
         practice::create_words(
-            TYPING_LEVELS[selected_practice_level.unwrap_or(0)].1,
+            &practice::level_keys(&layout, language, selected_practice_level.unwrap_or(0)),
             50,
         )
     } else if quote {
-        utils::get_random_quote()
+        config::quote_reference(language, quote_length, &mut quote_notice)
     } else if wiki_mode {
-        utils::get_wiki_summary()
+        utils::get_wiki_summary(language)
+    } else if adaptive_mode {
+        config::adaptive_reference(language)
+    } else if code_mode {
+        config::code_reference(code_language, &mut snippet_language)
+    } else if let Some(chunk) = library_chunk {
+        chunk
     } else {
-        utils::get_reference(punctuation, false, &updated_word_list, batch_size)
+        utils::get_reference(punctuation, false, &updated_word_list, batch_size, &word_sampler)
     };
 
-    let mut pressed_vec: Vec<char> = vec![];
-    let mut is_correct: VecDeque<i32> = VecDeque::from(vec![0; reference.len()]);
-    let mut pos1: usize = 0;
-    let mut timer = time::Duration::from_secs(0);
-    let mut start_time: Instant = Instant::now();
     let mut test_time = app_config.test_time as f32;
-    let mut game_started = false;
-    let mut game_over = false;
-
-    let mut speed_per_second: Vec<f64> = vec![];
-    let mut errors_per_second: Vec<f64> = vec![];
-    let mut errors_this_second: f64 = 0.0;
-    let mut char_number = 0;
-    let mut error_positions = vec![false; reference.len()];
+    let mut session = TypingSession::new(
+        reference,
+        config::session_mode(time_mode, word_mode, practice_mode, test_time, word_number),
+    );
 
     let mut lines: Vec<String>;
-    let mut last_recorded_time = Instant::now();
-
-    let mut words_done = 0;
+    let mut ghost: Option<Ghost> = None;
+    let mut pacer: Option<Pacer> = None;
+    let mut heatmap_data = HeatmapData::default();
+    let mut leaderboard_data = LeaderboardData::default();
+    let mut ghost_start: Option<Instant> = None;
 
     let mut config_opened = false;
     let mut selected_config: String = "time".to_string();
@@ -97,6 +136,7 @@ pub async fn gui_main_async() {
     let mut practice_menu = false;
     let mut scroll_offset: f32 = 0.0;
     let mut saved_results = false;
+    let mut practice_prev_best = 0.0;
 
     let mut color_scheme = app_config.color_scheme;
 
@@ -119,9 +159,10 @@ pub async fn gui_main_async() {
         settings: PopupState { visible: false, selected: 0 },
         batch_size_selection: PopupState { visible: false, selected: 0 },
         top_words_selection: PopupState { visible: false, selected: 0 },
+        texts: PopupState { visible: false, selected: 0 },
     };
 
-    let words: Vec<&str> = reference.split_whitespace().collect();
+    let words: Vec<&str> = session.reference.split_whitespace().collect();
     let average_word_length: f64 = if !words.is_empty() {
         words.iter().map(|w| w.len()).sum::<usize>() as f64 / words.len() as f64 + 1.0
     } else {
@@ -153,28 +194,59 @@ pub async fn gui_main_async() {
             .width
             .floor();
         lines = create_lines(
-            &mut reference,
+            &session.reference,
             Some(font.clone()),
             font_size,
             max_width,
             quote,
             word_mode,
             wiki_mode,
+            code_mode || library_mode,
         );
+        session.truncate_reference(lines.iter().map(|line| line.chars().count()).sum());
 
         let mut chars_in_line: Vec<i32> = vec![];
         for line in &lines {
             chars_in_line.push(line.chars().count() as i32);
         }
 
-        if !game_started {
-            last_recorded_time = Instant::now();
-            timer = time::Duration::from_secs(0);
-            start_time = Instant::now();
-            pos1 = 0;
+        if !session.is_started() {
+            session.mode = config::session_mode(time_mode, word_mode, practice_mode, test_time, word_number);
+            session.auto_indent = skip_indent;
+            session.set_strictness(strictness);
+            session.half_accent_errors = half_accent_errors;
+        }
+
+        if session.start_time != ghost_start {
+            // Picked once per test, when typing starts
+            ghost_start = session.start_time;
+            ghost = if pace_ghost && (time_mode || word_mode) && !practice_mode {
+                chosen_ghost.clone().or_else(|| {
+                    Ghost::personal_best(&config::test_type(
+                        time_mode,
+                        word_mode,
+                        quote,
+                        wiki_mode,
+                        adaptive_mode,
+                        code_mode,
+                        library_mode,
+                        test_time,
+                        word_number,
+                        selected_practice_level,
+                    ))
+                })
+            } else {
+                None
+            };
+            pacer = if practice_mode { None } else { Pacer::new(pacer_wpm) };
+            heatmap_data.open = false;
+            leaderboard_data.open = false;
+        }
+        if let Some(pacer) = pacer.as_mut().filter(|_| !session.is_finished()) {
+            pacer.update(&session);
         }
 
-        if !game_over && !practice_menu {
+        if !session.is_finished() && !practice_menu {
             let total_height = lines.len() as f32 * font_size * 1.2;
             let start_y = screen_height() / 2.0 - total_height / 2.0 + font_size;
             let start_x = screen_width() / 2.0 - max_width / 2.0 + 20.0;
@@ -182,15 +254,28 @@ pub async fn gui_main_async() {
 
             draw_reference_text(
                 &lines,
-                &pressed_vec,
-                &is_correct,
+                &session,
                 Some(&font.clone()),
                 font_size,
                 start_x,
                 start_y,
                 popup_states.language.visible,
+                snippet_language.filter(|_| code_mode),
                 &color_scheme,
             );
+            if let Some(notice) = quote_notice.as_deref().filter(|_| quote) {
+                draw_text_ex(
+                    notice,
+                    start_x,
+                    start_y,
+                    TextParams {
+                        font: Some(&font),
+                        font_size: font_size as u16,
+                        color: color_scheme.ref_color(),
+                        ..Default::default()
+                    },
+                );
+            }
 
             let any_button_hovered = config::handle_settings_buttons(
                 &Option::Some(font.clone()),
@@ -201,21 +286,10 @@ pub async fn gui_main_async() {
                 &mut quote,
                 &mut time_mode,
                 &mut word_mode,
-                &mut pressed_vec,
-                &mut is_correct,
-                &mut pos1,
-                &mut timer,
-                &mut start_time,
-                &mut game_started,
-                &mut game_over,
-                &mut reference,
+                &mut session,
                 &mut test_time,
                 &mut batch_size,
                 start_x,
-                &mut speed_per_second,
-                &mut last_recorded_time,
-                &mut words_done,
-                &mut errors_per_second,
                 u16::max((font_size / 1.2) as u16, 15),
                 &mut config_opened,
                 &mut selected_config,
@@ -223,14 +297,33 @@ pub async fn gui_main_async() {
                 &mut selected_practice_level,
                 &mut practice_mode,
                 &mut saved_results,
-                &mut error_positions,
                 &mut language,
                 &mut color_scheme,
                 &mut wiki_mode,
+                &mut adaptive_mode,
                 &mut menu_buttons_times,
                 &mut popup_states,
                 &mut top_words,
                 &mut word_number,
+                &mut pace_ghost,
+                &mut pacer_wpm,
+                &mut layout,
+                &mut emulate_layout,
+                &mut sampling_mode,
+                &mut no_repeat_within,
+                &mut code_mode,
+                &mut code_language,
+                &mut snippet_language,
+                &mut skip_indent,
+                &mut library_mode,
+                &mut library,
+                &mut library_text,
+                &mut library_chunk_end,
+                &mut chunk_size,
+                &mut strictness,
+                &mut half_accent_errors,
+                &mut quote_length,
+                &mut quote_notice,
            );
 
             set_mouse_cursor(if any_button_hovered {
@@ -239,51 +332,7 @@ pub async fn gui_main_async() {
                 CursorIcon::Default
             });
 
-            config::update_game_state(
-                &reference,
-                &mut pressed_vec,
-                &mut is_correct,
-                &mut pos1,
-                &mut timer,
-                &mut start_time,
-                &mut game_started,
-                &mut game_over,
-                test_time,
-                time_mode,
-                &mut words_done,
-                &mut errors_this_second,
-                &mut practice_mode,
-                practice_menu,
-                wiki_mode,
-                quote,
-                word_number,
-            );
-
-            if !game_started
-                && handle_input(
-                    &reference,
-                    &mut pressed_vec,
-                    &mut is_correct,
-                    &mut pos1,
-                    &mut words_done,
-                    &mut errors_this_second,
-                    &mut config_opened,
-                    &mut error_positions,
-                    practice_mode,
-                    practice_menu,
-                    game_over,
-                )
-            {
-                game_started = true;
-            }
-
-            if (game_started || words_done == word_number) && !game_over {
-                timer = start_time.elapsed();
-                if (timer.as_secs_f32() >= test_time && time_mode) || (pos1 >= reference.chars().count() && (wiki_mode || quote)) || (words_done >= word_number && !wiki_mode && !quote)
-                {
-                    game_over = true;
-                }
-            }
+            config::update_game_state(&mut session, &mut config_opened, practice_menu, emulate_layout.then_some(&layout), &mut composer);
 
             write_title(
                 Some(title_font.clone()),
@@ -297,29 +346,13 @@ pub async fn gui_main_async() {
                 color_scheme,
             );
 
-            if !game_over {
-                handle_input(
-                    &reference,
-                    &mut pressed_vec,
-                    &mut is_correct,
-                    &mut pos1,
-                    &mut words_done,
-                    &mut errors_this_second,
-                    &mut config_opened,
-                    &mut error_positions,
-                    practice_mode,
-                    practice_menu,
-                    game_over,
-                );
-            }
-
             if time_mode {
                 draw_timer(
                     Some(&font.clone()),
                     font_size,
                     start_x,
                     start_y,
-                    timer,
+                    session.elapsed(),
                     test_time,
                     &color_scheme,
                 );
@@ -329,37 +362,27 @@ pub async fn gui_main_async() {
                     font_size,
                     start_x,
                     start_y,
-                    &mut words_done,
+                    session.words_done,
                     word_number,
                     &color_scheme,
                 );
-            } else if practice_mode {
-                draw_word_count(
-                    Some(&font.clone()),
-                    font_size,
-                    start_x,
-                    start_y,
-                    &mut words_done,
-                    50,
-                    &color_scheme,
-                );
-            } else if quote || wiki_mode{
+            } else if practice_mode || quote || wiki_mode || adaptive_mode || code_mode || library_mode {
                 draw_word_count(
                     Some(&font.clone()),
                     font_size,
                     start_x,
                     start_y,
-                    &mut words_done,
-                    reference.split_whitespace().count(),
+                    session.words_done,
+                    session.reference.split_whitespace().count(),
                     &color_scheme,
                 );
             }
 
-            let (calc_pos_x, calc_pos_y) = calc_pos(&chars_in_line, pos1);
-            if !game_started {
+            let (calc_pos_x, calc_pos_y) = calc_pos(&chars_in_line, session.pos);
+            if !session.is_started() {
                 let blink_interval = 0.5;
                 let show_cursor = ((get_time() / blink_interval) as i32) % 2 == 0;
-                if show_cursor && !game_over && !config_opened {
+                if show_cursor && !config_opened {
                     draw_cursor(
                         calc_pos_x,
                         calc_pos_y,
@@ -381,37 +404,16 @@ pub async fn gui_main_async() {
                     &color_scheme,
                 );
             }
-
-            let now = Instant::now();
-            let time_since_last = now.duration_since(last_recorded_time);
-
-            if time_since_last >= Duration::from_secs(1) {
-                let total_typed = pressed_vec.len();
-                let chars_in_this_second = total_typed.saturating_sub(char_number);
-                let cpm = chars_in_this_second as f64 * 60.0;
-
-                speed_per_second.push(cpm);
-
-                char_number = total_typed;
-
-                errors_per_second.push(errors_this_second);
-                errors_this_second = 0.0;
-                last_recorded_time += Duration::from_secs(1);
+            if let Some(ghost_pos) = ghost.as_ref().and_then(|ghost| ghost.cursor(&session)) {
+                let (ghost_x, ghost_y) = calc_pos(&chars_in_line, ghost_pos);
+                draw_ghost_cursor(ghost_x, ghost_y, start_x, start_y, line_h, char_w, &color_scheme);
             }
-        } else if game_over {
-            handle_input(
-                &reference,
-                &mut pressed_vec,
-                &mut is_correct,
-                &mut pos1,
-                &mut words_done,
-                &mut errors_this_second,
-                &mut config_opened,
-                &mut error_positions,
-                practice_mode,
-                practice_menu,
-                game_over,
-            );
+            if let Some(pacer_pos) = pacer.as_ref().and_then(|pacer| pacer.cursor(&session)) {
+                let (pacer_x, pacer_y) = calc_pos(&chars_in_line, pacer_pos);
+                draw_pacer_cursor(pacer_x, pacer_y, start_x, start_y, line_h, char_w, &color_scheme);
+            }
+        } else if session.is_finished() {
+            handle_input(&mut session, &mut config_opened, practice_menu, emulate_layout.then_some(&layout), &mut composer);
             let mode = if time_mode {
                 "time".to_string()
             } else if word_mode {
@@ -420,6 +422,12 @@ pub async fn gui_main_async() {
                 "quote".to_string()
             } else if wiki_mode {
                 "wiki".to_string()
+            } else if adaptive_mode {
+                "adaptive".to_string()
+            } else if code_mode {
+                "code".to_string()
+            } else if library_mode {
+                "library".to_string()
             } else {
                 "practice".to_string()
             };
@@ -430,23 +438,102 @@ pub async fn gui_main_async() {
                 selected_practice_level
             };
 
-            results::write_results(
-                &is_correct,
-                screen_width(),
-                screen_height(),
-                Some(&title_font.clone()),
-                timer.as_secs_f32(),
-                &speed_per_second,
-                average_word_length,
-                &mode,
-                punctuation,
-                numbers,
-                &errors_per_second,
-                &reference,
-                practice_level,
-                &mut saved_results,
-                &color_scheme,
-            );
+            if !saved_results {
+                let test_type = config::test_type(
+                    time_mode,
+                    word_mode,
+                    quote,
+                    wiki_mode,
+                    adaptive_mode,
+                    code_mode,
+                    library_mode,
+                    test_time,
+                    word_number,
+                    selected_practice_level,
+                );
+                let entry = LeaderboardEntry::from_session(
+                    &session,
+                    test_type,
+                    &mode,
+                    language,
+                    TestConfig { punctuation, digits: numbers, top_words, batch_size },
+                );
+                if session.is_started() {
+                    if let Err(e) = leaderboard::save_entry(&entry) {
+                        eprintln!("Failed to save leaderboard entry: {:?}", e);
+                    }
+                }
+                keystroke_log::save_session_at(&session, entry.test_type, &mode, language, &entry.timestamp);
+                quote_favorite = quote && quotes::last_is_favorite();
+                practice_prev_best = practice_level
+                    .map_or(0.0, |level| practice::get_prev_best_wpm(&practice::LevelKey::new(&layout, language, level)));
+                if let Some(name) = library_text.as_ref().filter(|_| library_mode) {
+                    let stats = session.stats();
+                    let _ = library.record(name, library_chunk_end, stats.wpm, stats.accuracy, session.words_done);
+                }
+            }
+            let summaries: Vec<String> = ghost
+                .as_ref()
+                .map(|ghost| ghost.summary(&session))
+                .into_iter()
+                .chain(pacer.as_ref().map(|pacer| pacer.summary()))
+                .collect();
+
+            if heatmap_data.open {
+                if heatmap::handle_heatmap_keys(&mut heatmap_data) {
+                    heatmap_data.open = false;
+                }
+                heatmap::draw_heatmap(
+                    &heatmap_data,
+                    &session,
+                    screen_width(),
+                    screen_height(),
+                    Some(&font.clone()),
+                    &color_scheme,
+                    &layout,
+                );
+            } else if leaderboard_data.open {
+                if gui_leaderboard::handle_leaderboard_keys(&mut leaderboard_data) {
+                    leaderboard_data.open = false;
+                }
+                gui_leaderboard::draw_leaderboard(
+                    &leaderboard_data,
+                    screen_width(),
+                    screen_height(),
+                    Some(&font.clone()),
+                    &color_scheme,
+                );
+            } else {
+                if is_key_pressed(KeyCode::H) {
+                    heatmap_data.toggle();
+                }
+                if is_key_pressed(KeyCode::L) {
+                    leaderboard_data.toggle();
+                }
+                if quote && is_key_pressed(KeyCode::S) {
+                    if let Some(favorite) = quotes::toggle_last_favorite() {
+                        quote_favorite = favorite;
+                    }
+                }
+                results::write_results(
+                    &session,
+                    screen_width(),
+                    screen_height(),
+                    Some(&title_font.clone()),
+                    average_word_length,
+                    &mode,
+                    punctuation,
+                    numbers,
+                    practice_level,
+                    practice_prev_best,
+                    &layout,
+                    language,
+                    &summaries,
+                    &FingerStats::from_events(&session.log, &layout),
+                    &mut saved_results,
+                    &color_scheme,
+                );
+            }
         } else if practice_menu {
             let level = gui_practice::display_practice_menu(
                 Some(font.clone()),
@@ -455,85 +542,36 @@ pub async fn gui_main_async() {
                 &mut selected_practice_level,
                 &mut practice_menu,
                 &mut time_mode,
-                &mut pressed_vec,
-                &mut is_correct,
-                &mut pos1,
-                &mut timer,
-                &mut start_time,
-                &mut game_started,
-                &mut game_over,
-                &mut speed_per_second,
-                &mut last_recorded_time,
-                &mut words_done,
-                &mut errors_per_second,
+                &mut session,
                 &mut saved_results,
-                &mut error_positions,
                 &color_scheme,
+                &layout,
+                language,
             );
-            if level.is_some() {
-                config::reset_game_state(
-                    &mut pressed_vec,
-                    &mut is_correct,
-                    &mut pos1,
-                    &mut timer,
-                    &mut start_time,
-                    &mut game_started,
-                    &mut game_over,
-                    &mut speed_per_second,
-                    &mut last_recorded_time,
-                    &mut words_done,
-                    &mut errors_per_second,
-                    &mut saved_results,
-                    &mut error_positions,
-                );
-                reference = practice::create_words(TYPING_LEVELS[level.unwrap()].1, 50);
-                is_correct = VecDeque::from(vec![0; reference.len()]);
-                error_positions = vec![false; is_correct.len()];
+            if let Some(level) = level {
                 practice_mode = true;
                 wiki_mode = false;
+                adaptive_mode = false;
+                code_mode = false;
+                library_mode = false;
                 time_mode = false;
                 word_mode = false;
                 quote = false;
                 practice_menu = false;
                 config_opened = false;
+                session = TypingSession::new(
+                    practice::create_words(&practice::level_keys(&layout, language, level), 50),
+                    config::session_mode(time_mode, word_mode, practice_mode, test_time, word_number),
+                );
+                saved_results = false;
             }
         }
         if is_key_pressed(KeyCode::Escape) {
             if practice_menu {
                 practice_menu = false;
                 practice_mode = false;
-                game_over = false;
 
-                reset_game_state(
-                    &mut pressed_vec,
-                    &mut is_correct,
-                    &mut pos1,
-                    &mut timer,
-                    &mut start_time,
-                    &mut game_started,
-                    &mut game_over,
-                    &mut speed_per_second,
-                    &mut last_recorded_time,
-                    &mut words_done,
-                    &mut errors_per_second,
-                    &mut saved_results,
-                    &mut error_positions,
-                );
-                reset_game_state(
-                    &mut pressed_vec,
-                    &mut is_correct,
-                    &mut pos1,
-                    &mut timer,
-                    &mut start_time,
-                    &mut game_started,
-                    &mut game_over,
-                    &mut speed_per_second,
-                    &mut last_recorded_time,
-                    &mut words_done,
-                    &mut errors_per_second,
-                    &mut saved_results,
-                    &mut error_positions,
-                );
+                reset_game_state(&mut session, &mut saved_results);
             } else if popup_states.language.visible {
                 popup_states.language.visible = false;
                 config_opened = false;
@@ -552,6 +590,9 @@ pub async fn gui_main_async() {
             } else if popup_states.top_words_selection.visible {
                 popup_states.top_words_selection.visible = false;
                 config_opened = false;
+            } else if popup_states.texts.visible {
+                popup_states.texts.visible = false;
+                config_opened = false;
             } else if popup_states.settings.visible {
                 popup_states.settings.visible = false;
                 config_opened = false;
@@ -564,6 +605,7 @@ pub async fn gui_main_async() {
                     quote: quote,
                     practice_mode: practice_mode,
                     wiki_mode: wiki_mode,
+                    adaptive_mode: adaptive_mode,
                     batch_size: batch_size,
                     test_time: test_time,
                     selected_level: selected_practice_level.unwrap_or(0),
@@ -571,6 +613,21 @@ pub async fn gui_main_async() {
                     color_scheme: color_scheme,
                     word_number: word_number,
                     top_words: top_words,
+                    pace_ghost: pace_ghost,
+                    pacer_wpm: pacer_wpm,
+                    keyboard_layout: layout.name.clone(),
+                    emulate_layout: emulate_layout,
+                    sampling_mode: sampling_mode,
+                    no_repeat_within: no_repeat_within,
+                    code_mode: code_mode,
+                    code_language: code_language,
+                    skip_indent: skip_indent,
+                    library_mode: library_mode,
+                    library_text: library_text.clone(),
+                    chunk_size: chunk_size,
+                    strictness: strictness,
+                    half_accent_errors: half_accent_errors,
+                    quote_length: quote_length,
                 };
                 let _ = app_config.save();
 
@@ -579,51 +636,39 @@ pub async fn gui_main_async() {
         }
 
         if is_key_down(KeyCode::Tab) && is_key_down(KeyCode::Enter) && !practice_menu {
-            config::reset_game_state(
-                &mut pressed_vec,
-                &mut is_correct,
-                &mut pos1,
-                &mut timer,
-                &mut start_time,
-                &mut game_started,
-                &mut game_over,
-                &mut speed_per_second,
-                &mut last_recorded_time,
-                &mut words_done,
-                &mut errors_per_second,
-                &mut saved_results,
-                &mut error_positions,
-            );
-            if practice_mode {
-                reference = practice::create_words(
-                    TYPING_LEVELS[selected_practice_level.unwrap_or(0)].1,
+            let reference = if practice_mode {
+                practice::create_words(
+                    &practice::level_keys(&layout, language, selected_practice_level.unwrap_or(0)),
                     50,
-                );
+                )
             } else if quote {
-                reference = utils::get_random_quote();
+                config::quote_reference(language, quote_length, &mut quote_notice)
             } else if wiki_mode {
-                reference = utils::get_wiki_summary();
+                utils::get_wiki_summary(language)
+            } else if adaptive_mode {
+                config::adaptive_reference(language)
+            } else if code_mode {
+                config::code_reference(code_language, &mut snippet_language)
+            } else if let Some(chunk) = config::library_reference(&mut library_mode, &library, &library_text, chunk_size, &mut library_chunk_end) {
+                chunk
             } else {
                 let updated_word_list = utils::read_first_n_words(500, language);
-                reference =
-                    utils::get_reference(punctuation, false, &updated_word_list, batch_size);
-            }
-            is_correct = VecDeque::from(vec![0; reference.len()]);
-            error_positions = vec![false; is_correct.len()];
+                word_sampler.update(sampling_mode, no_repeat_within, language);
+                utils::get_reference(punctuation, false, &updated_word_list, batch_size, &word_sampler)
+            };
+            config::new_test(&mut session, reference, &mut saved_results);
             thread::sleep(time::Duration::from_millis(80));
         }
 
-        if pos1 >= reference.chars().count() && (time_mode || word_mode) && !game_over {
-            words_done += 1;
-            reference = utils::get_reference(
+        if session.needs_batch() {
+            word_sampler.update(sampling_mode, no_repeat_within, language);
+            session.next_batch(utils::get_reference(
                 punctuation,
                 numbers,
                 &utils::read_first_n_words(500, language),
                 batch_size,
-            );
-            is_correct = VecDeque::from(vec![0; reference.len()]);
-            error_positions = vec![false; is_correct.len()];
-            pos1 = 0;
+                &word_sampler,
+            ));
         }
 
         draw_shortcut_info(
@@ -633,8 +678,9 @@ pub async fn gui_main_async() {
             screen_height() - screen_height() / 7.5,
             emoji_font.clone(),
             practice_menu,
-            game_over,
+            session.is_finished(),
             practice_mode,
+            quote.then_some(quote_favorite),
             &color_scheme,
         );
         next_frame().await;
@@ -683,6 +729,7 @@ fn draw_shortcut_info(
     practice_menu: bool,
     game_over: bool,
     practice_mode: bool,
+    quote_favorite: Option<bool>,
     color_scheme: &ColorScheme,
 ) {
     let mut x = if practice_menu { 200.0 } else { x };
@@ -709,7 +756,17 @@ fn draw_shortcut_info(
         ]
     } else if game_over {
         x /= 2.0;
-        vec!["Tab + Enter - reset"]
+        let mut lines = vec![
+            "Tab + Enter - reset",
+            "H - key heatmap (A - this test / all tests, M - error rate / time to press)",
+            "L - local leaderboard",
+        ];
+        match quote_favorite {
+            Some(true) => lines.push("S - remove quote from favorites"),
+            Some(false) => lines.push("S - add quote to favorites"),
+            None => {}
+        }
+        lines
     } else {
         vec![
             "↑ or ↓ to navigate to config, ← → to change settings, ↵ - apply config (or click)",
@@ -790,60 +847,19 @@ fn draw_shortcut_info(
 }
 
 pub fn create_lines(
-    reference: &mut String,
+    reference: &str,
     font: Option<Font>,
     font_size: f32,
     max_width: f32,
     quote: bool,
     word_mode: bool,
     wiki_mode: bool,
+    code_mode: bool,
 ) -> Vec<String> {
-    let mut lines = Vec::new();
-    let mut current_line = String::new();
-    let mut no_words = 0;
-    let words: Vec<&str> = reference.split_whitespace().collect();
-    for word in words.iter() {
-        let test_line = if current_line.is_empty() {
-            word.to_string()
-        } else {
-            format!("{} {}", current_line, word)
-        };
-        let dims = measure_text(&test_line, font.as_ref(), font_size as u16, 1.0);
-        if dims.width > max_width && !current_line.is_empty() {
-            current_line += " ";
-            lines.push(current_line.clone());
-            if lines.len() >= 5 && !quote && !word_mode && !wiki_mode {
-                if no_words < words.len() {
-                    let char_indices = reference.char_indices();
-                    let mut end_idx = 0;
-                    let mut word_count = 0;
-                    for (idx, c) in char_indices {
-                        if c.is_whitespace() {
-                            word_count += 1;
-                            if word_count == no_words {
-                                end_idx = idx;
-                                break;
-                            }
-                        }
-                    }
-                    if end_idx == 0 {
-                        end_idx = reference.len();
-                    }
-                    reference.truncate(end_idx);
-                }
-                return lines;
-            }
-            current_line = word.to_string();
-        } else {
-            current_line = test_line;
-        }
-        no_words += 1;
-        if lines.len() >= 5 && !quote && !word_mode && !wiki_mode {
-            break;
-        }
-    }
-    if !current_line.is_empty() {
-        lines.push(current_line);
+    let char_w = measure_text("G", font.as_ref(), font_size as u16, 1.0).width.floor();
+    let mut lines = utils::wrap_reference(reference, (max_width / char_w) as usize);
+    if !quote && !word_mode && !wiki_mode && !code_mode {
+        lines.truncate(5);
     }
     if let Some(last) = lines.last_mut() {
         *last = last.trim_end().to_string();
@@ -852,21 +868,19 @@ pub fn create_lines(
 }
 
 pub fn handle_input(
-    reference: &str,
-    pressed_vec: &mut Vec<char>,
-    is_correct: &mut VecDeque<i32>,
-    pos1: &mut usize,
-    words_done: &mut usize,
-    errors_this_second: &mut f64,
+    session: &mut TypingSession,
     config_opened: &mut bool,
-    error_positions: &mut Vec<bool>,
-    practice_mode: bool,
     practice_menu: bool,
-    game_over: bool,
+    emulated: Option<&KeyboardLayout>,
+    composer: &mut Composer,
 ) -> bool {
-    let pressed = get_char_pressed();
-    if let Some(ch) = pressed {
-        if game_over {
+    let mut handled = false;
+    if let Some((ch, pressed)) = composer.flush_stale() {
+        session.type_char_at(ch, pressed);
+        handled = true;
+    }
+    while let Some(ch) = get_char_pressed() {
+        if session.is_finished() {
             return true;
         }
         if ch == '\u{f700}'
@@ -878,57 +892,38 @@ pub fn handle_input(
             || ch == '\u{1b}' // esc
         {
             // Arrow keys
-            return false;
+            continue;
         }
         *config_opened = false;
+        if (ch == '\n' || ch == '\r') && session.is_multiline() && !is_key_down(KeyCode::Tab) {
+            session.type_char('\n');
+            handled = true;
+            continue;
+        }
         if ch == '\t' || ch == '\n' || ch == '\r' {
-            return false;
+            continue;
         }
         if ch == '\u{8}' {
             // Backspace
-            if !pressed_vec.is_empty() && reference.chars().nth(*pos1) == Some(' ') {
-                *words_done -= 1;
-            }
-            pressed_vec.pop();
-            if *pos1 > 0 {
-                *pos1 -= 1;
+            if !composer.cancel() {
+                session.backspace();
             }
         } else if ch == '\u{7f}' {
             // Delete
-            return false;
+            continue;
         } else {
             if ch == 'q' && practice_menu {
-                return false;
-            }
-            let ref_char: Option<char> = reference.chars().nth(*pos1);
-            if is_correct.len() > *pos1
-                && ref_char == Some(ch)
-                && is_correct[*pos1] != -1
-                && is_correct[*pos1] != 1
-            {
-                is_correct[*pos1] = 2; // Correct
-            } else if is_correct.len() > *pos1
-                && ref_char == Some(ch)
-                && (is_correct[*pos1] == -1 || is_correct[*pos1] == 1)
-            {
-                is_correct[*pos1] = 1; // Corrected
-            } else if is_correct.len() > *pos1 {
-                is_correct[*pos1] = -1; // Incorrect
-                error_positions[*pos1] = true;
-                *errors_this_second += 1.0;
+                continue;
             }
-            if practice_mode && is_correct.len() > *pos1 && is_correct[*pos1] == -1 {
-                return true;
-            }
-            *pos1 += 1;
-            pressed_vec.push(ch);
-            if reference.chars().nth(*pos1) == Some(' ') {
-                *words_done += 1;
+            let ch = emulated.map_or(ch, |layout| layout.from_qwerty(ch));
+            let expected = session.ref_chars.get(session.pos).copied();
+            for (ch, pressed) in composer.feed(ch, expected, session.strictness) {
+                session.type_char_at(ch, pressed);
             }
         }
-        return true;
+        handled = true;
     }
-    false
+    handled
 }
 
 fn draw_timer(
@@ -959,7 +954,7 @@ fn draw_word_count(
     font_size: f32,
     start_x: f32,
     start_y: f32,
-    words_done: &mut usize,
+    words_done: usize,
     total_words: usize,
     color_scheme: &ColorScheme,
 ) {
@@ -979,27 +974,38 @@ fn draw_word_count(
 
 fn draw_reference_text(
     lines: &[String],
-    pressed_vec: &[char],
-    is_correct: &VecDeque<i32>,
+    session: &TypingSession,
     font: Option<&Font>,
     font_size: f32,
     start_x: f32,
     start_y: f32,
     _lang_popup_open: bool,
+    syntax: Option<CodeLanguage>,
     color_scheme: &ColorScheme,
 ) {
     let mut pos = 0;
     let mut pos_y = 0.0;
+    let kinds = syntax.map(|language| code_snippets::highlight(&session.reference, language));
+    let matching_bracket = kinds
+        .as_ref()
+        .and_then(|kinds| code_snippets::matching_bracket(&session.ref_chars, kinds, session.pos));
 
     for line in lines.iter() {
         let mut pos_x = 0;
         for char in line.chars() {
-            let mut curr_char = char;
-            let color = if pos + 1 > pressed_vec.len() || is_correct[pos] == 0 {
-                color_scheme.ref_color()
-            } else if is_correct.get(pos).is_some() && is_correct[pos] == 2 {
+            // Line breaks have to be typed, so they are shown
+            let mut curr_char = if char == '\n' { '¶' } else { char };
+            let state = session.is_correct.get(pos).copied().unwrap_or(0);
+            let color = if matching_bracket == Some(pos) {
+                color_scheme.main_color()
+            } else if pos >= session.pos || state == 0 {
+                match kinds.as_ref().and_then(|kinds| kinds.get(pos)) {
+                    Some(kind) => color_scheme.syntax_color(*kind),
+                    None => color_scheme.ref_color(),
+                }
+            } else if state == 2 {
                 color_scheme.text_color()
-            } else if is_correct.get(pos).is_some() && is_correct[pos] == 1 {
+            } else if state == 1 {
                 if char == ' ' {
                     curr_char = '_';
                 }
@@ -1052,6 +1058,49 @@ fn draw_cursor(
     );
 }
 
+fn draw_ghost_cursor(
+    cursor_x: usize,
+    cursor_y: usize,
+    start_x: f32,
+    start_y: f32,
+    line_h: f32,
+    char_w: f32,
+    color_scheme: &ColorScheme,
+) {
+    let cursor_x = start_x + cursor_x as f32 * char_w;
+    let cursor_y = start_y + cursor_y as f32 * line_h;
+    draw_line(
+        cursor_x,
+        cursor_y - line_h * 0.7,
+        cursor_x,
+        cursor_y + line_h * 0.3,
+        2.0,
+        color_scheme.ref_color(),
+    );
+}
+
+/// Underline below the char the pacer has reached
+fn draw_pacer_cursor(
+    cursor_x: usize,
+    cursor_y: usize,
+    start_x: f32,
+    start_y: f32,
+    line_h: f32,
+    char_w: f32,
+    color_scheme: &ColorScheme,
+) {
+    let cursor_x = start_x + cursor_x as f32 * char_w;
+    let cursor_y = start_y + cursor_y as f32 * line_h + line_h * 0.3;
+    draw_line(
+        cursor_x,
+        cursor_y,
+        cursor_x + char_w,
+        cursor_y,
+        2.0,
+        color_scheme.main_color(),
+    );
+}
+
 fn calc_pos(chars_in_line: &[i32], pos1: usize) -> (usize, usize) {
     let mut total = 0;
     for (i, &count) in chars_in_line.iter().enumerate() {
@@ -13,7 +13,10 @@ pub enum PopupContent {
     WordNumberSelection,
     Settings,
     BatchSizeSelection,
-    TopWordsSelection,
+    /// Carries the words of the language, top words beyond them are marked
+    TopWordsSelection(usize),
+    /// Summaries of the library texts
+    Texts(Vec<String>),
 }
 
 pub struct PopupState {
@@ -29,6 +32,7 @@ pub struct PopupStates {
     pub settings: PopupState,
     pub batch_size_selection: PopupState,
     pub top_words_selection: PopupState,
+    pub texts: PopupState,
 }
 
 pub trait PopupData {
@@ -46,19 +50,24 @@ impl PopupData for PopupContent {
             PopupContent::WordNumberSelection => "Select Number of Words",
             PopupContent::Settings => "Select Setting",
             PopupContent::BatchSizeSelection => "Select Batch Size",
-            PopupContent::TopWordsSelection => "Select Top Words",
+            PopupContent::TopWordsSelection(_) => "Select Top Words",
+            PopupContent::Texts(_) => "Select Text",
         }
     }
 
     fn items(&self) -> Vec<String> {
         match self {
-            PopupContent::Language => Language::all().iter().map(|x| x.to_string()).collect(),
+            PopupContent::Language => Language::all().iter().map(Language::label).collect(),
             PopupContent::ColorScheme => ColorScheme::all().iter().map(|x| x.name().to_string()).collect(),
             PopupContent::TimeSelection => TimeSelection::all().iter().map(|x| x.to_string()).collect(),
             PopupContent::WordNumberSelection => vec!["25".to_string(), "50".to_string(), "100".to_string(), "200".to_string(), "500".to_string()],
             PopupContent::Settings => Settings::all().iter().map(|x| x.to_string()).collect(),
             PopupContent::BatchSizeSelection => vec!["10".to_string(), "25".to_string(), "50".to_string(), "100".to_string(), "200".to_string()],
-            PopupContent::TopWordsSelection => vec!["100".to_string(), "200".to_string(), "500".to_string(), "1000".to_string()],
+            PopupContent::TopWordsSelection(available) => [100, 200, 500, 1000]
+                .iter()
+                .map(|n| if n > available { format!("{} (all {})", n, available) } else { n.to_string() })
+                .collect(),
+            PopupContent::Texts(summaries) => summaries.clone(),
         }
     }
 
@@ -70,7 +79,8 @@ impl PopupData for PopupContent {
             PopupContent::WordNumberSelection => &popup_states.word_number_selection.selected,
             PopupContent::Settings => &popup_states.settings.selected,
             PopupContent::BatchSizeSelection => &popup_states.batch_size_selection.selected,
-            PopupContent::TopWordsSelection => &popup_states.top_words_selection.selected,
+            PopupContent::TopWordsSelection(_) => &popup_states.top_words_selection.selected,
+            PopupContent::Texts(_) => &popup_states.texts.selected,
         }
     }
 }
@@ -104,8 +114,11 @@ impl PopupState {
         let screen_w = screen_width();
         let screen_h = screen_height();
 
+        let (title, items) = (content.title(), content.items());
+
         let popup_w = screen_w * 0.3;
-        let popup_h = f32::max(screen_h * 0.3, 450.0);
+        // Tall enough for every item, e.g. a language list with user packs
+        let popup_h = f32::max(screen_h * 0.3, 450.0).max(120.0 + items.len() as f32 * 30.0).min(screen_h);
         let x = (screen_w - popup_w) / 2.0;
         let y = (screen_h - popup_h) / 2.0;
 
@@ -120,8 +133,6 @@ impl PopupState {
         let font_size1 = if screen_h < 800.0 { 20 } else { 24 };
         let font_size2 = if screen_h < 800.0 { 16 } else { 20 };
 
-        let (title, items) = (content.title(), content.items());
-
         let title_size = measure_text(title, font.as_ref(), font_size1, 1.0);
         draw_text_ex(
             title,
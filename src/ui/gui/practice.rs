@@ -1,12 +1,14 @@
 use macroquad::prelude::*;
 use miniquad::CursorIcon;
 use miniquad::window::set_mouse_cursor;
-use std::collections::VecDeque;
 use std::thread;
 use std::time::{Duration, Instant};
 
 use crate::color_scheme::ColorScheme;
-use crate::practice::{TYPING_LEVELS, check_if_completed};
+use crate::keyboard_layout::KeyboardLayout;
+use crate::language::Language;
+use crate::practice::{self, LevelKey, PracticeProgress, TYPING_LEVELS};
+use crate::typing_session::TypingSession;
 use crate::ui::gui::config;
 use crate::utils;
 
@@ -17,21 +19,13 @@ pub fn display_practice_menu(
     selected_level: &mut Option<usize>,
     practice_menu: &mut bool,
     time_mode: &mut bool,
-    pressed_vec: &mut Vec<char>,
-    is_correct: &mut VecDeque<i32>,
-    pos1: &mut usize,
-    timer: &mut Duration,
-    start_time: &mut Instant,
-    game_started: &mut bool,
-    game_over: &mut bool,
-    speed_per_second: &mut Vec<f64>,
-    last_recorded_time: &mut Instant,
-    words_done: &mut usize,
-    errors_per_second: &mut Vec<f64>,
+    session: &mut TypingSession,
     saved_results: &mut bool,
-    error_positions: &mut Vec<bool>,
     color_scheme: &ColorScheme,
+    layout: &KeyboardLayout,
+    language: Language,
 ) -> Option<usize> {
+    let levels = practice::levels(layout, language);
     let font_size = if screen_width() > 3000.0 {
         20
     } else if screen_width() > 1900.0 {
@@ -51,10 +45,10 @@ pub fn display_practice_menu(
     let (_, y_scroll) = mouse_wheel();
     *scroll_offset -= y_scroll * 60.0;
 
-    let total_height = TYPING_LEVELS.len() as f32 * 60.0;
+    let total_height = levels.len() as f32 * 60.0;
     let visible_height = screen_height() - 100.0;
     let max_scroll = f32::max(
-        (TYPING_LEVELS.len() + 5) as f32 * (20.0 + font_size as f32) - screen_height(),
+        (levels.len() + 5) as f32 * (20.0 + font_size as f32) - screen_height(),
         0.0,
     );
 
@@ -81,11 +75,11 @@ pub fn display_practice_menu(
     }
 
     let start_index = 0;
-    let end_index = TYPING_LEVELS.len();
+    let end_index = levels.len();
 
     let mut any_hovered = false;
     let mut y: f32 = screen_height() / 10.0 + 2.0 * font_size as f32;
-    for (i, (level_name, _)) in TYPING_LEVELS
+    for (i, (level_name, _)) in levels
         .iter()
         .enumerate()
         .take(end_index)
@@ -110,14 +104,15 @@ pub fn display_practice_menu(
         y += 20.0 + font_size as f32;
     }
     let mut y: f32 = screen_height() / 10.0 + 2.0 * font_size as f32;
+    let progress = PracticeProgress::load();
 
-    for (i, _) in TYPING_LEVELS
+    for (i, _) in levels
         .iter()
         .enumerate()
         .take(end_index)
         .skip(start_index)
     {
-        let (level_name, _) = &TYPING_LEVELS[i];
+        let (level_name, _) = &levels[i];
         let mut text = format!("{}. {}", i + 1, level_name);
         if i + 1 < 10 {
             text = format!("{}.  {}", i + 1, level_name);
@@ -137,8 +132,7 @@ pub fn display_practice_menu(
             20.0 + font_size as f32,
         );
 
-        let results_path = format!("practice_results/level_{}.txt", i + 1);
-        let show_tick = check_if_completed(results_path.as_str());
+        let show_tick = progress.is_completed(&LevelKey::new(layout, language, i));
 
         let is_hovered = if any_hovered {
             button_rect.contains(vec2(mouse_pos.0, mouse_pos.1))
@@ -227,7 +221,7 @@ pub fn display_practice_menu(
                             *scroll_offset =
                                 (*scroll_offset + 20.0 + font_size as f32).min(max_scroll);
                             *selected_level = if let Some(level) = *selected_level {
-                                Some((level + 1).min(TYPING_LEVELS.len() - 1))
+                                Some((level + 1).min(levels.len() - 1))
                             } else {
                                 Some(0)
                             };
@@ -246,7 +240,7 @@ pub fn display_practice_menu(
                                 *scroll_offset =
                                     (*scroll_offset + 20.0 + font_size as f32).min(max_scroll);
                                 *selected_level = if let Some(level) = *selected_level {
-                                    Some((level + 1).min(TYPING_LEVELS.len() - 1))
+                                    Some((level + 1).min(levels.len() - 1))
                                 } else {
                                     Some(0)
                                 };
@@ -267,7 +261,7 @@ pub fn display_practice_menu(
                             *selected_level = if let Some(level) = *selected_level {
                                 Some((level as isize - 1).max(0) as usize)
                             } else {
-                                Some(TYPING_LEVELS.len() - 1)
+                                Some(levels.len() - 1)
                             };
                         } else if let (Some(start), Some(last)) =
                             (up_key_held_start, last_up_scroll)
@@ -286,7 +280,7 @@ pub fn display_practice_menu(
                                 *selected_level = if let Some(level) = *selected_level {
                                     Some((level as isize - 1).max(0) as usize)
                                 } else {
-                                    Some(TYPING_LEVELS.len() - 1)
+                                    Some(levels.len() - 1)
                                 };
                                 last_up_scroll = Some(now);
                             }
@@ -314,23 +308,8 @@ pub fn display_practice_menu(
         if *practice_menu {
             *practice_menu = false;
             *time_mode = true;
-            config::reset_game_state(
-                pressed_vec,
-                is_correct,
-                pos1,
-                timer,
-                start_time,
-                game_started,
-                game_over,
-                speed_per_second,
-                last_recorded_time,
-                words_done,
-                errors_per_second,
-                saved_results,
-                error_positions,
-            );
+            config::reset_game_state(session, saved_results);
             thread::sleep(Duration::from_millis(200));
-            pressed_vec.clear();
             return None;
         }
     } else {
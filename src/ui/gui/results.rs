@@ -2,57 +2,39 @@ use eframe::egui;
 use egui::{Area, pos2};
 use egui_plot::{Line, Plot};
 use macroquad::prelude::*;
-use std::collections::VecDeque;
 
 use crate::color_scheme::ColorScheme;
-use crate::practice;
-use crate::utils;
-
-fn calc_standard_deviation(values: &[f64], average_word_length: f64) -> f64 {
-    let wpm_values: Vec<f64> = values
-        .iter()
-        .map(|&cpm| cpm / average_word_length)
-        .collect();
-    let mean = wpm_values.iter().sum::<f64>() / wpm_values.len() as f64;
-    let variance =
-        wpm_values.iter().map(|&x| (x - mean).powi(2)).sum::<f64>() / wpm_values.len() as f64;
-    variance.sqrt()
-}
+use crate::finger_stats::FingerStats;
+use crate::keyboard_layout::KeyboardLayout;
+use crate::language::Language;
+use crate::practice::{self, LevelKey};
+use crate::typing_session::TypingSession;
 
 pub fn write_results(
-    is_correct: &VecDeque<i32>,
+    session: &TypingSession,
     screen_width: f32,
     screen_height: f32,
     font: Option<&Font>,
-    test_time: f32,
-    speed_per_second: &[f64],
     average_word_length: f64,
     mode: &str,
     punctuation: bool,
     numbers: bool,
-    errors_per_second: &Vec<f64>,
-    reference: &String,
     practice_level: Option<usize>,
+    practice_prev_best: f64,
+    layout: &KeyboardLayout,
+    language: Language,
+    summaries: &[String],
+    finger_stats: &FingerStats,
     saved_results: &mut bool,
     color_scheme: &ColorScheme,
 ) {
-    let (no_corrected_words, correct_words, all_words) =
-        utils::count_correct_words(reference, is_correct);
-
-    let correct_count = is_correct.iter().filter(|&&v| v == 1 || v == 2).count();
-    let all_pressed_count = is_correct.iter().filter(|&&v| v != 0).count();
-    let accuracy = if correct_count > 0 {
-        (correct_count as f64 / all_pressed_count as f64) * 100.0
-    } else {
-        0.0
-    };
-
-    let wpm = if practice_level.is_some() {
-        (no_corrected_words as f32 / (test_time / 60.0)).round()
-    } else {
-        (correct_words as f32 / (test_time / 60.0)).round()
-    };
-    let raw = all_words as f32 / (test_time / 60.0);
+    let stats = session.stats();
+    let test_time = stats.elapsed as f32;
+    let accuracy = stats.accuracy;
+    let wpm = (stats.wpm as f32).round();
+    let raw = stats.raw_wpm as f32;
+    let speed_per_second = &session.speed_per_second;
+    let errors_per_second = &session.errors_per_second;
 
     let chart_width = f32::min(
         f32::max(
@@ -89,7 +71,7 @@ pub fn write_results(
         (screen_height - chart_height) / 3.0
     };
 
-    let avg_wpm = write_wpm(
+    write_wpm(
         font,
         chart_x - 1.2 * text_size.width,
         wpm_y,
@@ -117,12 +99,10 @@ pub fn write_results(
         color_scheme,
     );
     write_consistency(
-        speed_per_second,
-        average_word_length,
+        stats.consistency,
         font,
         chart_x + padding + text2_width + padding,
         chart_y + chart_height + fontsize_4 as f32 * 2.0,
-        avg_wpm,
         fontsize_3,
         fontsize_4,
         color_scheme,
@@ -170,8 +150,18 @@ pub fn write_results(
         color_scheme,
     );
     egui_macroquad::draw();
+    draw_finger_panel(
+        finger_stats,
+        stats.wpm,
+        font,
+        chart_x + chart_width + fontsize_4 as f32,
+        chart_y + fontsize_4 as f32,
+        fontsize_4,
+        color_scheme,
+    );
 
-    if practice_level.is_some() {
+    if let Some(level) = practice_level {
+        let key = LevelKey::new(layout, language, level);
         let passed_text_font = if screen_width > 1900.0 && screen_height > 1000.0 {
             30
         } else if screen_height > 1000.0 {
@@ -180,12 +170,13 @@ pub fn write_results(
             19
         };
 
-        let practice_text = if wpm >= practice::WPM_MIN as f32 {
+        let practice_text = if practice::passes(stats.wpm, accuracy) {
             "Congratulations! You passed this level.".to_string()
         } else {
             format!(
-                "You need at least {} WPM to pass this level.",
-                practice::WPM_MIN
+                "You need at least {} WPM and {}% accuracy to pass this level.",
+                practice::WPM_MIN,
+                practice::ACCURACY_MIN
             )
         };
         let text_size = measure_text(&practice_text, font, passed_text_font, 1.0);
@@ -202,7 +193,7 @@ pub fn write_results(
                 ..Default::default()
             },
         );
-        if practice::get_prev_best_wpm(practice_level.unwrap() + 1) < wpm as f64 {
+        if practice_prev_best < stats.wpm {
             let new_highscore_text = "New highscore for this level!";
             let text_size = measure_text(new_highscore_text, font, 30, 1.0);
             draw_text_ex(
@@ -219,15 +210,79 @@ pub fn write_results(
             );
         }
         if !*saved_results {
-            *saved_results = true;
             practice::save_results(
                 test_time as f64,
                 accuracy,
-                wpm as f64,
-                practice_level.unwrap() + 1,
+                stats.wpm,
+                &key,
+            );
+        }
+    } else {
+        let font_size = if screen_width > 1900.0 && screen_height > 1000.0 {
+            30
+        } else if screen_height > 1000.0 {
+            22
+        } else {
+            19
+        };
+        for (i, summary) in summaries.iter().enumerate() {
+            let text_size = measure_text(summary, font, font_size, 1.0);
+            draw_text_ex(
+                summary,
+                (screen_width - text_size.width) / 2.0,
+                chart_y + chart_height + screen_height / 4.0 + i as f32 * font_size as f32 * 1.5,
+                TextParams {
+                    font,
+                    font_size,
+                    font_scale: 1.0,
+                    color: Color::from_rgba(255, 255, 255, 100),
+                    ..Default::default()
+                },
             );
         }
     }
+    *saved_results = true;
+}
+
+/// Per-finger wpm, error rate and same-finger slowdown, drawn right of the chart
+fn draw_finger_panel(
+    finger_stats: &FingerStats,
+    wpm: f64,
+    font: Option<&Font>,
+    x: f32,
+    y: f32,
+    font_size: u16,
+    color_scheme: &ColorScheme,
+) {
+    if finger_stats.is_empty() {
+        return;
+    }
+    let line_height = font_size as f32 * 1.5;
+    draw_text_ex(
+        "fingers",
+        x,
+        y,
+        TextParams {
+            font,
+            font_size,
+            color: color_scheme.main_color(),
+            ..Default::default()
+        },
+    );
+    let lines = finger_stats.lines(wpm);
+    for (i, line) in lines.iter().chain([&finger_stats.summary()]).enumerate() {
+        draw_text_ex(
+            line,
+            x,
+            y + (i + 1) as f32 * line_height,
+            TextParams {
+                font,
+                font_size,
+                color: if i < lines.len() { color_scheme.text_color() } else { color_scheme.ref_color() },
+                ..Default::default()
+            },
+        );
+    }
 }
 
 fn write_mode(
@@ -366,23 +421,14 @@ fn write_time(
 }
 
 fn write_consistency(
-    speed_per_second: &[f64],
-    average_word_length: f64,
+    consistency: f64,
     font: Option<&Font>,
     x: f32,
     y: f32,
-    avg_wpm: f32,
     fontsize_3: u16,
     fontsize_4: u16,
     color_scheme: &ColorScheme,
 ) {
-    let standard_deviation = calc_standard_deviation(speed_per_second, average_word_length);
-    let consistency = if avg_wpm > 0.0 {
-        (100.0 - (standard_deviation / avg_wpm as f64 * 100.0).round()).max(0.0)
-    } else {
-        0.0
-    };
-
     let consistency_text = format!("{consistency}%");
     draw_text_ex(
         "consistency",
@@ -416,7 +462,7 @@ fn write_wpm(
     fontsize_1: u16,
     fontsize_2: u16,
     color_scheme: &ColorScheme,
-) -> f32 {
+) {
     let wpm_text = format!("{:.0}", wpm);
     draw_text_ex(
         "wpm",
@@ -440,7 +486,6 @@ fn write_wpm(
             ..Default::default()
         },
     );
-    wpm
 }
 
 fn write_acc(
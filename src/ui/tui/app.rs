@@ -3,12 +3,10 @@ use crossterm::event::{self, Event as CEvent, KeyEvent, KeyCode};
 use ratatui::DefaultTerminal;
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
-use chrono;
 
 use crate::batch_size_selection::BatchSizeSelection;
 use crate::ui::tui::ui::render_app;
 use crate::{practice, utils};
-use crate::practice::TYPING_LEVELS;
 use crate::language::Language;
 use crate::color_scheme::ColorScheme;
 use crate::config::AppConfig;
@@ -19,6 +17,20 @@ use crate::word_number_selection::WordNumberSelection;
 use crate::top_words_selection::TopWordsSelection;
 use crate::settings::Settings;
 use crate::leaderboard::LeaderboardData;
+use crate::replay::Replay;
+use crate::ghost::Ghost;
+use crate::pacer::{self, Pacer};
+use crate::key_stats::{HeatmapData, KeyStats};
+use crate::keyboard_layout::KeyboardLayout;
+use crate::word_sampling::{self, SamplingMode, WordSampler};
+use crate::adaptive;
+use crate::text_library::{self, TextLibrary};
+use crate::normalization::Strictness;
+use crate::quotes::{self, QuoteLength};
+use crate::compose::Composer;
+use crate::code_snippets::{self, CodeLanguage};
+use crate::leaderboard::TestType;
+use crate::typing_session::{Keystroke, SessionMode, TypingSession};
 
 
 #[derive(PartialEq, Eq)]
@@ -30,14 +42,8 @@ pub enum GameState {
 
 pub struct App {
     pub exit: bool,
-    pub reference: String,
-    pub pressed_vec: Vec<char>,
-    pub pos1: usize,
-    pub words_done: usize,
-    pub is_correct: Vec<i32>,
-    pub errors_this_second: f32,
+    pub session: TypingSession,
     pub test_time: f32,
-    pub start_time: Option<Instant>,
     pub game_state: GameState,
     pub config: bool,
     pub punctuation: bool,
@@ -46,14 +52,10 @@ pub struct App {
     pub word_mode: bool,
     pub quote: bool,
     pub wiki_mode: bool,
+    pub adaptive_mode: bool,
     pub batch_size: usize,
     pub selected_config: String,
-    pub speed_per_second: Vec<f64>,
-    pub char_number: usize,
-    pub errors_per_second: Vec<f32>,
     pub tab_pressed: Instant,
-    pub correct_count: usize,
-    pub error_count: usize,
     pub practice_menu: bool,
     pub practice_mode: bool,
     pub selected_level: usize,
@@ -67,6 +69,46 @@ pub struct App {
     pub popup_states: PopupStates,
     pub menu_buttons_times: HashMap<String, Instant>,
     pub leaderboard: LeaderboardData,
+    pub replay: Option<Replay>,
+    pub exit_after_replay: bool,
+    pub pace_ghost: bool,
+    /// Ghost picked from a leaderboard entry or a log file, used instead of the personal best
+    pub chosen_ghost: Option<Ghost>,
+    pub ghost: Option<Ghost>,
+    pub pacer_wpm: u32,
+    pub pacer: Option<Pacer>,
+    pub heatmap: HeatmapData,
+    pub layout: KeyboardLayout,
+    /// Remaps QWERTY key presses to `layout`
+    pub emulate_layout: bool,
+    /// Results show the finger breakdown instead of the speed chart
+    pub finger_tab: bool,
+    pub sampling_mode: SamplingMode,
+    pub no_repeat_within: usize,
+    /// Kept between batches so no repeats carry over, see `word_sampler`
+    pub word_sampler: WordSampler,
+    pub code_mode: bool,
+    /// Language code snippets are picked from, None for any
+    pub code_language: Option<CodeLanguage>,
+    /// Language of the snippet being typed, used to highlight it
+    pub snippet_language: Option<CodeLanguage>,
+    pub skip_indent: bool,
+    pub library_mode: bool,
+    pub library: TextLibrary,
+    /// Name of the library text being typed through
+    pub library_text: Option<String>,
+    /// Where the chunk being typed ends in the library text
+    pub library_chunk_end: usize,
+    pub chunk_size: usize,
+    pub strictness: Strictness,
+    pub half_accent_errors: bool,
+    pub quote_length: QuoteLength,
+    /// The quote of the test is a favorite
+    pub quote_favorite: bool,
+    /// Shown instead of the reference when the test has nothing to type
+    pub notice: Option<String>,
+    /// Holds dead keys and letters until the mark they compose with
+    pub composer: Composer,
 }
 
 impl App {
@@ -75,14 +117,8 @@ impl App {
         
         Self {
             exit: false,
-            reference: String::new(),
-            pressed_vec: Vec::new(),
-            pos1: 0,
-            words_done: 0,
-            is_correct: Vec::new(),
-            errors_this_second: 0.0,
+            session: TypingSession::new(String::new(), SessionMode::Text),
             test_time: app_config.test_time,
-            start_time: None,
             game_state: GameState::NotStarted,
             config: false,
             punctuation: app_config.punctuation,
@@ -91,18 +127,17 @@ impl App {
             word_mode: app_config.word_mode,
             quote: app_config.quote,
             wiki_mode: app_config.wiki_mode,
+            adaptive_mode: app_config.adaptive_mode,
             batch_size: app_config.batch_size,
             selected_config: if app_config.time_mode { "time".into() }
                 else if app_config.word_mode { "words".into() }
                 else if app_config.quote { "quote".into() }
                 else if app_config.practice_mode { "practice".into() }
+                else if app_config.adaptive_mode { "adaptive".into() }
+                else if app_config.code_mode { "code".into() }
+                else if app_config.library_mode { "texts".into() }
                 else { "time".into() },
-            speed_per_second: Vec::new(),
-            char_number: 0,
-            errors_per_second: Vec::new(),
             tab_pressed: Instant::now() - Duration::from_secs(5),
-            correct_count: 0,
-            error_count: 0,
             practice_menu: false,
             practice_mode: app_config.practice_mode,
             selected_level: app_config.selected_level,
@@ -111,6 +146,29 @@ impl App {
             color_scheme: app_config.color_scheme,
             word_number: app_config.word_number,
             top_words: app_config.top_words,
+            pace_ghost: app_config.pace_ghost,
+            pacer_wpm: app_config.pacer_wpm,
+            layout: KeyboardLayout::find(&app_config.keyboard_layout),
+            emulate_layout: app_config.emulate_layout,
+            finger_tab: false,
+            sampling_mode: app_config.sampling_mode,
+            no_repeat_within: app_config.no_repeat_within,
+            word_sampler: WordSampler::default(),
+            code_mode: app_config.code_mode,
+            code_language: app_config.code_language,
+            snippet_language: None,
+            skip_indent: app_config.skip_indent,
+            library_mode: app_config.library_mode,
+            library: TextLibrary::load(),
+            library_text: app_config.library_text.clone(),
+            library_chunk_end: 0,
+            chunk_size: app_config.chunk_size,
+            strictness: app_config.strictness,
+            half_accent_errors: app_config.half_accent_errors,
+            quote_length: app_config.quote_length,
+            quote_favorite: false,
+            notice: None,
+            composer: Composer::default(),
             app_config,
             button_states: ButtonStates::new(),
             popup_states: PopupStates {
@@ -121,6 +179,7 @@ impl App {
                 settings: PopupState { open: false, selected: 0 },
                 batch_size_selection: PopupState { open: false, selected: 0 },
                 top_words_selection: PopupState { open: false, selected: 0 },
+                texts: PopupState { open: false, selected: 0 },
             },
             menu_buttons_times: HashMap::from([
                 ("settings".to_string(), Instant::now() - Duration::from_secs(5)),
@@ -131,134 +190,210 @@ impl App {
                 ("punctuation".to_string(), Instant::now() - Duration::from_secs(5)),
                 ("numbers".to_string(), Instant::now() - Duration::from_secs(5)),
                 ("wiki".to_string(), Instant::now() - Duration::from_secs(5)),
+                ("adaptive".to_string(), Instant::now() - Duration::from_secs(5)),
+                ("code".to_string(), Instant::now() - Duration::from_secs(5)),
+                ("texts".to_string(), Instant::now() - Duration::from_secs(5)),
             ]),
-            leaderboard: LeaderboardData {
-                open: false,
-                entries: crate::leaderboard::load_entries().unwrap_or_default(),
-                selected: 0,
-            },
+            leaderboard: LeaderboardData::default(),
+            replay: None,
+            exit_after_replay: false,
+            chosen_ghost: None,
+            ghost: None,
+            pacer: None,
+            heatmap: HeatmapData::default(),
         }
     }
 
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
-        if self.quote {
-            self.reference = utils::get_random_quote();
-            self.batch_size = self.reference.split_whitespace().count();
-        } else if self.practice_mode {
-            let level = practice::get_first_not_done();
-            self.reference = practice::create_words(TYPING_LEVELS[level].1, 50);
-        } else if self.time_mode {
-            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), self.batch_size);
-        } else if self.wiki_mode {
-            self.reference = utils::get_wiki_summary();
-        } else {
-            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), usize::min(self.batch_size, self.word_number));
+        if self.practice_mode {
+            self.selected_level = practice::get_first_not_done(&self.layout, self.language);
         }
-   
-        self.is_correct = vec![0; self.reference.chars().count()];
-        let mut last_recorded_time = Instant::now();
-        
+        self.restart();
+
         while !self.exit {
             self.button_states = ButtonStates {
                 settings: ButtonState::new("settings", "settings", "...", false, true),
                 divider0: ButtonState::new("|", "|", "|", true, true),
-                punctuation: ButtonState::new("punctuation", "! punctuation", "! punct", self.punctuation, !self.quote && !self.practice_mode && !self.wiki_mode),
-                numbers: ButtonState::new("numbers", "# numbers", "# num", self.numbers, !self.quote && !self.practice_mode && !self.wiki_mode),
+                punctuation: ButtonState::new("punctuation", "! punctuation", "! punct", self.punctuation, !self.quote && !self.practice_mode && !self.wiki_mode && !self.adaptive_mode && !self.code_mode && !self.library_mode),
+                numbers: ButtonState::new("numbers", "# numbers", "# num", self.numbers, !self.quote && !self.practice_mode && !self.wiki_mode && !self.adaptive_mode && !self.code_mode && !self.library_mode),
                 divider1: ButtonState::new("|", "|", "|", true, self.time_mode || self.word_mode),
                 time: ButtonState::new("time", "⌄ time", "⌄ time", self.time_mode, true),
                 words: ButtonState::new("words", "⌄ words", "⌄ words", self.word_mode, true),
                 quote: ButtonState::new("quote", "quote", "quote", self.quote, true),
                 wiki_mode: ButtonState::new("wiki", "wikipedia", "wiki", self.wiki_mode, true),
                 practice: ButtonState::new("practice", "practice", "practice", self.practice_mode, true),
+                adaptive: ButtonState::new("adaptive", "adaptive", "adapt", self.adaptive_mode, true),
+                code: ButtonState::new("code", "code", "code", self.code_mode, true),
+                texts: ButtonState::new("texts", "⌄ texts", "⌄ texts", self.library_mode, true),
             };
 
-            if self.game_state != GameState::Started {
-                last_recorded_time = Instant::now();
-            }
             if event::poll(Duration::from_millis(16))? {
                 if let CEvent::Key(key) = event::read()? {
-                    // Pass mutable reference to button_states
-                    self.handle_key_event(key, self.reference.clone())?;
+                    self.handle_key_event(key)?;
                 }
             }
-            self.timer = if let Some(start_time) = self.start_time {
-                if self.game_state == GameState::Started {
-                    Instant::now().duration_since(start_time)
-                } else if self.game_state != GameState::Results {
-                    Duration::from_secs(0)
-                } else {
-                    self.timer
-                }
-            } else {
-                Duration::from_secs(0)
-            };
 
-            if self.game_state != GameState::Results && ((self.test_time - self.timer.as_secs_f32() < 0.0 && self.game_state == GameState::Started && self.time_mode)
-                || (self.words_done >= self.word_number && self.word_mode)
-                || (self.words_done >= self.reference.split_whitespace().count() && (self.quote || self.wiki_mode) && self.game_state != GameState::Results)
-                || (self.words_done >= self.word_number 
-                    && (self.word_mode|| self.practice_mode)
-                    && self.game_state != GameState::Results)
-                || ((self.words_done >= 50 || self.pos1 >= self.reference.chars().count()) && self.practice_mode && self.game_state != GameState::Results))
-
-            {
-                self.errors_per_second.push(self.errors_this_second);
-                let total_typed = self.pressed_vec.len();
-                let chars_in_this_second = total_typed.saturating_sub(self.char_number);
-                let cpm = chars_in_this_second as f64 * 60.0;
-                self.speed_per_second.push(cpm);
-                self.game_state = GameState::Results;
+            if let Some((ch, pressed)) = self.composer.flush_stale() {
+                self.type_char_at(ch, pressed);
+            }
+
+            if let Some(replay) = self.replay.as_mut() {
+                replay.update();
+            }
 
-                let (correct_words, _, _) = utils::count_correct_words(&self.reference, &std::collections::VecDeque::from(self.is_correct.clone()));
-                let wpm = (correct_words as f32 / self.timer.as_secs_f32()) * 60.0;
+            if let Some(pacer) = self.pacer.as_mut().filter(|_| self.game_state == GameState::Started) {
+                pacer.update(&self.session);
+            }
 
-                let accuracy = if self.words_done > 0 {
-                    (self.correct_count as f32 / self.pressed_vec.len() as f32) * 100.0
-                } else {
-                    0.0
-                };
+            if self.game_state == GameState::Started && self.session.tick() {
+                self.game_state = GameState::Results;
+                let stats = self.session.stats();
 
                 if self.practice_mode {
                     practice::save_results(
-                        self.test_time as f64,
-                        accuracy as f64,
-                        wpm as f64,
-                        self.selected_level + 1,
+                        stats.elapsed,
+                        stats.accuracy,
+                        stats.wpm,
+                        &practice::LevelKey::new(&self.layout, self.language, self.selected_level),
                     );
                 }
-                
+
+                if let Some(name) = self.library_text.as_ref().filter(|_| self.library_mode) {
+                    let _ = self.library.record(name, self.library_chunk_end, stats.wpm, stats.accuracy, self.session.words_done);
+                }
+
                 // Save result to leaderboard
                 self.save_to_leaderboard();
             }
-            let now = Instant::now();
-            let time_since_last = now.duration_since(last_recorded_time);
+            self.timer = self.session.elapsed();
+
+            terminal.draw(|frame| render_app(frame, self))?;
+        }
+        Ok(())
+    }
 
-            if time_since_last >= Duration::from_secs(1) && self.game_state == GameState::Started && self.game_state != GameState::Results {
-                let total_typed = self.pressed_vec.len();
-                let chars_in_this_second = total_typed.saturating_sub(self.char_number);
-                let cpm = chars_in_this_second as f64 * 60.0;
+    fn session_mode(&self) -> SessionMode {
+        if self.practice_mode {
+            SessionMode::Practice
+        } else if self.time_mode {
+            SessionMode::Time(self.test_time)
+        } else if self.word_mode {
+            SessionMode::Words(self.word_number)
+        } else {
+            SessionMode::Text
+        }
+    }
 
-                self.speed_per_second.push(cpm);
+    fn generate_reference(&mut self) -> String {
+        if self.quote {
+            quotes::next_quote(self.language, self.quote_length).unwrap_or_else(|e| {
+                self.notice = Some(e.to_string());
+                String::new()
+            })
+        } else if self.practice_mode {
+            practice::create_words(&practice::level_keys(&self.layout, self.language, self.selected_level), 50)
+        } else if self.wiki_mode {
+            utils::get_wiki_summary(self.language)
+        } else if self.adaptive_mode {
+            let stats = KeyStats::load_all().unwrap_or_default();
+            adaptive::create_words(&stats, &utils::read_first_n_words(1000, self.language), adaptive::WORD_NUMBER)
+        } else if self.time_mode {
+            utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), self.batch_size, &self.word_sampler())
+        } else {
+            utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), usize::min(self.batch_size, self.word_number), &self.word_sampler())
+        }
+    }
 
-                self.char_number = total_typed;
+    /// Sampler of the current settings, rebuilt when one of them changed
+    fn word_sampler(&mut self) -> &WordSampler {
+        self.word_sampler.update(self.sampling_mode, self.no_repeat_within, self.language);
+        &self.word_sampler
+    }
 
-                self.errors_per_second.push(self.errors_this_second);
-                self.errors_this_second = 0.0;
-                last_recorded_time += Duration::from_secs(1);
-            }
-            terminal.draw(|frame| render_app(frame, self))?;
+    fn test_type(&self) -> TestType {
+        if self.practice_mode {
+            TestType::Practice(self.selected_level + 1)
+        } else if self.time_mode {
+            TestType::Time(self.test_time as u32)
+        } else if self.word_mode {
+            TestType::Word(self.word_number)
+        } else if self.quote {
+            TestType::Quote
+        } else if self.wiki_mode {
+            TestType::Wiki
+        } else if self.adaptive_mode {
+            TestType::Adaptive
+        } else if self.code_mode {
+            TestType::Code
+        } else if self.library_mode {
+            TestType::Custom
+        } else {
+            TestType::Time(30) // Default fallback
         }
-        Ok(())
     }
 
-    pub fn handle_key_event(
-        &mut self,
-        key_event: KeyEvent,
-        reference: String,
-    ) -> io::Result<()> {
-        let reference_chars: Vec<char> = reference.chars().collect();
+    /// Starts a fresh test for the current mode and settings
+    pub fn restart(&mut self) {
+        self.snippet_language = None;
+        self.notice = None;
+        let chunk = self.library_text.as_ref()
+            .filter(|_| self.library_mode)
+            .and_then(|name| self.library.next_chunk(name, self.chunk_size));
+        if self.library_mode && chunk.is_none() {
+            // The text was deleted, nothing to type through anymore
+            self.library_mode = false;
+            self.time_mode = true;
+        }
+        let reference = if let Some(chunk) = chunk {
+            self.library_chunk_end = chunk.end;
+            chunk.text
+        } else if self.code_mode {
+            let snippet = code_snippets::random_snippet(self.code_language);
+            self.snippet_language = Some(snippet.language);
+            snippet.text
+        } else {
+            self.generate_reference()
+        };
+        self.session = TypingSession::new(reference, self.session_mode());
+        self.session.auto_indent = self.skip_indent;
+        self.session.set_strictness(self.strictness);
+        self.session.half_accent_errors = self.half_accent_errors;
+        self.quote_favorite = self.quote && quotes::last_is_favorite();
+        self.composer = Composer::default();
+        self.ghost = match self.session.mode {
+            SessionMode::Time(_) | SessionMode::Words(_) => self.chosen_ghost.clone().or_else(|| {
+                if self.pace_ghost { Ghost::personal_best(&self.test_type()) } else { None }
+            }),
+            _ => None,
+        };
+        self.pacer = match self.session.mode {
+            SessionMode::Practice => None,
+            _ => Pacer::new(self.pacer_wpm),
+        };
+        self.game_state = GameState::NotStarted;
+        self.timer = Duration::from_secs(0);
+        self.tab_pressed = Instant::now() - Duration::from_secs(5);
+    }
 
+    pub fn handle_key_event(&mut self, key_event: KeyEvent) -> io::Result<()> {
         if key_event.kind == crossterm::event::KeyEventKind::Press {
+            if let Some(replay) = self.replay.as_mut() {
+                match key_event.code {
+                    KeyCode::Esc => {
+                        self.replay = None;
+                        if self.exit_after_replay {
+                            self.exit = true;
+                        }
+                    }
+                    KeyCode::Char(' ') => replay.toggle_pause(),
+                    KeyCode::Left => replay.slower(),
+                    KeyCode::Right => replay.faster(),
+                    KeyCode::Char('r') => replay.restart(),
+                    _ => {}
+                }
+                return Ok(());
+            }
+
             let schemes = ColorScheme::all();
             if self.popup_states.color_scheme.open {
                 match key_event.code {
@@ -314,6 +449,7 @@ impl App {
                             self.test_time = schemes[self.popup_states.time_selection.selected].to_seconds() as f32;
                         }
                         self.popup_states.time_selection.open = false;
+                        self.restart();
                         self.save_config();
                         return Ok(());
                     }
@@ -341,23 +477,10 @@ impl App {
                         return Ok(());
                     }
                     KeyCode::Enter => {
-                        self.language = match self.popup_states.language.selected {
-                            0 => Language::English,
-                            1 => Language::Indonesian,
-                            2 => Language::Italian,
-                            _ => Language::English,
-                        };
+                        self.language = schemes.get(self.popup_states.language.selected).copied().unwrap_or_default();
                         self.popup_states.language.open = false;
                         if self.word_mode || self.time_mode {
-                            if self.time_mode {
-                                self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), self.batch_size);
-                            } else if self.word_mode {
-                                self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), usize::min(self.batch_size, self.word_number));
-                            }
-                            self.is_correct = vec![0; self.reference.chars().count()];
-                            self.pressed_vec.clear();
-                            self.pos1 = 0;
-                            self.words_done = 0;
+                            self.restart();
                         }
                         self.save_config();
                         return Ok(());
@@ -389,15 +512,7 @@ impl App {
                         if self.popup_states.word_number_selection.selected < schemes.len() {
                             self.word_number = schemes[self.popup_states.word_number_selection.selected].to_words() as usize;
                         }
-                        if self.time_mode {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), self.batch_size);
-                        } else if self.word_mode {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), usize::min(self.batch_size, self.word_number));
-                        }
-                        self.is_correct = vec![0; self.reference.chars().count()];
-                        self.pressed_vec.clear();
-                        self.pos1 = 0;
-                        self.words_done = 0;
+                        self.restart();
                         self.popup_states.word_number_selection.open = false;
                         self.save_config();
                         return Ok(());
@@ -429,15 +544,7 @@ impl App {
                         if self.popup_states.batch_size_selection.selected < schemes.len() {
                             self.batch_size = schemes[self.popup_states.batch_size_selection.selected].to_words() as usize;
                         }
-                        if self.time_mode {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), self.batch_size);
-                        } else if self.word_mode {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), usize::min(self.batch_size, self.word_number));
-                        }
-                        self.is_correct = vec![0; self.reference.chars().count()];
-                        self.pressed_vec.clear();
-                        self.pos1 = 0;
-                        self.words_done = 0;
+                        self.restart();
                         self.popup_states.batch_size_selection.open = false;
                         self.save_config();
                         return Ok(());
@@ -469,15 +576,7 @@ impl App {
                         if self.popup_states.top_words_selection.selected < schemes.len() {
                             self.top_words = schemes[self.popup_states.top_words_selection.selected].to_words() as usize;
                         }
-                        if self.time_mode {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), self.batch_size);
-                        } else if self.word_mode {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), usize::min(self.batch_size, self.word_number));
-                        }
-                        self.is_correct = vec![0; self.reference.chars().count()];
-                        self.pressed_vec.clear();
-                        self.pos1 = 0;
-                        self.words_done = 0;
+                        self.restart();
                         self.popup_states.batch_size_selection.open = false;
                         self.save_config();
                         return Ok(());
@@ -486,6 +585,45 @@ impl App {
                 }
             }
 
+            if self.popup_states.texts.open {
+                match key_event.code {
+                    KeyCode::Esc => {
+                        self.popup_states.texts.open = false;
+                        return Ok(());
+                    }
+                    KeyCode::Up => {
+                        if self.popup_states.texts.selected > 0 {
+                            self.popup_states.texts.selected -= 1;
+                        }
+                        return Ok(());
+                    }
+                    KeyCode::Down => {
+                        if self.popup_states.texts.selected + 1 < self.library.texts.len() {
+                            self.popup_states.texts.selected += 1;
+                        }
+                        return Ok(());
+                    }
+                    KeyCode::Enter => {
+                        self.popup_states.texts.open = false;
+                        if let Some(text) = self.library.texts.get(self.popup_states.texts.selected) {
+                            self.library_text = Some(text.name.clone());
+                            self.library_mode = true;
+                            self.time_mode = false;
+                            self.word_mode = false;
+                            self.quote = false;
+                            self.wiki_mode = false;
+                            self.practice_mode = false;
+                            self.adaptive_mode = false;
+                            self.code_mode = false;
+                            self.restart();
+                            self.save_config();
+                        }
+                        return Ok(());
+                    }
+                    _ => return Ok(()),
+                }
+            }
+
             if self.popup_states.settings.open {
                 match key_event.code {
                     KeyCode::Esc => {
@@ -511,21 +649,108 @@ impl App {
                             self.popup_states.color_scheme.selected = schemes.iter().position(|&s| s == self.color_scheme).unwrap_or(0);
                         } else if self.popup_states.settings.selected == 1 {
                             self.popup_states.language.open = true;
-                            self.popup_states.language.selected = match self.language {
-                                Language::English => 0,
-                                Language::Indonesian => 1,
-                                Language::Italian=> 2,
-                            };
+                            self.popup_states.language.selected = Language::all().iter().position(|&l| l == self.language).unwrap_or(0);
                         } else if self.popup_states.settings.selected == 2 {
                             self.popup_states.batch_size_selection.open = true;
                         } else if self.popup_states.settings.selected == 3 {
                             self.popup_states.top_words_selection.open = true;
+                        } else if self.popup_states.settings.selected == 4 {
+                            self.pace_ghost = !self.pace_ghost;
+                            if !self.pace_ghost {
+                                self.chosen_ghost = None;
+                            }
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 5 {
+                            self.pacer_wpm = pacer::next_preset(self.pacer_wpm);
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 6 {
+                            self.layout = KeyboardLayout::find(&KeyboardLayout::next_name(&self.layout.name));
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 7 {
+                            self.emulate_layout = !self.emulate_layout;
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 8 {
+                            self.sampling_mode = self.sampling_mode.next();
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 9 {
+                            self.no_repeat_within = word_sampling::next_no_repeat(self.no_repeat_within);
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 10 {
+                            self.code_language = CodeLanguage::next(self.code_language);
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 11 {
+                            self.skip_indent = !self.skip_indent;
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 12 {
+                            self.chunk_size = text_library::next_chunk_size(self.chunk_size);
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 13 {
+                            self.strictness = self.strictness.next();
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 14 {
+                            self.half_accent_errors = !self.half_accent_errors;
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
+                        } else if self.popup_states.settings.selected == 15 {
+                            self.quote_length = self.quote_length.next();
+                            self.popup_states.settings.open = false;
+                            self.save_config();
+                            self.restart();
+                            return Ok(());
                         }
                     }
                     _ => return Ok(()),
                 }
             }
 
+            // Handle heatmap if it's open
+            if self.heatmap.open {
+                match key_event.code {
+                    KeyCode::Esc => self.heatmap.open = false,
+                    KeyCode::Char('a') => self.heatmap.all_tests = !self.heatmap.all_tests,
+                    KeyCode::Char('m') => self.heatmap.metric = self.heatmap.metric.next(),
+                    KeyCode::Tab => self.tab_pressed = Instant::now(),
+                    KeyCode::Char('h') | KeyCode::Char('H') => {
+                        if self.tab_pressed.elapsed() < Duration::from_secs(1) {
+                            self.heatmap.open = false;
+                            self.tab_pressed = Instant::now() - Duration::from_secs(5);
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+
             // Handle leaderboard if it's open
             if self.leaderboard.open {
                 match key_event.code {
@@ -545,6 +770,20 @@ impl App {
                         }
                         return Ok(());
                     }
+                    KeyCode::Enter => {
+                        if let Some(entry) = self.leaderboard.entries.get(self.leaderboard.selected) {
+                            self.replay = Replay::for_timestamp(&entry.timestamp);
+                        }
+                        return Ok(());
+                    }
+                    KeyCode::Char('g') => {
+                        if let Some(entry) = self.leaderboard.entries.get(self.leaderboard.selected) {
+                            self.chosen_ghost = Some(Ghost::for_entry(entry, &format!("run {:.0} wpm", entry.wpm)));
+                            self.leaderboard.open = false;
+                            self.restart();
+                        }
+                        return Ok(());
+                    }
                     KeyCode::Tab => {
                         self.tab_pressed = Instant::now();
                         return Ok(());
@@ -556,6 +795,22 @@ impl App {
                         }
                         return Ok(());
                     }
+                    KeyCode::Char(c @ ('t' | 'n' | 'p' | 'd' | 'r' | 's' | 'b' | 'c')) => {
+                        let query = &mut self.leaderboard.query;
+                        match c {
+                            't' => query.next_test_type(&self.leaderboard.history),
+                            'n' => query.next_language(&self.leaderboard.history),
+                            'p' => query.next_punctuation(),
+                            'd' => query.next_digits(),
+                            'r' => query.dates = query.dates.next(),
+                            's' => query.sort = query.sort.next(),
+                            'b' => query.personal_bests = !query.personal_bests,
+                            _ => query.clear_filters(),
+                        }
+                        self.leaderboard.selected = 0;
+                        self.leaderboard.refresh();
+                        return Ok(());
+                    }
                     _ => return Ok(()),
                 }
             }
@@ -566,17 +821,8 @@ impl App {
                     self.exit = true;
                 },
                 KeyCode::Backspace => {
-                    if !self.pressed_vec.is_empty() && reference_chars.get(self.pos1) == Some(&' ') {
-                        self.words_done = self.words_done.saturating_sub(1);
-                    }
-                    if self.is_correct[self.pos1] == 2 || self.is_correct[self.pos1] == 1 {
-                        self.correct_count = self.correct_count.saturating_sub(1);
-                    } else if self.is_correct[self.pos1] == -1 {
-                        self.error_count = self.error_count.saturating_sub(1);
-                    }
-                    self.pressed_vec.pop();
-                    if self.pos1 > 0 {
-                        self.pos1 -= 1;
+                    if !self.composer.cancel() {
+                        self.session.backspace();
                     }
                     self.config = false;
                 }
@@ -589,7 +835,7 @@ impl App {
                 }
                 KeyCode::Down => {
                     if self.practice_menu {
-                        if self.selected_level < TYPING_LEVELS.len() - 1 {
+                        if self.selected_level < practice::levels(&self.layout, self.language).len() - 1 {
                             self.selected_level += 1;
                         }
                     } else {
@@ -601,30 +847,9 @@ impl App {
                 },
                 KeyCode::Enter => {
                     if self.tab_pressed.elapsed() < Duration::from_secs(1) {
-                        if self.word_mode {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), usize::min(self.batch_size, self.word_number));
-                        } else if self.time_mode {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), self.batch_size);
-                        } else if self.quote {
-                            self.reference = utils::get_random_quote();
-                        } else if self.practice_mode {
-                            self.reference = practice::create_words(TYPING_LEVELS[self.selected_level].1, 50);
-                        } else if self.wiki_mode {
-                            self.reference = utils::get_wiki_summary();
-                        }
-                        self.is_correct = vec![0; self.reference.chars().count()];
-                        self.pressed_vec.clear();
-                        self.pos1 = 0;
-                        self.words_done = 0;
-                        self.errors_this_second = 0.0;
-                        self.start_time = None;
-                        self.game_state = GameState::NotStarted;
-                        self.speed_per_second.clear();
-                        self.char_number = 0;
-                        self.errors_per_second.clear();
-                        self.tab_pressed = Instant::now() - Duration::from_secs(5);
-                        self.correct_count = 0;
-                        self.error_count = 0;
+                        self.restart();
+                    } else if self.session.is_multiline() && !self.config && !self.practice_menu && self.game_state != GameState::Results {
+                        self.type_char('\n');
                     }
                     if self.practice_menu {
                         self.practice_menu = false;
@@ -633,21 +858,11 @@ impl App {
                         self.word_mode = false;
                         self.quote = false;
                         self.wiki_mode = false;
-                        self.pressed_vec.clear();
-                        self.pos1 = 0;
-                        self.words_done = 0;
-                        self.errors_this_second = 0.0;
-                        self.start_time = None;
-                        self.game_state = GameState::NotStarted;
-                        self.speed_per_second.clear();
-                        self.char_number = 0;
-                        self.errors_per_second.clear();
-                        self.tab_pressed = Instant::now() - Duration::from_secs(5);
-                        self.correct_count = 0;
-                        self.error_count = 0;
+                        self.adaptive_mode = false;
+                        self.code_mode = false;
+                        self.library_mode = false;
                         self.config = false;
-                        self.reference = practice::create_words(TYPING_LEVELS[self.selected_level].1, 50);
-                        self.is_correct = vec![0; self.reference.chars().count()];
+                        self.restart();
                     }
                     if self.config {
                         match self.selected_config.as_str() {
@@ -661,6 +876,9 @@ impl App {
                                 self.practice_mode = false;
                                 self.wiki_mode = false;
                                 self.practice_mode = false;
+                                self.adaptive_mode = false;
+                                self.code_mode = false;
+                                self.library_mode = false;
                                 if let Some(time) = self.menu_buttons_times.get_mut("time") {
                                     *time = Instant::now();
                                 }
@@ -674,6 +892,9 @@ impl App {
                                 self.wiki_mode = false;
                                 self.quote = false;
                                 self.practice_mode = false;
+                                self.adaptive_mode = false;
+                                self.code_mode = false;
+                                self.library_mode = false;
                                 if let Some(time) = self.menu_buttons_times.get_mut("words") {
                                     *time = Instant::now();
                                 }
@@ -684,10 +905,13 @@ impl App {
                                 self.wiki_mode = false;
                                 self.word_mode = false;
                                 self.practice_mode = false;
+                                self.adaptive_mode = false;
+                                self.code_mode = false;
+                                self.library_mode = false;
                             }
                             "practice" => {
                                 self.practice_menu = !self.practice_menu;
-                                self.selected_level = practice::get_first_not_done();
+                                self.selected_level = practice::get_first_not_done(&self.layout, self.language);
                             }
                             "punctuation" => {
                                 self.punctuation = !self.punctuation;
@@ -701,14 +925,40 @@ impl App {
                                 self.word_mode = false;
                                 self.wiki_mode = true;
                                 self.practice_mode = false;
+                                self.adaptive_mode = false;
+                                self.code_mode = false;
+                                self.library_mode = false;
+                            }
+                            "adaptive" => {
+                                self.quote = false;
+                                self.time_mode = false;
+                                self.word_mode = false;
+                                self.wiki_mode = false;
+                                self.practice_mode = false;
+                                self.adaptive_mode = true;
+                                self.code_mode = false;
+                                self.library_mode = false;
+                            }
+                            "code" => {
+                                self.quote = false;
+                                self.time_mode = false;
+                                self.word_mode = false;
+                                self.wiki_mode = false;
+                                self.practice_mode = false;
+                                self.adaptive_mode = false;
+                                self.code_mode = true;
+                                self.library_mode = false;
+                            }
+                            "texts" => {
+                                self.library = TextLibrary::load();
+                                self.popup_states.texts.open = true;
+                                self.popup_states.texts.selected = self.library_text.as_ref()
+                                    .and_then(|name| self.library.names().iter().position(|n| n == name))
+                                    .unwrap_or(0);
                             }
                             "language" => {
                                 self.popup_states.language.open = true;
-                                self.popup_states.language.selected = match self.language {
-                                    Language::English => 0,
-                                    Language::Indonesian => 1,
-                                    Language::Italian=> 2,
-                                };
+                                self.popup_states.language.selected = Language::all().iter().position(|&l| l == self.language).unwrap_or(0);
                             }
                             "theme" => {
                                 self.popup_states.color_scheme.open = true;
@@ -741,28 +991,7 @@ impl App {
                             }
                             _ => {}
                         }
-                        if self.selected_config == "quote" {
-                            self.reference = utils::get_random_quote();
-                        } else if self.time_mode {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), self.batch_size);
-                        } else if self.wiki_mode {
-                            self.reference = utils::get_wiki_summary();
-                        } else if !self.popup_states.settings.open {
-                            self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), usize::min(self.batch_size, self.word_number));
-                        }
-                        self.is_correct = vec![0; self.reference.chars().count()];
-                        self.pressed_vec.clear();
-                        self.pos1 = 0;
-                        self.words_done = 0;
-                        self.errors_this_second = 0.0;
-                        self.start_time = None;
-                        self.game_state = GameState::NotStarted;
-                        self.speed_per_second.clear();
-                        self.char_number = 0;
-                        self.errors_per_second.clear();
-                        self.tab_pressed = Instant::now() - Duration::from_secs(5);
-                        self.correct_count = 0;
-                        self.error_count = 0;
+                        self.restart();
                         self.save_config();
                     }
                 }
@@ -825,13 +1054,31 @@ impl App {
                 KeyCode::Char(ch) => {
                     // Handle Tab+L leaderboard toggle
                     if (ch == 'l' || ch == 'L') && self.tab_pressed.elapsed() < Duration::from_secs(1) {
-                        self.leaderboard.open = !self.leaderboard.open;
+                        self.leaderboard.toggle();
+                        self.tab_pressed = Instant::now() - Duration::from_secs(5);
+                        return Ok(());
+                    }
+
+                    // Handle Tab+H heatmap toggle
+                    if (ch == 'h' || ch == 'H') && self.tab_pressed.elapsed() < Duration::from_secs(1) {
+                        self.heatmap.toggle();
                         self.tab_pressed = Instant::now() - Duration::from_secs(5);
-                        // Reload entries when opening leaderboard
-                        if self.leaderboard.open {
-                            self.leaderboard.entries = crate::leaderboard::load_entries().unwrap_or_default();
-                            self.leaderboard.selected = 0;
+                        return Ok(());
+                    }
+
+                    // Handle Tab+S favorite toggle of the quote just typed
+                    if (ch == 's' || ch == 'S') && self.quote && self.game_state == GameState::Results && self.tab_pressed.elapsed() < Duration::from_secs(1) {
+                        if let Some(favorite) = quotes::toggle_last_favorite() {
+                            self.quote_favorite = favorite;
                         }
+                        self.tab_pressed = Instant::now() - Duration::from_secs(5);
+                        return Ok(());
+                    }
+
+                    // Handle Tab+F results tab toggle
+                    if (ch == 'f' || ch == 'F') && self.game_state == GameState::Results && self.tab_pressed.elapsed() < Duration::from_secs(1) {
+                        self.finger_tab = !self.finger_tab;
+                        self.tab_pressed = Instant::now() - Duration::from_secs(5);
                         return Ok(());
                     }
                     
@@ -840,64 +1087,13 @@ impl App {
                         self.practice_mode = false;
                         return Ok(());
                     }
-                    if self.is_correct[0] == 0 && ch == ' ' {
+                    if self.game_state == GameState::Results {
                         return Ok(());
                     }
-                    let reference_chars: Vec<char> = self.reference.chars().collect();
-                    if let Some(&ref_char) = reference_chars.get(self.pos1) {
-                        if self.game_state == GameState::Results {
-                            return Ok(());
-                        }
-                        if self.game_state == GameState::NotStarted {
-                            self.game_state = GameState::Started;
-                            self.start_time = Some(Instant::now());
-                        }
-                        if self.is_correct.len() > self.pos1 {
-                            
-                            if ref_char == ch && self.is_correct[self.pos1] != -1 && self.is_correct[self.pos1] != 1 {
-                                self.is_correct[self.pos1] = 2; // Correct
-                                self.correct_count += 1;
-                                self.pos1 += 1;
-                            } else if ref_char == ch && (self.is_correct[self.pos1] == -1 || self.is_correct[self.pos1] == 1) {
-                                self.is_correct[self.pos1] = 1; // Corrected
-                                self.pos1 += 1;
-                            } else {
-                                self.is_correct[self.pos1] = -1; // Incorrect
-                                self.errors_this_second += 1.0;
-                                self.error_count += 1;
-                                if !self.practice_mode {
-                                    self.pos1 += 1;
-                                }
-                            }
-                        }
-                        
-                        self.pressed_vec.push(ch);
-                        if (reference_chars.get(self.pos1) == Some(&' ') && !self.practice_mode) || (reference_chars.get(self.pos1) == Some(&' ') && self.is_correct[self.pos1] != -1 || self.pos1 == reference_chars.len()) {
-                            self.words_done += 1;
-                        }
-                    }
-                    self.config = false;
-
-                    if self.pos1 >= self.reference.chars().count() {
-                        // If we've reached the end of reference text, count the final word for word/quote modes
-                        if (self.word_mode || self.quote) && self.pos1 > 0 {
-                            // Check if we just completed a word (not already counted)
-                            let previous_char = reference_chars.get(self.pos1 - 1);
-                            if previous_char.is_some() && previous_char != Some(&' ') {
-                                //self.words_done += 1;
-                            }
-                        }
-                        
-                        // Only generate new reference if we haven't reached target word count yet
-                        if self.time_mode || self.word_mode {
-                            if self.time_mode {
-                                self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), self.batch_size);
-                            } else if self.word_mode {
-                                self.reference = utils::get_reference(self.punctuation, self.numbers, &utils::read_first_n_words(self.top_words, self.language), usize::min(self.batch_size, self.word_number));
-                            }
-                            self.is_correct = vec![0; self.reference.chars().count()];
-                            self.pos1 = 0;
-                        }
+                    let ch = if self.emulate_layout { self.layout.from_qwerty(ch) } else { ch };
+                    let expected = self.session.ref_chars.get(self.session.pos).copied();
+                    for (ch, pressed) in self.composer.feed(ch, expected, self.session.strictness) {
+                        self.type_char_at(ch, pressed);
                     }
                 }
                 _ => {}
@@ -906,6 +1102,22 @@ impl App {
         Ok(())
     }
 
+    fn type_char(&mut self, ch: char) {
+        self.type_char_at(ch, Instant::now());
+    }
+
+    fn type_char_at(&mut self, ch: char, pressed: Instant) {
+        if self.session.type_char_at(ch, pressed) != Keystroke::Ignored && self.game_state == GameState::NotStarted {
+            self.game_state = GameState::Started;
+        }
+        self.config = false;
+
+        if self.session.needs_batch() {
+            let reference = self.generate_reference();
+            self.session.next_batch(reference);
+        }
+    }
+
     fn save_config(&mut self) {
         self.app_config = AppConfig {
             punctuation: self.punctuation,
@@ -915,6 +1127,7 @@ impl App {
             quote: self.quote,
             practice_mode: self.practice_mode,
             wiki_mode: self.wiki_mode,
+            adaptive_mode: self.adaptive_mode,
             batch_size: self.batch_size,
             test_time: self.test_time,
             selected_level: self.selected_level,
@@ -922,60 +1135,51 @@ impl App {
             color_scheme: self.color_scheme,
             word_number: self.word_number,
             top_words: self.top_words,
+            pace_ghost: self.pace_ghost,
+            pacer_wpm: self.pacer_wpm,
+            keyboard_layout: self.layout.name.clone(),
+            emulate_layout: self.emulate_layout,
+            sampling_mode: self.sampling_mode,
+            no_repeat_within: self.no_repeat_within,
+            code_mode: self.code_mode,
+            code_language: self.code_language,
+            skip_indent: self.skip_indent,
+            library_mode: self.library_mode,
+            library_text: self.library_text.clone(),
+            chunk_size: self.chunk_size,
+            strictness: self.strictness,
+            half_accent_errors: self.half_accent_errors,
+            quote_length: self.quote_length,
         };
         
         let _ = self.app_config.save();
     }
 
     fn save_to_leaderboard(&mut self) {
-        if let Some(start_time) = self.start_time {
-            let elapsed = start_time.elapsed().as_secs_f64();
-            let total_chars = self.pressed_vec.len();
-            
-            // Calculate WPM (words per minute) - using original formula: words_done / time
+        if self.session.is_started() {
+            let test_mode = if self.practice_mode { "practice" }
+                else if self.time_mode { "time" }
+                else if self.word_mode { "word" }
+                else if self.quote { "quote" }
+                else if self.wiki_mode { "wiki" }
+                else if self.adaptive_mode { "adaptive" }
+                else if self.code_mode { "code" }
+                else if self.library_mode { "library" }
+                else { "time" };
 
-            let (correct_words, _, _) = utils::count_correct_words(&self.reference, &std::collections::VecDeque::from(self.is_correct.clone()));
-            let wpm = (correct_words as f32 / self.timer.as_secs_f32()) * 60.0;
-            
-            // Calculate accuracy
-            let correct_chars = self.correct_count;
-            let accuracy = if total_chars > 0 {
-                (correct_chars as f64 / total_chars as f64) * 100.0
-            } else {
-                0.0
-            };
-            
-            // Determine test type
-            let test_type = if self.practice_mode {
-                crate::leaderboard::TestType::Practice(self.selected_level + 1)
-            } else if self.time_mode {
-                crate::leaderboard::TestType::Time(self.test_time as u32)
-            } else if self.word_mode {
-                crate::leaderboard::TestType::Word(self.word_number)
-            } else if self.quote {
-                crate::leaderboard::TestType::Quote
-            } else if self.wiki_mode {
-                crate::leaderboard::TestType::Wiki
-            } else {
-                crate::leaderboard::TestType::Time(30) // Default fallback
-            };
-            
             // Create leaderboard entry
-            let entry = crate::leaderboard::LeaderboardEntry {
-                wpm: wpm as f64,
-                accuracy,
-                test_type,
-                test_mode: if self.practice_mode { "practice".to_string() }
-                          else if self.time_mode { "time".to_string() }
-                          else if self.word_mode { "word".to_string() }
-                          else if self.quote { "quote".to_string() }
-                          else if self.wiki_mode { "wiki".to_string() }
-                          else { "time".to_string() },
-                word_count: self.words_done, // Actual completed words
-                test_duration: elapsed,
-                timestamp: chrono::Local::now().to_rfc3339(),
-                language: self.language,
-            };
+            let entry = crate::leaderboard::LeaderboardEntry::from_session(
+                &self.session,
+                self.test_type(),
+                test_mode,
+                self.language,
+                crate::leaderboard::TestConfig {
+                    punctuation: self.punctuation,
+                    digits: self.numbers,
+                    top_words: self.top_words,
+                    batch_size: self.batch_size,
+                },
+            );
             
             // Save entry
             if let Err(e) = crate::leaderboard::save_entry(&entry) {
@@ -1003,10 +1207,12 @@ impl App {
                     }
                 }
             }
+
+            crate::keystroke_log::save_session_at(&self.session, entry.test_type, &entry.test_mode, self.language, &entry.timestamp);
             
             // Always update in-memory entries to ensure synchronization
             // This ensures the leaderboard immediately reflects the latest game results
-            self.leaderboard.entries = crate::leaderboard::load_entries().unwrap_or_default();
+            self.leaderboard.reload();
         }
     }
 }
\ No newline at end of file
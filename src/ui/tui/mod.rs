@@ -1,12 +1,32 @@
 use std::io;
+use std::path::Path;
 use crate ::ui::tui::app::App;
+use crate::replay::Replay;
+use crate::ghost::Ghost;
 
 
-pub fn main() -> io::Result<()> {
+pub fn main(ghost: Option<Ghost>) -> io::Result<()> {
     let mut terminal = ratatui::init();
     let mut app = App::new();
+    app.chosen_ghost = ghost;
+    app.pace_ghost |= app.chosen_ghost.is_some();
 
     let app_result = app.run(&mut terminal);
     ratatui::restore();
     app_result
-}
\ No newline at end of file
+}
+
+pub fn replay(path: &Path) -> io::Result<()> {
+    let replay = Replay::load(path).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e))
+    })?;
+
+    let mut terminal = ratatui::init();
+    let mut app = App::new();
+    app.replay = Some(replay);
+    app.exit_after_replay = true;
+
+    let app_result = app.run(&mut terminal);
+    ratatui::restore();
+    app_result
+}
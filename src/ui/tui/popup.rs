@@ -1,6 +1,6 @@
 use ratatui::{
     style::Style,
-    widgets::{Block, Borders, List, ListItem},
+    widgets::{Block, Borders, List, ListItem, ListState},
     layout::{Rect, Layout, Constraint, Direction},
     Frame,
 };
@@ -18,7 +18,10 @@ pub enum PopupContent {
     WordNumberSelection,
     Settings,
     BatchSizeSelection,
-    TopWordsSelection,
+    /// Carries the words of the language, top words beyond them are marked
+    TopWordsSelection(usize),
+    /// Summaries of the library texts
+    Texts(Vec<String>),
 }
 
 pub struct PopupState {
@@ -34,6 +37,7 @@ pub struct PopupStates {
     pub settings: PopupState,
     pub batch_size_selection: PopupState,
     pub top_words_selection: PopupState,
+    pub texts: PopupState,
 }
 
 pub trait PopupData {
@@ -51,19 +55,24 @@ impl PopupData for PopupContent {
             PopupContent::WordNumberSelection => "Select Number of Words",
             PopupContent::Settings => "Select Setting",
             PopupContent::BatchSizeSelection => "Select Batch Size",
-            PopupContent::TopWordsSelection => "Select Top Words",
+            PopupContent::TopWordsSelection(_) => "Select Top Words",
+            PopupContent::Texts(_) => "Select Text",
         }
     }
 
     fn items(&self) -> Vec<String> {
         match self {
-            PopupContent::Language => Language::all().iter().map(|x| x.to_string()).collect(),
+            PopupContent::Language => Language::all().iter().map(Language::label).collect(),
             PopupContent::ColorScheme => ColorScheme::all().iter().map(|x| x.name().to_string()).collect(),
             PopupContent::TimeSelection => TimeSelection::all().iter().map(|x| x.to_string()).collect(),
             PopupContent::WordNumberSelection => vec!["25".to_string(), "50".to_string(), "100".to_string(), "200".to_string(), "500".to_string()],
             PopupContent::Settings => Settings::all().iter().map(|x| x.to_string()).collect(),
             PopupContent::BatchSizeSelection => vec!["10".to_string(), "25".to_string(), "50".to_string(), "100".to_string(), "200".to_string()],
-            PopupContent::TopWordsSelection => vec!["100".to_string(), "200".to_string(), "500".to_string(), "1000".to_string()],
+            PopupContent::TopWordsSelection(available) => [100, 200, 500, 1000]
+                .iter()
+                .map(|n| if n > available { format!("{} (all {})", n, available) } else { n.to_string() })
+                .collect(),
+            PopupContent::Texts(summaries) => summaries.clone(),
         }
     }
 
@@ -75,7 +84,8 @@ impl PopupData for PopupContent {
             PopupContent::WordNumberSelection => &app.popup_states.word_number_selection.selected,
             PopupContent::Settings => &app.popup_states.settings.selected,
             PopupContent::BatchSizeSelection => &app.popup_states.batch_size_selection.selected,
-            PopupContent::TopWordsSelection => &app.popup_states.top_words_selection.selected,
+            PopupContent::TopWordsSelection(_) => &app.popup_states.top_words_selection.selected,
+            PopupContent::Texts(_) => &app.popup_states.texts.selected,
         }
     }
 }
@@ -118,7 +128,9 @@ pub fn render_popup(frame: &mut Frame, app: &App, area: Rect, color_scheme: Colo
     let ref_color = color_scheme.ref_color();
     let border_color = color_scheme.border_color();
 
-    let percent_y = if area.height < 25 && app.popup_states.color_scheme.open { 80 } else if area.height < 40 && app.popup_states.color_scheme.open { 50 } else { 30 };
+    // Long lists need a taller popup to show every item
+    let long_list = app.popup_states.color_scheme.open || app.popup_states.settings.open || app.popup_states.language.open;
+    let percent_y = if area.height < 25 && long_list { 80 } else if area.height < 40 && long_list { 50 } else { 30 };
 
     let popup_area = centered_rect(30, percent_y, area);
     frame.render_widget(ratatui::widgets::Clear, popup_area);
@@ -144,5 +156,7 @@ pub fn render_popup(frame: &mut Frame, app: &App, area: Rect, color_scheme: Colo
             .border_style(Style::default().fg(border_color))
             .style(Style::default().bg(bg_color)),
     );
-    frame.render_widget(list, popup_area);
+    // Scrolls to the selected item when the list is taller than the popup
+    let mut state = ListState::default().with_selected(Some(*content.selected_index(app)));
+    frame.render_stateful_widget(list, popup_area, &mut state);
 }
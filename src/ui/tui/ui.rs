@@ -2,16 +2,18 @@ use ratatui::{Frame, prelude::*, widgets::*};
 use ratatui::widgets::canvas::Canvas;
 use std::collections::HashMap;
 use std::time::Duration;
-use crate::utils;
 
 use crate::color_scheme::ColorScheme;
 use crate::custom_colors::MyColor;
-use crate::language::Language;
-use crate::practice;
-use crate::practice::TYPING_LEVELS;
+use crate::finger_stats::FingerStats;
+use crate::key_stats::{HeatmapMetric, KeyStats};
+use crate::{practice, utils};
+use crate::code_snippets::{self, CodeLanguage};
 use crate::ui::tui::app::{App, GameState};
 use crate::button_states::ButtonStates;
 use crate::ui::tui::popup::*;
+use crate::replay::Replay;
+use crate::typing_session::{TypingSession, CHARS_PER_WORD};
 
 fn render_instructions(
     frame: &mut Frame,
@@ -19,11 +21,20 @@ fn render_instructions(
     show: bool,
     practice_menu: bool,
     leaderboard_open: bool,
+    replay_open: bool,
+    heatmap_open: bool,
+    results: bool,
+    quote_favorite: Option<bool>,
     color_scheme: ColorScheme,
 ) {
     let mut lines = Vec::new();
-    if leaderboard_open {
-        lines.push(Line::from("  ↑/↓ - navigate, Tab + L - close, Esc - exit"));
+    if replay_open {
+        lines.push(Line::from("  Space - pause, ←/→ - speed, r - restart, Esc - close replay"));
+    } else if heatmap_open {
+        lines.push(Line::from("  a - this test / all tests, m - error rate / time to press, Tab + H - close, Esc - close"));
+    } else if leaderboard_open {
+        lines.push(Line::from("  ↑/↓ - navigate, ↵ - replay, g - race as ghost, Tab + L - close, Esc - exit"));
+        lines.push(Line::from("  t - type, n - language, p - punctuation, d - digits, r - dates, c - clear filters, s - sort, b - personal bests"));
     } else if show {
         lines.push(Line::from(
             "  \u{2191} - enter config, \u{2190}/\u{2192} - toggle config, ↵ - apply config",
@@ -32,12 +43,20 @@ fn render_instructions(
         lines.push(Line::from("  ↑ or ↓ to navigate, ↵ to select"));
         lines.push(Line::from("  q - quit menu"));
     }
-    if !practice_menu && !leaderboard_open {
+    if !practice_menu && !leaderboard_open && !replay_open && !heatmap_open {
         lines.push(Line::from("  Tab + Enter - restart"));
+        if results {
+            lines.push(Line::from("  Tab + F - speed chart / finger stats"));
+        }
+        match quote_favorite {
+            Some(true) => lines.push(Line::from("  Tab + S - remove quote from favorites ★")),
+            Some(false) => lines.push(Line::from("  Tab + S - add quote to favorites")),
+            None => {}
+        }
         lines.push(Line::from("  ⌄ - double Enter to view more options"));
-        lines.push(Line::from("  Tab + L - local leaderboard"));
+        lines.push(Line::from("  Tab + L - local leaderboard, Tab + H - key heatmap"));
     }
-    if !leaderboard_open {
+    if !leaderboard_open && !replay_open && !heatmap_open {
         lines.push(Line::from("  Esc - exit"));
     }
 
@@ -57,16 +76,22 @@ pub fn render_app(frame: &mut Frame, app: &App) {
         .constraints([
             Constraint::Min(0),
             if app.leaderboard.open {
+                Constraint::Length(2)
+            } else if app.replay.is_some() || app.heatmap.open {
                 Constraint::Length(1)
             } else if app.game_state == GameState::Results {
-                Constraint::Length(2)
+                Constraint::Length(3)
             } else {
                 Constraint::Length(4)
             },
         ])
         .split(frame.area());
 
-    if app.leaderboard.open {
+    if app.replay.is_some() {
+        render_reference_frame(frame, chunks[0], app, app.timer, app.color_scheme, &app.button_states);
+    } else if app.heatmap.open {
+        render_heatmap(frame, chunks[0], app, app.color_scheme);
+    } else if app.leaderboard.open {
         render_leaderboard(frame, chunks[0], app, app.color_scheme);
     } else if app.game_state == GameState::Results {
         render_results(frame, chunks[0], app, app.color_scheme);
@@ -81,6 +106,10 @@ pub fn render_app(frame: &mut Frame, app: &App) {
         app.game_state != GameState::Results && !app.practice_menu && !app.leaderboard.open,
         app.practice_menu,
         app.leaderboard.open,
+        app.replay.is_some(),
+        app.heatmap.open,
+        app.game_state == GameState::Results,
+        (app.game_state == GameState::Results && app.quote).then_some(app.quote_favorite),
         app.color_scheme,
     );
 
@@ -96,7 +125,9 @@ pub fn render_app(frame: &mut Frame, app: &App) {
     } else if app.popup_states.batch_size_selection.open {
         render_popup(frame, app, frame.area(), app.color_scheme, PopupContent::BatchSizeSelection);
     } else if app.popup_states.top_words_selection.open {
-        render_popup(frame, app, frame.area(), app.color_scheme, PopupContent::TopWordsSelection);
+        render_popup(frame, app, frame.area(), app.color_scheme, PopupContent::TopWordsSelection(app.language.word_count()));
+    } else if app.popup_states.texts.open {
+        render_popup(frame, app, frame.area(), app.color_scheme, PopupContent::Texts(app.library.summaries()));
     } else if app.popup_states.settings.open {
         render_popup(frame, app, frame.area(), app.color_scheme, PopupContent::Settings);
     }
@@ -114,7 +145,8 @@ fn render_practice_menu(frame: &mut Frame, area: Rect, app: &App, color_scheme:
     } else {
         0
     };
-    for level in TYPING_LEVELS.iter().enumerate().skip(to_skip as usize) {
+    let progress = practice::PracticeProgress::load();
+    for level in practice::levels(&app.layout, app.language).iter().enumerate().skip(to_skip as usize) {
         let mut fg_color = color_scheme.text_color();
         let mut bg_color = color_scheme.bg_color();
 
@@ -124,8 +156,7 @@ fn render_practice_menu(frame: &mut Frame, area: Rect, app: &App, color_scheme:
         }
 
         let line =
-            if practice::check_if_completed(&format!("practice_results/level_{}.txt", level.0 + 1))
-            {
+            if progress.is_completed(&practice::LevelKey::new(&app.layout, app.language, level.0)) {
                 Line::from(vec![
                     Span::styled(
                         "✔ ",
@@ -234,43 +265,19 @@ fn smooth(
     smoothed
 }
 
-fn calc_standard_deviation(values: &[f64], average_word_length: f64) -> f64 {
-    let wpm_values: Vec<f64> = values
-        .iter()
-        .map(|&cpm| cpm / average_word_length)
-        .collect();
-    let mean = wpm_values.iter().sum::<f64>() / wpm_values.len() as f64;
-    let variance =
-        wpm_values.iter().map(|&x| (x - mean).powi(2)).sum::<f64>() / wpm_values.len() as f64;
-    variance.sqrt()
-}
-
 fn get_stats(app: &App, color_scheme: ColorScheme) -> (Line<'static>, Line<'static>, bool) {
     let bg_color = color_scheme.bg_color();
     let main_color = color_scheme.main_color();
     let ref_color = color_scheme.ref_color();
-    let (correct_words, _, _) = utils::count_correct_words(&app.reference, &std::collections::VecDeque::from(app.is_correct.clone()));
-    let wpm = (correct_words as f32 / app.timer.as_secs_f32()) * 60.0;
+    let stats = app.session.stats();
+    let wpm = stats.wpm;
     let wpm_str = format!("{}", wpm as i32);
 
-    let accuracy = if app.words_done > 0 {
-        (app.correct_count as f32 / app.pressed_vec.len() as f32) * 100.0
-    } else {
-        0.0
-    };
-    let acc_str = format!("{}%", accuracy.round());
+    let acc_str = format!("{}%", stats.accuracy.round());
 
-    let raw = app.words_done as f32 / (app.timer.as_secs_f32() / 60.0);
+    let raw_str = format!("{}", stats.raw_wpm.round());
 
-    let raw_str = format!("{}", raw.round());
-
-    let standard_deviation = calc_standard_deviation(&app.speed_per_second, 6.0);
-    let consistency = if wpm > 0.0 {
-        (100.0 - (standard_deviation / wpm as f64 * 100.0).round()).max(0.0)
-    } else {
-        0.0
-    };
-    let consistency_str = format!("{consistency}%");
+    let consistency_str = format!("{}%", stats.consistency);
 
     let time_str = format!("{:.0}s", app.timer.as_secs_f32());
 
@@ -282,13 +289,19 @@ fn get_stats(app: &App, color_scheme: ColorScheme) -> (Line<'static>, Line<'stat
         "quote".to_string()
     } else if app.wiki_mode {
         "wiki".to_string()
+    } else if app.adaptive_mode {
+        "adaptive".to_string()
+    } else if app.code_mode {
+        "code".to_string()
+    } else if app.library_mode {
+        "texts".to_string()
     } else {
         "practice".to_string()
     };
-    if app.punctuation && !app.quote && !app.practice_mode {
+    if app.punctuation && !app.quote && !app.practice_mode && !app.adaptive_mode {
         mode_str += " !";
     }
-    if app.numbers && !app.quote && !app.practice_mode {
+    if app.numbers && !app.quote && !app.practice_mode && !app.adaptive_mode {
         mode_str += " #";
     }
 
@@ -348,7 +361,7 @@ fn get_stats(app: &App, color_scheme: ColorScheme) -> (Line<'static>, Line<'stat
     (
         Line::from(label_spans).alignment(Alignment::Center),
         Line::from(value_spans).alignment(Alignment::Center),
-        wpm >= practice::WPM_MIN as f32,
+        practice::passes(wpm, stats.accuracy),
     )
 }
 
@@ -371,11 +384,11 @@ fn get_chart(
 
     let max_speed: f64 = f64::max(
         70.0,
-        app.speed_per_second
+        app.session.speed_per_second
             .iter()
             .fold(0.0_f64, |a, &b| a.max(b))
             .max(1.0)
-            / 6.0
+            / CHARS_PER_WORD
             + 30.0,
     );
     let max_time = app.timer.as_secs_f32().ceil() as f64;
@@ -488,22 +501,22 @@ fn render_results(frame: &mut Frame, area: Rect, app: &App, color_scheme: ColorS
         step = 10;
     }
 
-    let mut errors_per_second: Vec<f32> = Vec::new();
+    let mut errors_per_second: Vec<f64> = Vec::new();
     let mut speed_per_second: Vec<f64> = Vec::new();
     let mut prev = 0.0;
 
     if test_time >= 120 {
-        let mut errs = app.errors_per_second.clone();
+        let mut errs = app.session.errors_per_second.clone();
         for (i, err) in errs.iter_mut().enumerate() {
             if i % 2 == 0 {
                 errors_per_second.push(*err);
-                speed_per_second.push(app.speed_per_second[i]);
+                speed_per_second.push(app.session.speed_per_second[i]);
             }
             prev = *err;
         }
     } else {
-        errors_per_second = app.errors_per_second.clone();
-        speed_per_second = app.speed_per_second.clone();
+        errors_per_second = app.session.errors_per_second.clone();
+        speed_per_second = app.session.speed_per_second.clone();
     }
 
     for (i, err) in errors_per_second.iter_mut().enumerate() {
@@ -518,7 +531,7 @@ fn render_results(frame: &mut Frame, area: Rect, app: &App, color_scheme: ColorS
         errors_per_second[1] = 0.0;
     }
 
-    let smoothed_speeds = smooth(&speed_per_second, 6.0, extra_columns, columns_to_delete);
+    let smoothed_speeds = smooth(&speed_per_second, CHARS_PER_WORD, extra_columns, columns_to_delete);
 
     let chart = get_chart(&smoothed_speeds, app, step, color_scheme);
 
@@ -526,7 +539,7 @@ fn render_results(frame: &mut Frame, area: Rect, app: &App, color_scheme: ColorS
 
     let inner_area = block.inner(area);
 
-    let chart_height = 12u16;
+    let chart_height = 13u16;
     let y_offset = if inner_area.height > chart_height {
         (inner_area.height - chart_height) / 2
     } else {
@@ -545,6 +558,7 @@ fn render_results(frame: &mut Frame, area: Rect, app: &App, color_scheme: ColorS
         Constraint::Min(3),
         Constraint::Length(1),
         Constraint::Length(1),
+        Constraint::Length(1),
     ])
     .split(centered_area);
 
@@ -570,15 +584,17 @@ fn render_results(frame: &mut Frame, area: Rect, app: &App, color_scheme: ColorS
         Block::default().style(Style::default().bg(bg_color)),
         chart_area,
     );
-    frame.render_widget(chart, chart_area);
+    if !app.finger_tab {
+        frame.render_widget(chart, chart_area);
+    }
 
     let max_speed = f64::max(
         70.0,
-        app.speed_per_second
+        app.session.speed_per_second
             .iter()
             .fold(0.0_f64, |a, &b| a.max(b))
             .max(1.0)
-            / 6.0
+            / CHARS_PER_WORD
             + 30.0,
     );
 
@@ -614,9 +630,12 @@ fn render_results(frame: &mut Frame, area: Rect, app: &App, color_scheme: ColorS
             }
         });
 
-    frame.render_widget(canvas, chart_area);
-
-    frame.render_widget(empty_line.clone(), chunks[1]);
+    if app.finger_tab {
+        render_finger_stats(frame, chunks[0], chunks[1], app, color_scheme);
+    } else {
+        frame.render_widget(canvas, chart_area);
+        frame.render_widget(empty_line.clone(), chunks[1]);
+    }
     frame.render_widget(stats, chunks[2]);
     frame.render_widget(empty_line, chunks[3]);
 
@@ -629,14 +648,47 @@ fn render_results(frame: &mut Frame, area: Rect, app: &App, color_scheme: ColorS
         } else {
             frame.render_widget(
                 Line::from(format!(
-                    "You need at least {} WPM to pass this level.",
-                    practice::WPM_MIN
+                    "You need at least {} WPM and {}% accuracy to pass this level.",
+                    practice::WPM_MIN,
+                    practice::ACCURACY_MIN
                 ))
                 .alignment(Alignment::Center),
                 chunks[4],
             );
         }
+    } else if let Some(ghost) = &app.ghost {
+        frame.render_widget(
+            Line::from(ghost.summary(&app.session)).alignment(Alignment::Center),
+            chunks[4],
+        );
     }
+
+    if let Some(pacer) = &app.pacer {
+        frame.render_widget(
+            Line::from(pacer.summary()).alignment(Alignment::Center),
+            chunks[5],
+        );
+    }
+}
+
+/// Per-finger wpm, error rate and same-finger slowdown of the finished test
+fn render_finger_stats(frame: &mut Frame, area: Rect, summary_area: Rect, app: &App, color_scheme: ColorScheme) {
+    let finger_stats = FingerStats::from_events(&app.session.log, &app.layout);
+    let wpm = app.session.stats().wpm;
+    let style = Style::default().fg(color_scheme.text_color()).bg(color_scheme.bg_color());
+
+    let lines: Vec<Line> = if finger_stats.is_empty() {
+        vec![Line::from("No keys pressed")]
+    } else {
+        finger_stats.lines(wpm).into_iter().map(Line::from).collect()
+    };
+    frame.render_widget(Paragraph::new(lines).style(style).alignment(Alignment::Center), area);
+    frame.render_widget(
+        Line::from(finger_stats.summary())
+            .style(Style::default().fg(color_scheme.ref_color()).bg(color_scheme.bg_color()))
+            .alignment(Alignment::Center),
+        summary_area,
+    );
 }
 
 fn render_reference_frame(
@@ -651,23 +703,44 @@ fn render_reference_frame(
     let max_ref_width = calculate_max_ref_width(area);
     let ref_padding = calculate_ref_padding(area, max_ref_width);
 
-    let instruction_line = create_config_line(app, color_scheme, &button_states, area);
+    let session = app.replay.as_ref().map_or(&app.session, |replay| &replay.session);
+    let instruction_line = match &app.replay {
+        Some(replay) => create_replay_line(replay, color_scheme),
+        None => create_config_line(app, color_scheme, &button_states, area),
+    };
     let horizontal_line = create_horizontal_line(area, color_scheme);
-    let time_words = if app.time_mode {
+    let time_words = if let Some(replay) = &app.replay {
+        create_replay_timer(replay, color_scheme)
+    } else if app.time_mode {
         create_timer(timer, app.test_time, color_scheme)
     } else {
         let all_words = if app.word_mode {
             app.word_number
-        } else if app.quote || app.wiki_mode {
-            app.reference.split_whitespace().count()
+        } else if app.quote || app.wiki_mode || app.adaptive_mode || app.code_mode || app.library_mode {
+            app.session.reference.split_whitespace().count()
         } else if app.practice_mode {
             50
         } else {
             app.batch_size
         };
-        create_words_count(all_words, app.words_done, color_scheme)
+        create_words_count(all_words, app.session.words_done, color_scheme)
     };
-    let colored_lines = create_colored_lines(app, max_ref_width, color_scheme);
+    let (ghost_pos, pacer_pos) = match &app.replay {
+        Some(_) => (None, None),
+        None => (
+            app.ghost.as_ref().and_then(|ghost| ghost.cursor(&app.session)),
+            app.pacer.as_ref().and_then(|pacer| pacer.cursor(&app.session)),
+        ),
+    };
+    // Replays don't know the language of a snippet, they are shown without highlighting
+    let syntax = app.snippet_language.filter(|_| app.replay.is_none());
+    let mut colored_lines = match app.notice.as_ref().filter(|_| app.replay.is_none()) {
+        Some(notice) => vec![Line::from(notice.clone()).style(Style::default().fg(color_scheme.ref_color()))],
+        None => create_colored_lines(session, ghost_pos, pacer_pos, syntax, max_ref_width, color_scheme),
+    };
+    if app.language.is_right_to_left() {
+        colored_lines = colored_lines.into_iter().map(|line| line.alignment(Alignment::Right)).collect();
+    }
     let empty_space = calculate_vertical_padding(area, colored_lines.len());
 
     let content = assemble_content(
@@ -709,6 +782,50 @@ fn create_timer(timer: Duration, test_time: f32, color_scheme: ColorScheme) -> L
         .alignment(Alignment::Left)
 }
 
+fn create_replay_timer(replay: &Replay, color_scheme: ColorScheme) -> Line<'static> {
+    let bg_color = color_scheme.bg_color();
+    let main_color = color_scheme.main_color();
+    let text = format!(
+        "{:.1}s  {}/{} words",
+        replay.elapsed().as_secs_f64(),
+        replay.session.words_done,
+        replay.log.word_count(),
+    );
+
+    Line::from(text)
+        .style(Style::default().fg(main_color).bg(bg_color))
+        .alignment(Alignment::Left)
+}
+
+fn create_replay_line(replay: &Replay, color_scheme: ColorScheme) -> Line<'static> {
+    let bg_color = color_scheme.bg_color();
+    let ref_color = color_scheme.ref_color();
+    let dimmer_main = color_scheme.dimmer_main();
+
+    let mut spans = vec![Span::styled(
+        format!(" replay {} ", replay.log.test_mode),
+        Style::default().fg(color_scheme.main_color()).bg(bg_color),
+    )];
+    for (i, speed) in crate::replay::SPEEDS.iter().enumerate() {
+        let style = if i == replay.speed_index {
+            Style::default().fg(bg_color).bg(dimmer_main)
+        } else {
+            Style::default().fg(ref_color).bg(bg_color)
+        };
+        spans.push(Span::styled(format!(" {}x ", speed), style));
+    }
+    let status = if replay.paused {
+        " paused "
+    } else if replay.is_done() {
+        " finished "
+    } else {
+        ""
+    };
+    spans.push(Span::styled(status, Style::default().fg(ref_color).bg(bg_color)));
+
+    Line::from(spans).alignment(Alignment::Center)
+}
+
 fn create_words_count(
     all_words: usize,
     typed_words: usize,
@@ -757,6 +874,12 @@ fn create_config_line(app: &App, color_scheme: ColorScheme, button_states: &Butt
             Style::default().fg(fg_colors[i]).bg(bg_colors[i]),
         ));
     }
+    if app.emulate_layout {
+        spans.push(Span::styled(
+            format!(" ⌨ {} ", app.layout.name.to_lowercase()),
+            Style::default().fg(ref_color).bg(bg_color),
+        ));
+    }
 
     Line::from(spans).alignment(Alignment::Center)
 }
@@ -774,7 +897,10 @@ fn create_horizontal_line(area: Rect, color_scheme: ColorScheme) -> Line<'static
 }
 
 fn create_colored_lines<'a>(
-    app: &App,
+    session: &TypingSession,
+    ghost_pos: Option<usize>,
+    pacer_pos: Option<usize>,
+    syntax: Option<CodeLanguage>,
     max_ref_width: usize,
     color_scheme: ColorScheme,
 ) -> Vec<Line<'a>> {
@@ -784,27 +910,38 @@ fn create_colored_lines<'a>(
     let correct_color = color_scheme.correct_color();
     let corrected_color = color_scheme.corrected_color();
     let incorrect_color = color_scheme.incorrect_color();
-    let mut fg_colors: Vec<Color> = vec![ref_color; app.reference.chars().count()];
-    let mut bg_colors: Vec<Color> = vec![bg_color; app.reference.chars().count()];
-
-    for i in 0..app.is_correct.len() {
-        if app.pos1 == i {
+    let kinds = syntax.map(|language| code_snippets::highlight(&session.reference, language));
+    let untyped_color = |i: usize| match kinds.as_ref().and_then(|kinds| kinds.get(i)) {
+        Some(kind) => color_scheme.syntax_color(*kind),
+        None => ref_color,
+    };
+    let matching_bracket = kinds
+        .as_ref()
+        .and_then(|kinds| code_snippets::matching_bracket(&session.ref_chars, kinds, session.pos));
+    let mut fg_colors: Vec<Color> = vec![ref_color; session.ref_chars.len()];
+    let mut bg_colors: Vec<Color> = vec![bg_color; session.ref_chars.len()];
+
+    for i in 0..session.is_correct.len() {
+        if session.pos == i {
             fg_colors[i] = bg_color;
             bg_colors[i] = main_color
-        } else if app.is_correct[i] == 0 || i >= app.pos1 {
-            fg_colors[i] = ref_color;
-        } else if app.is_correct[i] == 2 {
+        } else if session.is_correct[i] == 0 || i >= session.pos {
+            fg_colors[i] = untyped_color(i);
+        } else if session.is_correct[i] == 2 {
             fg_colors[i] = correct_color;
-        } else if app.is_correct[i] == 1 {
+        } else if session.is_correct[i] == 1 {
             fg_colors[i] = corrected_color;
-        } else if app.is_correct[i] == -1 {
+        } else if session.is_correct[i] == -1 {
             fg_colors[i] = incorrect_color;
         } else {
             fg_colors[i] = ref_color;
         }
     }
+    if let Some(ghost_pos) = ghost_pos.filter(|&g| g != session.pos && g < bg_colors.len()) {
+        bg_colors[ghost_pos] = color_scheme.border_color();
+    }
 
-    let split = split_lines(&app.reference, max_ref_width);
+    let split = utils::wrap_reference(&session.reference, max_ref_width);
 
     let mut char_index = 0;
     split
@@ -815,8 +952,14 @@ fn create_colored_lines<'a>(
                 .map(|c| {
                     let fg_color = fg_colors.get(char_index).cloned().unwrap_or(ref_color);
                     let bg_color = bg_colors.get(char_index).cloned().unwrap_or(bg_color);
+                    let mut style = Style::default().fg(fg_color).bg(bg_color);
+                    if (pacer_pos == Some(char_index) || matching_bracket == Some(char_index)) && char_index != session.pos {
+                        style = style.add_modifier(Modifier::UNDERLINED | Modifier::BOLD);
+                    }
                     char_index += 1;
-                    Span::styled(c.to_string(), Style::default().fg(fg_color).bg(bg_color))
+                    // Line breaks of the reference have to be typed, so they are shown
+                    let c = if c == '\n' { '↵' } else { c };
+                    Span::styled(c.to_string(), style)
                 })
                 .collect();
             Line::from(spans)
@@ -885,32 +1028,97 @@ fn create_reference_block(ref_padding: u16, color_scheme: ColorScheme) -> Block<
         .title_alignment(Alignment::Left)
 }
 
-fn split_lines(text: &str, width: usize) -> Vec<String> {
-    text.lines()
-        .flat_map(|line| {
-            let words = line.split_whitespace();
+/// Width of one key on the heatmap keyboard
+const HEATMAP_KEY_WIDTH: usize = 6;
 
-            let mut current_line = String::new();
-            let mut lines = Vec::new();
+fn render_heatmap(frame: &mut Frame, area: Rect, app: &App, color_scheme: ColorScheme) {
+    let current;
+    let (stats, scope) = if app.heatmap.all_tests {
+        (&app.heatmap.all, "all tests")
+    } else {
+        current = KeyStats::from_session(&app.session);
+        (&current, "this test")
+    };
+    let metric = app.heatmap.metric;
 
-            for word in words {
-                if current_line.len() + word.len() + 1 > width {
-                    lines.push(current_line.to_string());
-                    current_line.clear();
-                }
-                current_line.push_str(&format!("{} ", word));
-            }
-            if !current_line.is_empty() {
-                lines.push(current_line.to_string());
-            }
-            lines
-        })
-        .collect()
+    let block = Block::default()
+        .title(format!("Key Heatmap - {} - {}", scope, metric))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(color_scheme.border_color()))
+        .title_style(Style::default().fg(color_scheme.main_color()));
+
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+
+    if stats.is_empty() {
+        let empty_text = Paragraph::new("No keys pressed yet.\nComplete a test to see your keys here!")
+            .style(Style::default().fg(color_scheme.ref_color()))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+        frame.render_widget(empty_text, inner_area);
+        return;
+    }
+
+    let bg: MyColor = color_scheme.bg_color();
+    let cold: MyColor = color_scheme.correct_color();
+    let hot: MyColor = color_scheme.incorrect_color();
+
+    let mut lines: Vec<Line> = Vec::new();
+    for (i, row) in app.layout.rows.iter().enumerate() {
+        let indent = " ".repeat(i * HEATMAP_KEY_WIDTH / 2);
+        let mut keys = vec![Span::raw(indent.clone())];
+        let mut labels = vec![Span::raw(indent)];
+        for key in row.chars() {
+            let width = if key == ' ' { HEATMAP_KEY_WIDTH * 6 } else { HEATMAP_KEY_WIDTH };
+            let shade: Color = stats.color(key, metric, bg, cold, hot).into();
+            let fg = if stats.heat(key, metric).is_some() {
+                color_scheme.bg_color()
+            } else {
+                color_scheme.ref_color()
+            };
+            let style = Style::default().fg(fg).bg(shade);
+            let name = if key == ' ' { "space".to_string() } else { key.to_string() };
+            keys.push(Span::styled(format!("{:^w$}", name, w = width - 1), style));
+            labels.push(Span::styled(format!("{:^w$}", stats.label(key, metric), w = width - 1), style));
+            keys.push(Span::raw(" "));
+            labels.push(Span::raw(" "));
+        }
+        lines.push(Line::from(keys));
+        lines.push(Line::from(labels));
+        lines.push(Line::from(""));
+    }
+
+    let worst: Vec<String> = stats
+        .worst_keys(metric, 5)
+        .into_iter()
+        .map(|key| format!("{} {}", key, stats.label(key, metric)))
+        .collect();
+    let title = match metric {
+        HeatmapMetric::Errors => "Most missed",
+        HeatmapMetric::Latency => "Slowest",
+    };
+    lines.push(Line::from(Span::styled(
+        format!("{}: {}", title, worst.join(", ")),
+        Style::default().fg(color_scheme.text_color()),
+    )));
+
+    let keyboard_width = (app.layout.rows[1].chars().count() + 1) * HEATMAP_KEY_WIDTH;
+    let keyboard_height = lines.len() as u16;
+    let keyboard_area = Rect {
+        x: inner_area.x + inner_area.width.saturating_sub(keyboard_width as u16) / 2,
+        y: inner_area.y + inner_area.height.saturating_sub(keyboard_height) / 2,
+        width: (keyboard_width as u16).min(inner_area.width),
+        height: keyboard_height.min(inner_area.height),
+    };
+    frame.render_widget(
+        Paragraph::new(lines).style(Style::default().bg(color_scheme.bg_color())),
+        keyboard_area,
+    );
 }
 
 fn render_leaderboard(frame: &mut Frame, area: Rect, app: &App, color_scheme: ColorScheme) {
     let block = Block::default()
-        .title("Local Leaderboard")
+        .title(format!("Local Leaderboard - {}", app.leaderboard.query.describe()))
         .borders(Borders::ALL)
         .border_style(Style::default().fg(color_scheme.border_color()))
         .title_style(Style::default().fg(color_scheme.main_color()));
@@ -919,8 +1127,13 @@ fn render_leaderboard(frame: &mut Frame, area: Rect, app: &App, color_scheme: Co
     frame.render_widget(block, area);
 
     if app.leaderboard.entries.is_empty() {
+        let message = if app.leaderboard.history.is_empty() {
+            "No typing test results yet.\nComplete a test to see your scores here!"
+        } else {
+            "No results match the filters.\nPress c to clear them."
+        };
         let empty_text =
-            Paragraph::new("No typing test results yet.\nComplete a test to see your scores here!")
+            Paragraph::new(message)
                 .style(Style::default().fg(color_scheme.ref_color()))
                 .alignment(Alignment::Center)
                 .wrap(Wrap { trim: true });
@@ -990,20 +1203,16 @@ fn render_leaderboard(frame: &mut Frame, area: Rect, app: &App, color_scheme: Co
         };
 
         // Format test type
-        let test_type = match &entry.test_type {
-            crate::leaderboard::TestType::Time(secs) => format!("{}s", secs),
-            crate::leaderboard::TestType::Word(words) => format!("{}w", words),
-            crate::leaderboard::TestType::Quote => "Quote".to_string(),
-            crate::leaderboard::TestType::Practice(level) => format!("L{}", level),
-            crate::leaderboard::TestType::Wiki => "Wiki".to_string(),
-        };
+        let mut test_type = entry.test_type.to_string();
+        if entry.punctuation {
+            test_type.push('!');
+        }
+        if entry.digits {
+            test_type.push('#');
+        }
 
         // Format language
-        let lang = match entry.language {
-            Language::English => "EN",
-            Language::Indonesian => "ID",
-            Language::Italian => "IT",
-        };
+        let lang = entry.language.short_name();
 
         let row_style = if i == app.leaderboard.selected {
             Style::default()
@@ -1034,7 +1243,7 @@ fn render_leaderboard(frame: &mut Frame, area: Rect, app: &App, color_scheme: Co
             Constraint::Length(4),  // Rank
             Constraint::Length(10), // Date
             Constraint::Length(8),  // Time (HH:MM AM/PM)
-            Constraint::Length(6),  // Type
+            Constraint::Length(8),  // Type
             Constraint::Length(5),  // WPM
             Constraint::Length(5),  // Acc%
             Constraint::Length(6),  // Words
@@ -1046,6 +1255,23 @@ fn render_leaderboard(frame: &mut Frame, area: Rect, app: &App, color_scheme: Co
 
     frame.render_widget(table, inner_area);
 
+    // Quote typed in the selected entry, on the bottom line next to the scroll indicator
+    if let Some(quote) = app.leaderboard.entries.get(app.leaderboard.selected).and_then(|entry| entry.quote.as_ref()) {
+        let width = inner_area.width.saturating_sub(12);
+        let mut line: String = quote.chars().take(width as usize).collect();
+        if quote.chars().count() > width as usize {
+            line.pop();
+            line.push('…');
+        }
+        let quote_area = Rect {
+            x: inner_area.x,
+            y: inner_area.y + inner_area.height.saturating_sub(1),
+            width,
+            height: 1,
+        };
+        frame.render_widget(Paragraph::new(line).style(Style::default().fg(color_scheme.dimmer_main())), quote_area);
+    }
+
     // Show scroll indicators if there are more entries than visible
     if app.leaderboard.entries.len() > max_visible_rows {
         let scroll_info = format!(
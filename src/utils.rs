@@ -6,13 +6,10 @@ use macroquad::prelude::*;
 
 use ::rand::Rng;
 use ::rand::prelude::IndexedRandom;
-use ::rand::prelude::SliceRandom;
 
-use std::collections::VecDeque;
-
-use crate::{Quote, language::Language};
-
-const QUOTES: &str = include_str!("../assets/quotes.json");
+use crate::language::Language;
+use crate::word_sampling::WordSampler;
+use crate::word_source::WikiSummary;
 
 pub fn read_first_n_words(n: usize, language: Language) -> Vec<String> {
     language.get_words(n)
@@ -94,7 +91,7 @@ pub fn draw_rounded_rect_lines(x: f32, y: f32, w: f32, h: f32, radius: f32, widt
     }
 }
 
-pub fn get_reference(punctuation: bool, digits: bool, word_list: &[String], batch_size: usize) -> String {
+pub fn get_reference(punctuation: bool, digits: bool, word_list: &[String], batch_size: usize, sampler: &WordSampler) -> String {
     let mut items = Vec::new();
     let mut rng = ::rand::rng();
 
@@ -110,8 +107,7 @@ pub fn get_reference(punctuation: bool, digits: bool, word_list: &[String], batc
     let num_words = batch_size - num_digits;
 
     // Generate words
-    for _ in 0..num_words {
-        let mut word = word_list.choose(&mut rng).unwrap().clone();
+    for mut word in sampler.sample(word_list, num_words, &mut rng) {
         if punctuation {
             let punctuations = [".", ",", "!", "?", ";", ":"];
             if rng.random_bool(0.2) {
@@ -127,7 +123,7 @@ pub fn get_reference(punctuation: bool, digits: bool, word_list: &[String], batc
         items.push(word);
     }
 
-    // Generate digits
+    // Generate digits, at random places so the words keep the order the sampler gave them
     for _ in 0..num_digits {
         let choice = rng.random_range(0..4);
         let number = if choice == 0 {
@@ -137,12 +133,10 @@ pub fn get_reference(punctuation: bool, digits: bool, word_list: &[String], batc
         } else {
             rng.random_range(1..100).to_string()
         };
-        items.push(number);
+        let position = rng.random_range(0..=items.len());
+        items.insert(position, number);
     }
 
-    // Shuffle all items and join with spaces
-    items.shuffle(&mut rng);
-    
     // Clean each item to remove any embedded whitespace that could cause extra words
     let cleaned_items: Vec<String> = items.into_iter()
         .map(|item| item.replace(|c: char| c.is_whitespace(), ""))
@@ -161,30 +155,12 @@ pub fn get_reference(punctuation: bool, digits: bool, word_list: &[String], batc
     result
 }
 
-pub fn get_random_quote() -> String {
-    let quotes: Vec<Quote> = serde_json::from_str(QUOTES).unwrap_or_default();
-
-    let mut rng = ::rand::rng();
-
-    let fallback = Quote {
-        text: "Welcome to TypeMan!".to_string(),
-        author: "mzums".to_string(),
-    };
-
-    let random_quote = quotes.choose(&mut rng).unwrap_or(&fallback);
-
-    format!("\"{}\" - {}", random_quote.text, random_quote.author)
-}
-
-pub fn get_wiki_summary() -> String {
-    const SUMMARIES: &str = include_str!("../assets/featured_summaries.json");
-    #[derive(serde::Deserialize)]
-    #[derive(Clone)]
-    struct WikiSummary {
-        title: String,
-        summary: String,
+/// Random wiki summary in the language, an English one when the language has none
+pub fn get_wiki_summary(language: Language) -> String {
+    let mut summaries = language.source().summaries();
+    if summaries.is_empty() {
+        summaries = Language::English.source().summaries();
     }
-    let summaries: Vec<WikiSummary> = serde_json::from_str(SUMMARIES).unwrap_or_default();
     let mut rng = ::rand::rng();
     let summary = summaries
         .choose(&mut rng)
@@ -197,19 +173,76 @@ pub fn get_wiki_summary() -> String {
     return format!("{} - {}", summary.title, summary.summary);
 }
 
-pub fn count_correct_words(reference: &str, is_correct: &VecDeque<i32>) -> (usize, usize, usize) {
+/// Splits a reference into display lines of at most `width` chars. Every char is kept so
+/// the lines add up to the reference: lines break after the spaces following a word, right
+/// after a newline, which stays at the end of its line, and inside words longer than `width`.
+pub fn wrap_reference(reference: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+
+    // A token is a word with the spaces after it, or the indentation starting a line
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    for c in reference.chars() {
+        if !c.is_whitespace() && token.ends_with([' ', '\t']) {
+            tokens.push(std::mem::take(&mut token));
+        }
+        token.push(c);
+        if c == '\n' {
+            tokens.push(std::mem::take(&mut token));
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_len = 0;
+    for token in tokens {
+        let chars: Vec<char> = token.chars().collect();
+        if line_len > 0 && line_len + chars.len() > width {
+            lines.push(std::mem::take(&mut line));
+            line_len = 0;
+        }
+        for chunk in chars.chunks(width) {
+            if line_len > 0 && line_len + chunk.len() > width {
+                lines.push(std::mem::take(&mut line));
+                line_len = 0;
+            }
+            line.extend(chunk);
+            line_len += chunk.len();
+        }
+        if token.ends_with('\n') {
+            lines.push(std::mem::take(&mut line));
+            line_len = 0;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+pub fn count_correct_words(reference: &str, is_correct: &[i32]) -> (usize, usize, usize) {
     let mut correct_words = 0;
     let mut no_corrected_words = 0;
     let mut all_words = 0;
     let mut word_correct = true;
     let mut word_corrected = true;
     let mut char_idx = 0;
+    let mut prev_space = true;
 
     for c in reference.chars() {
         if is_correct[char_idx] == 0 {
             break;
         }
-        if c == ' ' {
+        // Indentation and blank lines end at most one word
+        if c.is_whitespace() && prev_space {
+            char_idx += 1;
+            continue;
+        }
+        prev_space = c.is_whitespace();
+        if c.is_whitespace() {
             if word_correct && char_idx > 0 && (is_correct[char_idx] == 1 || is_correct[char_idx] == 2) {
                 correct_words += 1;
             }
@@ -229,7 +262,7 @@ pub fn count_correct_words(reference: &str, is_correct: &VecDeque<i32>) -> (usiz
         }
         char_idx += 1;
     }
-    if !reference.ends_with(' ') && char_idx > 0 {
+    if !reference.ends_with(char::is_whitespace) && char_idx > 0 {
         if word_correct {
             correct_words += 1;
         }
@@ -246,13 +279,26 @@ mod tests {
     use super::*;
     use crate::language::Language;
 
+    #[test]
+    fn test_wrap_reference_keeps_every_char() {
+        assert_eq!(wrap_reference("the quick brown fox", 10), vec!["the quick ", "brown fox"]);
+
+        let code = "fn main() {\n    let x = 1;\n\n}";
+        let lines = wrap_reference(code, 12);
+        assert_eq!(lines, vec!["fn main() {\n", "    let x = ", "1;\n", "\n", "}"]);
+        assert_eq!(lines.concat(), code);
+
+        // Words longer than a line are cut
+        assert_eq!(wrap_reference("a abcdefgh", 4), vec!["a ", "abcd", "efgh"]);
+    }
+
     #[test]
     fn test_reference_text_structure() {
         let word_list = read_first_n_words(500, Language::English);
         
         // Test that reference text structure is correct for word completion logic
         for &batch_size in &[5, 10, 25] {
-            let reference = get_reference(false, false, &word_list, batch_size);
+            let reference = get_reference(false, false, &word_list, batch_size, &WordSampler::default());
             
             // Reference should not end with a space (important for completion logic)
             assert!(!reference.ends_with(' '), 
@@ -287,7 +333,7 @@ mod tests {
             for &(punctuation, digits) in &test_configs {
                 // Run multiple times to catch random variations
                 for iteration in 0..10 {
-                    let reference = get_reference(punctuation, digits, &word_list, batch_size);
+                    let reference = get_reference(punctuation, digits, &word_list, batch_size, &WordSampler::default());
                     let actual_word_count = reference.split_whitespace().count();
                     
                     assert_eq!(
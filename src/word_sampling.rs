@@ -0,0 +1,224 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+
+use rand::Rng;
+use rand::prelude::IndexedRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::language::Language;
+
+/// Windows offered by the settings for the no repeats constraint, 0 turns it off
+pub const NO_REPEAT_PRESETS: [usize; 5] = [0, 1, 3, 5, 10];
+
+/// How words are drawn from the top words of a list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum SamplingMode {
+    /// Every word is as likely as any other
+    #[default]
+    Uniform,
+    /// Common words come up more often, by the counts of the list or by Zipf's law on the rank
+    Frequency,
+}
+
+impl SamplingMode {
+    pub fn all() -> &'static [SamplingMode] {
+        &[SamplingMode::Uniform, SamplingMode::Frequency]
+    }
+
+    pub fn count() -> usize {
+        Self::all().len()
+    }
+
+    #[cfg(feature = "cli")]
+    pub fn from_str(s: &str) -> Option<SamplingMode> {
+        match s.to_lowercase().as_str() {
+            "uniform" | "random" => Some(SamplingMode::Uniform),
+            "frequency" | "weighted" | "zipf" => Some(SamplingMode::Frequency),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> SamplingMode {
+        let all = Self::all();
+        let i = all.iter().position(|mode| mode == self).unwrap_or(0);
+        all[(i + 1) % all.len()]
+    }
+}
+
+impl Display for SamplingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SamplingMode::Uniform => write!(f, "Uniform"),
+            SamplingMode::Frequency => write!(f, "Frequency"),
+        }
+    }
+}
+
+/// Next window in `NO_REPEAT_PRESETS`, wrapping back to off
+pub fn next_no_repeat(window: usize) -> usize {
+    NO_REPEAT_PRESETS
+        .iter()
+        .copied()
+        .find(|preset| *preset > window)
+        .unwrap_or(NO_REPEAT_PRESETS[0])
+}
+
+/// Draws the words of a test from a word list ordered by frequency.
+///
+/// The last words drawn are remembered, so a sampler kept for the whole test
+/// doesn't repeat a word across the end of one batch and the start of the next.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WordSampler {
+    pub mode: SamplingMode,
+    /// A word can't come back within this many words, 1 only forbids immediate repeats
+    pub no_repeat_within: usize,
+    pub language: Language,
+    /// Occurrence counts of the word list, empty when it only has a rank
+    pub counts: HashMap<String, u64>,
+    /// Up to `no_repeat_within` words drawn last, the newest at the back
+    recent: RefCell<VecDeque<String>>,
+}
+
+impl WordSampler {
+    pub fn new(mode: SamplingMode, no_repeat_within: usize, language: Language) -> Self {
+        let counts = match mode {
+            SamplingMode::Frequency => language.source().counts(),
+            SamplingMode::Uniform => HashMap::new(),
+        };
+        Self { mode, no_repeat_within, language, counts, recent: RefCell::default() }
+    }
+
+    /// Rebuilds the sampler when a setting changed, keeps the recent words otherwise
+    pub fn update(&mut self, mode: SamplingMode, no_repeat_within: usize, language: Language) {
+        if (self.mode, self.no_repeat_within, self.language) != (mode, no_repeat_within, language) {
+            *self = Self::new(mode, no_repeat_within, language);
+        }
+    }
+
+    /// Weight of every word as its share of the list: its count over the total count
+    /// of the list when it has one, its share under Zipf's law on the rank otherwise
+    pub fn weights(&self, word_list: &[String]) -> Vec<f64> {
+        if self.mode == SamplingMode::Uniform {
+            return vec![1.0; word_list.len()];
+        }
+        let total: u64 = word_list.iter().filter_map(|word| self.counts.get(word)).sum();
+        let harmonic: f64 = (1..=word_list.len()).map(|rank| 1.0 / rank as f64).sum();
+        word_list
+            .iter()
+            .enumerate()
+            .map(|(rank, word)| match self.counts.get(word) {
+                Some(count) if total > 0 => *count as f64 / total as f64,
+                _ => 1.0 / (rank + 1) as f64 / harmonic,
+            })
+            .collect()
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, word_list: &[String], n: usize, rng: &mut R) -> Vec<String> {
+        if self.mode == SamplingMode::Uniform && self.no_repeat_within == 0 {
+            return (0..n).filter_map(|_| word_list.choose(rng).cloned()).collect();
+        }
+
+        let weights = self.weights(word_list);
+        // With only k different words a window of k or more can't be filled
+        let distinct = word_list.iter().collect::<HashSet<_>>().len();
+        let window = self.no_repeat_within.min(distinct.saturating_sub(1));
+
+        let mut recent = self.recent.borrow_mut();
+        let mut words: Vec<String> = Vec::with_capacity(n);
+        for _ in 0..n {
+            let allowed: Vec<usize> = (0..word_list.len())
+                .filter(|i| !recent.iter().rev().take(window).any(|word| *word == word_list[*i]))
+                .collect();
+            let picked = allowed
+                .choose_weighted(rng, |i| weights[*i])
+                .ok()
+                .or_else(|| allowed.choose(rng));
+            if let Some(i) = picked {
+                recent.push_back(word_list[*i].clone());
+                if recent.len() > self.no_repeat_within {
+                    recent.pop_front();
+                }
+                words.push(word_list[*i].clone());
+            }
+        }
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(words: &[&str]) -> Vec<String> {
+        words.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_frequency_weights() {
+        let words = list(&["the", "of", "and", "to"]);
+        let mut sampler = WordSampler { mode: SamplingMode::Frequency, ..Default::default() };
+        // Zipf shares: 1 / rank over the 4th harmonic number, 25/12
+        let zipf = [0.48, 0.24, 0.16, 0.12];
+        let close = |weights: &[f64], expected: &[f64]| weights.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-9);
+        assert!(close(&sampler.weights(&words), &zipf));
+
+        // Counted words get their share of the counts, on the same scale as the rest
+        sampler.counts = HashMap::from([("the".to_string(), 50), ("of".to_string(), 30)]);
+        assert!(close(&sampler.weights(&words), &[0.625, 0.375, 0.16, 0.12]));
+
+        // "the" is 4 times as likely as "to" so it should win most draws
+        let drawn = WordSampler { mode: SamplingMode::Frequency, ..Default::default() }
+            .sample(&words, 1000, &mut rand::rng());
+        let the = drawn.iter().filter(|w| *w == "the").count();
+        let to = drawn.iter().filter(|w| *w == "to").count();
+        assert!(the > to, "the: {}, to: {}", the, to);
+    }
+
+    #[test]
+    fn test_no_repeats_within_window() {
+        let words = list(&["a", "b", "c", "d", "e"]);
+        for mode in SamplingMode::all() {
+            let sampler = WordSampler { mode: *mode, no_repeat_within: 3, ..Default::default() };
+            let drawn = sampler.sample(&words, 200, &mut rand::rng());
+            assert_eq!(drawn.len(), 200);
+            for window in drawn.windows(4) {
+                let distinct: HashSet<_> = window.iter().collect();
+                assert_eq!(distinct.len(), 4, "{:?}", window);
+            }
+        }
+
+        // Two words can only alternate, whatever the window
+        let sampler = WordSampler { no_repeat_within: 10, ..Default::default() };
+        let drawn = sampler.sample(&list(&["x", "y"]), 20, &mut rand::rng());
+        assert!(drawn.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn test_no_repeats_across_batches() {
+        let words = list(&["a", "b", "c", "d", "e"]);
+        let sampler = WordSampler { no_repeat_within: 3, ..Default::default() };
+        let mut drawn = Vec::new();
+        for _ in 0..50 {
+            drawn.extend(sampler.sample(&words, 2, &mut rand::rng()));
+        }
+        for window in drawn.windows(4) {
+            let distinct: HashSet<_> = window.iter().collect();
+            assert_eq!(distinct.len(), 4, "{:?}", window);
+        }
+
+        // The same settings keep the recent words, new ones start over
+        let mut sampler = sampler;
+        sampler.update(SamplingMode::Uniform, 3, Language::English);
+        assert_eq!(sampler.recent.borrow().len(), 3);
+        sampler.update(SamplingMode::Frequency, 3, Language::English);
+        assert!(sampler.recent.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_next_no_repeat_wraps() {
+        assert_eq!(next_no_repeat(0), 1);
+        assert_eq!(next_no_repeat(10), 0);
+        assert_eq!(next_no_repeat(4), 5);
+    }
+}
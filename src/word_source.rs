@@ -0,0 +1,354 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::leaderboard::{get_config_dir, LeaderboardError};
+use crate::Quote;
+
+/// A list of words tests are generated from
+pub trait WordSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Every word of the list, most common first when `frequency_ordered` is true
+    fn words(&self) -> Vec<String>;
+
+    /// The list is sorted by how common the words are, so the top n words are the n first ones
+    fn frequency_ordered(&self) -> bool {
+        true
+    }
+
+    /// Words are written right to left
+    fn right_to_left(&self) -> bool {
+        false
+    }
+
+    /// How often each word occurs, empty when the list only has a frequency order
+    fn counts(&self) -> HashMap<String, u64> {
+        HashMap::new()
+    }
+
+    /// Quotes for quote mode, the English ones are used when there are none
+    fn quotes(&self) -> Vec<Quote> {
+        Vec::new()
+    }
+
+    /// Summaries for wiki mode, the English ones are used when there are none
+    fn summaries(&self) -> Vec<WikiSummary> {
+        Vec::new()
+    }
+
+    /// Practice levels for the letters of the language, after the standard ones
+    fn lessons(&self) -> Vec<Lesson> {
+        Vec::new()
+    }
+
+    /// Name of the keyboard layout the language is usually typed on
+    fn layout_hint(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Encyclopedia article summary typed in wiki mode
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WikiSummary {
+    pub title: String,
+    pub summary: String,
+}
+
+/// Practice level of a language pack, e.g. "new: ä, ö & ü"
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Lesson {
+    pub name: String,
+    /// Keys the words of the level are made of
+    pub keys: String,
+}
+
+/// Word list embedded in the binary, one word per line, most common first,
+/// with its quotes and wiki summaries as JSON
+pub struct BuiltinWordList {
+    pub name: &'static str,
+    pub text: &'static str,
+    pub quotes: &'static str,
+    pub summaries: &'static str,
+}
+
+impl WordSource for BuiltinWordList {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn words(&self) -> Vec<String> {
+        self.text.lines().map(|s| s.trim().to_string()).collect()
+    }
+
+    fn quotes(&self) -> Vec<Quote> {
+        serde_json::from_str(self.quotes).unwrap_or_default()
+    }
+
+    fn summaries(&self) -> Vec<WikiSummary> {
+        serde_json::from_str(self.summaries).unwrap_or_default()
+    }
+}
+
+pub const BUILTIN_WORD_LISTS: [BuiltinWordList; 3] = [
+    BuiltinWordList {
+        name: "English",
+        text: include_str!("../assets/common_eng_words.txt"),
+        quotes: include_str!("../assets/quotes.json"),
+        summaries: include_str!("../assets/featured_summaries.json"),
+    },
+    BuiltinWordList {
+        name: "Indonesian",
+        text: include_str!("../assets/common_ind_words.txt"),
+        quotes: include_str!("../assets/quotes_ind.json"),
+        summaries: include_str!("../assets/summaries_ind.json"),
+    },
+    BuiltinWordList {
+        name: "Italian",
+        text: include_str!("../assets/common_ita_words.txt"),
+        quotes: include_str!("../assets/quotes_ita.json"),
+        summaries: include_str!("../assets/summaries_ita.json"),
+    },
+];
+
+/// Language packs shipped with typeman, in the same format as the user ones
+pub const BUNDLED_PACKS: [&str; 7] = [
+    include_str!("../assets/languages/german.json"),
+    include_str!("../assets/languages/french.json"),
+    include_str!("../assets/languages/spanish.json"),
+    include_str!("../assets/languages/portuguese.json"),
+    include_str!("../assets/languages/polish.json"),
+    include_str!("../assets/languages/russian.json"),
+    include_str!("../assets/languages/code.json"),
+];
+
+/// Language pack, bundled or from `~/.config/typeman/languages/`. A `.txt` file has one
+/// word per line, optionally followed by its count, and is named after the file. A `.json`
+/// file also carries the metadata, quotes, wiki summaries, practice lessons and layout hint.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct LanguagePack {
+    pub name: String,
+    pub words: Vec<String>,
+    #[serde(default = "default_frequency_ordered")]
+    pub frequency_ordered: bool,
+    #[serde(default)]
+    pub right_to_left: bool,
+    /// Occurrence count of the word at the same index in `words`
+    #[serde(default)]
+    pub counts: Vec<u64>,
+    #[serde(default)]
+    pub quotes: Vec<Quote>,
+    #[serde(default)]
+    pub summaries: Vec<WikiSummary>,
+    #[serde(default)]
+    pub lessons: Vec<Lesson>,
+    /// Name of a keyboard layout, e.g. "QWERTZ"
+    #[serde(default)]
+    pub layout: Option<String>,
+}
+
+fn default_frequency_ordered() -> bool {
+    true
+}
+
+impl WordSource for LanguagePack {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn words(&self) -> Vec<String> {
+        self.words.clone()
+    }
+
+    fn frequency_ordered(&self) -> bool {
+        self.frequency_ordered
+    }
+
+    fn right_to_left(&self) -> bool {
+        self.right_to_left
+    }
+
+    fn counts(&self) -> HashMap<String, u64> {
+        self.words.iter().cloned().zip(self.counts.iter().copied()).collect()
+    }
+
+    fn quotes(&self) -> Vec<Quote> {
+        self.quotes.clone()
+    }
+
+    fn summaries(&self) -> Vec<WikiSummary> {
+        self.summaries.clone()
+    }
+
+    fn lessons(&self) -> Vec<Lesson> {
+        self.lessons.clone()
+    }
+
+    fn layout_hint(&self) -> Option<&str> {
+        self.layout.as_deref()
+    }
+}
+
+impl LanguagePack {
+    pub fn load(path: &Path) -> Result<Self, LeaderboardError> {
+        let content = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            return Ok(serde_json::from_str(&content)?);
+        }
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut words = Vec::new();
+        let mut counts = Vec::new();
+        for line in content.lines().map(str::trim).filter(|s| !s.is_empty()) {
+            // "word 1234" lines carry a count, they only count if every line has one
+            match line.split_once(char::is_whitespace) {
+                Some((word, count)) if count.trim().parse::<u64>().is_ok() => {
+                    words.push(word.to_string());
+                    counts.push(count.trim().parse().unwrap_or(0));
+                }
+                _ => words.push(line.to_string()),
+            }
+        }
+        if counts.len() != words.len() {
+            counts.clear();
+        }
+        Ok(Self {
+            name,
+            words,
+            frequency_ordered: true,
+            right_to_left: false,
+            counts,
+            quotes: Vec::new(),
+            summaries: Vec::new(),
+            lessons: Vec::new(),
+            layout: None,
+        })
+    }
+}
+
+/// Every word source, the built-in lists and bundled packs first
+pub struct WordSourceRegistry {
+    sources: Vec<Box<dyn WordSource>>,
+}
+
+impl WordSourceRegistry {
+    pub fn with_builtins() -> Self {
+        let mut registry = Self { sources: Vec::new() };
+        for list in BUILTIN_WORD_LISTS {
+            registry.register(Box::new(list));
+        }
+        for pack in BUNDLED_PACKS {
+            if let Ok(pack) = serde_json::from_str::<LanguagePack>(pack) {
+                registry.register(Box::new(pack));
+            }
+        }
+        registry
+    }
+
+    /// Built-in lists and bundled packs plus the user packs in the languages directory
+    pub fn load() -> Self {
+        let mut registry = Self::with_builtins();
+        if let Ok(paths) = list_user_sources() {
+            for path in paths {
+                if let Ok(pack) = LanguagePack::load(&path) {
+                    registry.register(Box::new(pack));
+                }
+            }
+        }
+        registry
+    }
+
+    /// Adds a source, skipping empty ones and names that are already taken
+    pub fn register(&mut self, source: Box<dyn WordSource>) {
+        if source.name().is_empty() || source.words().is_empty() || self.get(source.name()).is_some() {
+            return;
+        }
+        self.sources.push(source);
+    }
+
+    /// Source with the given name, ignoring case
+    pub fn get(&self, name: &str) -> Option<&dyn WordSource> {
+        self.sources
+            .iter()
+            .find(|source| source.name().eq_ignore_ascii_case(name))
+            .map(|source| source.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.sources.iter().map(|source| source.name()).collect()
+    }
+}
+
+/// Sources loaded on first use, shared for the rest of the run
+pub fn registry() -> &'static WordSourceRegistry {
+    static REGISTRY: OnceLock<WordSourceRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(WordSourceRegistry::load)
+}
+
+pub fn get_languages_dir() -> Result<PathBuf, LeaderboardError> {
+    let config_dir = get_config_dir().map_err(|e| LeaderboardError::IoError(
+        std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+    ))?;
+    let languages_dir = config_dir.join("languages");
+    fs::create_dir_all(&languages_dir)?;
+    Ok(languages_dir)
+}
+
+/// `.txt` and `.json` files in the languages directory, sorted by name
+pub fn list_user_sources() -> Result<Vec<PathBuf>, LeaderboardError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(get_languages_dir()?)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt" || ext == "json"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(json: &str) -> Box<LanguagePack> {
+        Box::new(serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn test_registry_skips_duplicates() {
+        let mut registry = WordSourceRegistry::with_builtins();
+        registry.register(pack(r#"{"name": "english", "words": ["duplicate"]}"#));
+        registry.register(pack(r#"{"name": "Hebrew", "words": ["שלום"], "frequency_ordered": false, "right_to_left": true, "counts": [3]}"#));
+        assert_eq!(
+            registry.names(),
+            vec!["English", "Indonesian", "Italian", "German", "French", "Spanish", "Portuguese", "Polish", "Russian", "Code", "Hebrew"]
+        );
+        assert_eq!(registry.get("ENGLISH").map(|s| s.words()[0].clone()), Some("as".to_string()));
+        assert!(registry.get("hebrew").is_some_and(|s| s.right_to_left() && !s.frequency_ordered()));
+        assert_eq!(registry.get("hebrew").map(|s| s.counts().get("שלום").copied()), Some(Some(3)));
+    }
+
+    #[test]
+    fn test_user_list_metadata_defaults() {
+        let list: LanguagePack = serde_json::from_str(r#"{"name": "Mine", "words": ["a", "b"]}"#).unwrap();
+        assert!(list.frequency_ordered);
+        assert!(!list.right_to_left);
+        assert!(list.quotes.is_empty() && list.lessons.is_empty() && list.layout.is_none());
+    }
+
+    #[test]
+    fn test_bundled_packs_are_complete() {
+        for json in BUNDLED_PACKS {
+            let pack: LanguagePack = serde_json::from_str(json).unwrap();
+            assert!(pack.words.len() >= 100, "{} has too few words", pack.name);
+            assert!(!pack.quotes.is_empty(), "{} has no quotes", pack.name);
+            assert!(!pack.lessons.is_empty(), "{} has no lessons", pack.name);
+            assert!(pack.lessons.iter().all(|lesson| !lesson.keys.is_empty()));
+        }
+        for list in BUILTIN_WORD_LISTS {
+            assert!(!list.quotes().is_empty() && !list.summaries().is_empty(), "{} lacks quotes", list.name);
+        }
+    }
+}
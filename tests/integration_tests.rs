@@ -3,10 +3,11 @@
 #[test]
 fn test_leaderboard_data_structures() {
     // Test that leaderboard data structures work correctly
-    use typeman::leaderboard::{LeaderboardEntry, TestType};
+    use typeman::leaderboard::{LeaderboardEntry, TestType, ENTRY_VERSION};
     use typeman::language::Language;
     
     let entry = LeaderboardEntry {
+        version: ENTRY_VERSION,
         wpm: 85.5,
         accuracy: 98.2,
         test_type: TestType::Time(30),
@@ -15,6 +16,14 @@ fn test_leaderboard_data_structures() {
         test_duration: 30.0,
         timestamp: "2025-09-11T10:30:00Z".to_string(),
         language: Language::English,
+        quote: None,
+        punctuation: false,
+        digits: false,
+        top_words: Some(500),
+        batch_size: Some(50),
+        raw_wpm: Some(90.0),
+        consistency: Some(80.0),
+        errors: Some(3),
     };
     
     // Verify the entry was created successfully
@@ -26,10 +35,13 @@ fn test_leaderboard_data_structures() {
 #[test]
 fn test_leaderboard_storage() {
     // Test that leaderboard storage and retrieval works
-    use typeman::leaderboard::{save_entry, load_entries, LeaderboardEntry, TestType};
+    use typeman::leaderboard::{save_entry, load_entries, LeaderboardEntry, TestType, ENTRY_VERSION};
     use typeman::language::Language;
+
+    let config_dir = use_temp_config_dir();
     
     let entry = LeaderboardEntry {
+        version: ENTRY_VERSION,
         wpm: 75.0,
         accuracy: 95.0,
         test_type: TestType::Word(50),
@@ -38,18 +50,28 @@ fn test_leaderboard_storage() {
         test_duration: 45.0,
         timestamp: "2025-09-11T11:00:00Z".to_string(),
         language: Language::English,
+        quote: None,
+        punctuation: false,
+        digits: false,
+        top_words: Some(500),
+        batch_size: Some(50),
+        raw_wpm: Some(90.0),
+        consistency: Some(80.0),
+        errors: Some(3),
     };
     
     // Should be able to save and load entries
     assert!(save_entry(&entry).is_ok());
     assert!(load_entries().is_ok());
+    assert!(config_dir.join("history.jsonl").exists());
 }
 
 #[test]
 fn test_app_state_integration() {
     // Test that the App struct includes leaderboard fields
     use typeman::ui::tui::app::App;
-    
+
+    use_temp_config_dir();
     let app = App::new();
     
     // Verify leaderboard fields exist and are initialized
@@ -74,4 +96,17 @@ fn test_test_type_variants() {
     assert!(matches!(word_test, TestType::Word(100)));
     assert!(matches!(quote_test, TestType::Quote));
     assert!(matches!(practice_test, TestType::Practice(5)));
-}
\ No newline at end of file
+}
+
+/// Points the config directory at a temporary one, so the tests never touch the real files.
+/// Every test that reads or writes the config directory calls this first.
+fn use_temp_config_dir() -> std::path::PathBuf {
+    use std::sync::Once;
+    use typeman::leaderboard::CONFIG_DIR_VAR;
+
+    static SET: Once = Once::new();
+    let config_dir = std::env::temp_dir().join(format!("typeman-integration-{}", std::process::id()));
+    // SAFETY: tests only read the environment after this has run, Once makes the others wait
+    SET.call_once(|| unsafe { std::env::set_var(CONFIG_DIR_VAR, &config_dir) });
+    config_dir
+}